@@ -6,7 +6,7 @@ use emjudge_judgecore::{
     quantity::{MemorySize, TimeSpan},
     result::OnlyRunResult,
     settings::create_a_tmp_user_return_uid,
-    test::OnlyRun,
+    test::{OnlyRun, ProgramLimits, RunOptions},
 };
 
 fn mle(c: &mut Criterion) {
@@ -38,11 +38,14 @@ fn mle(c: &mut Criterion) {
                             &script,
                             compile_and_exe_settings.get_language("C++").unwrap(),
                         ),
-                        TimeSpan::from_seconds(1),
-                        MemorySize::from_megabytes(128),
-                        code_uid,
+                        &ProgramLimits::new(
+                            TimeSpan::from_seconds(1),
+                            MemorySize::from_megabytes(128),
+                            256,
+                            code_uid,
+                        ),
                         &input,
-                        MemorySize::from_megabytes(10),
+                        &RunOptions::new(MemorySize::from_megabytes(10)),
                     )
                     .await;
                     for i in result {
@@ -88,17 +91,20 @@ fn tle(c: &mut Criterion) {
                             &script,
                             compile_and_exe_settings.get_language("C++").unwrap(),
                         ),
-                        TimeSpan::from_seconds(1),
-                        MemorySize::from_megabytes(128),
-                        code_uid,
+                        &ProgramLimits::new(
+                            TimeSpan::from_seconds(1),
+                            MemorySize::from_megabytes(128),
+                            256,
+                            code_uid,
+                        ),
                         &input,
-                        MemorySize::from_megabytes(10),
+                        &RunOptions::new(MemorySize::from_megabytes(10)),
                     )
                     .await;
 
                     for i in result {
                         match i {
-                            OnlyRunResult::TimeLimitExceeded(_) => {}
+                            OnlyRunResult::TimeLimitExceeded(_, _) => {}
                             i => {
                                 panic!("Unexpected result: {}", i);
                             }
@@ -139,11 +145,14 @@ fn just_return(c: &mut Criterion) {
                             &script,
                             compile_and_exe_settings.get_language("C++").unwrap(),
                         ),
-                        TimeSpan::from_seconds(1),
-                        MemorySize::from_megabytes(128),
-                        code_uid,
+                        &ProgramLimits::new(
+                            TimeSpan::from_seconds(1),
+                            MemorySize::from_megabytes(128),
+                            256,
+                            code_uid,
+                        ),
                         &input,
-                        MemorySize::from_megabytes(10),
+                        &RunOptions::new(MemorySize::from_megabytes(10)),
                     )
                     .await;
                     for i in result {
@@ -189,11 +198,14 @@ fn large_printf(c: &mut Criterion) {
                             &script,
                             compile_and_exe_settings.get_language("C++").unwrap(),
                         ),
-                        TimeSpan::from_seconds(1),
-                        MemorySize::from_megabytes(128),
-                        code_uid,
+                        &ProgramLimits::new(
+                            TimeSpan::from_seconds(1),
+                            MemorySize::from_megabytes(128),
+                            256,
+                            code_uid,
+                        ),
                         &input,
-                        MemorySize::from_megabytes(1),
+                        &RunOptions::new(MemorySize::from_megabytes(1)),
                     )
                     .await;
                     for i in result {
@@ -6,7 +6,7 @@ use emjudge_judgecore::{
     quantity::{MemorySize, TimeSpan},
     result::RunAndInteractResult,
     settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
-    test::RunAndInteract,
+    test::{ProgramLimits, RunAndInteract, RunOptions},
 };
 
 fn guessnumber(c: &mut Criterion) {
@@ -55,25 +55,33 @@ fn guessnumber(c: &mut Criterion) {
                             &tested_script,
                             compile_and_exe_settings.get_language("C++").unwrap(),
                         ),
-                        TimeSpan::from_milliseconds(1000),
-                        MemorySize::from_megabytes(256),
-                        tested_uid,
+                        &ProgramLimits::new(
+                            TimeSpan::from_milliseconds(1000),
+                            MemorySize::from_megabytes(256),
+                            256,
+                            tested_uid,
+                        ),
                         &RawCode::new(
                             &interactor_script,
                             compile_and_exe_settings.get_language("C++").unwrap(),
                         ),
-                        TimeSpan::from_milliseconds(1000),
-                        MemorySize::from_megabytes(256),
-                        interactor_uid,
+                        &ProgramLimits::new(
+                            TimeSpan::from_milliseconds(1000),
+                            MemorySize::from_megabytes(256),
+                            256,
+                            interactor_uid,
+                        ),
                         &inputs,
-                        MemorySize::from_megabytes(10),
+                        None,
+                        &RunOptions::new(MemorySize::from_megabytes(10)),
                     )
                     .await;
                     let mut ith = 1;
                     for i in result {
                         ith = ith + 1;
                         match i {
-                            RunAndInteractResult::Ok(_, result) => {
+                            RunAndInteractResult::Ok(_, result, verdict) => {
+                                assert!(verdict.is_accepted());
                                 assert_eq!(
                                     result.stdout,
                                     format!("AC with {} steps\n", ith).as_bytes().to_vec()
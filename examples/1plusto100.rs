@@ -1,7 +1,10 @@
 use emjudge_judgecore::{
     quantity::{MemorySize, TimeSpan},
     settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
-    {program::RawCode, test::AnsAndEval},
+    {
+        program::RawCode,
+        test::{AnsAndEval, ProgramLimits, RunOptions},
+    },
 };
 use tokio::io::AsyncReadExt;
 
@@ -41,12 +44,15 @@ async fn main() {
             &eval_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &tested_ans,
         &std_ans,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
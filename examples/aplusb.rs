@@ -1,7 +1,10 @@
 use emjudge_judgecore::{
     quantity::{MemorySize, TimeSpan},
     settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
-    {program::RawCode, test::RunAndEval},
+    {
+        program::RawCode,
+        test::{ProgramLimits, RunAndEval, RunOptions},
+    },
 };
 use tokio::io::AsyncReadExt;
 
@@ -47,19 +50,25 @@ async fn main() {
             &tested_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        tested_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            tested_uid,
+        ),
         &RawCode::new(
             &eval_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        eval_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            eval_uid,
+        ),
         &input,
         &output,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
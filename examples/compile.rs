@@ -1,4 +1,8 @@
-use emjudge_judgecore::{program::RawCode, settings::CompileAndExeSettings};
+use emjudge_judgecore::{
+    program::RawCode,
+    quantity::{MemorySize, TimeSpan},
+    settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
+};
 use tokio::io::AsyncReadExt;
 
 #[tokio::main(flavor = "current_thread")]
@@ -8,6 +12,7 @@ async fn main() {
         config::FileFormat::Toml,
     )
     .unwrap();
+    let compile_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
     let mut code = vec![];
     tokio::fs::File::open("examples/programs/compile_error.cpp")
         .await
@@ -19,7 +24,13 @@ async fn main() {
     println!(
         "Result: {}",
         RawCode::new(&code, compile_and_exe_settings.get_language("C++").unwrap())
-            .compile()
+            .compile(
+                compile_uid,
+                TimeSpan::from_seconds(10),
+                MemorySize::from_gigabytes(1),
+                256,
+                MemorySize::from_megabytes(1),
+            )
             .await
     );
 
@@ -34,7 +45,13 @@ async fn main() {
     println!(
         "Result: {}",
         RawCode::new(&code, compile_and_exe_settings.get_language("C++").unwrap())
-            .compile()
+            .compile(
+                compile_uid,
+                TimeSpan::from_seconds(10),
+                MemorySize::from_gigabytes(1),
+                256,
+                MemorySize::from_megabytes(1),
+            )
             .await
     );
 }
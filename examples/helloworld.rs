@@ -1,7 +1,10 @@
 use emjudge_judgecore::{
     quantity::{MemorySize, TimeSpan},
     settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
-    {program::RawCode, test::OnlyRun},
+    {
+        program::RawCode,
+        test::{OnlyRun, ProgramLimits, RunOptions},
+    },
 };
 use tokio::io::AsyncReadExt;
 
@@ -56,11 +59,14 @@ async fn test_cpp() {
             &script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -83,11 +89,14 @@ async fn test_c() {
         .unwrap();
     let result = OnlyRun::single(
         &RawCode::new(&script, compile_and_exe_settings.get_language("C").unwrap()),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -113,11 +122,14 @@ async fn test_java() {
             &script,
             compile_and_exe_settings.get_language("Java").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -143,11 +155,14 @@ async fn test_python3() {
             &script,
             compile_and_exe_settings.get_language("Python 3").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -173,11 +188,14 @@ async fn test_pypy3() {
             &script,
             compile_and_exe_settings.get_language("Pypy 3").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -203,11 +221,14 @@ async fn test_python2() {
             &script,
             compile_and_exe_settings.get_language("Python 2").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -233,11 +254,14 @@ async fn test_ruby() {
             &script,
             compile_and_exe_settings.get_language("Ruby").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -263,11 +287,14 @@ async fn test_perl() {
             &script,
             compile_and_exe_settings.get_language("Perl").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -293,11 +320,14 @@ async fn test_cs() {
             &script,
             compile_and_exe_settings.get_language("C#").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -323,11 +353,14 @@ async fn test_cs() {
 //             &script,
 //             compile_and_exe_settings.get_language("Swift").unwrap(),
 //         ),
-//         TimeSpan::from_seconds(1),
-//         MemorySize::from_gigabytes(1),
-//         code_uid,
+//         &ProgramLimits::new(
+//             TimeSpan::from_seconds(1),
+//             MemorySize::from_gigabytes(1),
+//             256,
+//             code_uid,
+//         ),
 //         &vec![],
-//         MemorySize::from_megabytes(10),
+//         &RunOptions::new(MemorySize::from_megabytes(10)),
 //     )
 //     .await;
 //     println!("Result: {}", result);
@@ -353,11 +386,14 @@ async fn test_go() {
             &script,
             compile_and_exe_settings.get_language("Go").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -383,11 +419,14 @@ async fn test_javascript() {
             &script,
             compile_and_exe_settings.get_language("Javascript").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -413,11 +452,14 @@ async fn test_rust() {
             &script,
             compile_and_exe_settings.get_language("Rust").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -443,11 +485,14 @@ async fn test_rust() {
 //             &script,
 //             compile_and_exe_settings.get_language("Kotlin").unwrap(),
 //         ),
-//         TimeSpan::from_seconds(1),
-//         MemorySize::from_gigabytes(1),
-//         code_uid,
+//         &ProgramLimits::new(
+//             TimeSpan::from_seconds(1),
+//             MemorySize::from_gigabytes(1),
+//             256,
+//             code_uid,
+//         ),
 //         &vec![],
-//         MemorySize::from_megabytes(10),
+//         &RunOptions::new(MemorySize::from_megabytes(10)),
 //     )
 //     .await;
 //     println!("Result: {}", result);
@@ -473,11 +518,14 @@ async fn test_julia() {
             &script,
             compile_and_exe_settings.get_language("Julia").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -503,11 +551,14 @@ async fn test_fortran() {
             &script,
             compile_and_exe_settings.get_language("Fortran").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -533,11 +584,14 @@ async fn test_lua() {
             &script,
             compile_and_exe_settings.get_language("Lua").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -563,11 +617,14 @@ async fn test_php() {
             &script,
             compile_and_exe_settings.get_language("PHP").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -593,11 +650,14 @@ async fn test_smalltalk() {
             &script,
             compile_and_exe_settings.get_language("Smalltalk").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -623,11 +683,14 @@ async fn test_ocaml() {
             &script,
             compile_and_exe_settings.get_language("OCaml").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -653,11 +716,14 @@ async fn test_cobol() {
             &script,
             compile_and_exe_settings.get_language("COBOL").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -683,11 +749,14 @@ async fn test_ada() {
             &script,
             compile_and_exe_settings.get_language("Ada").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -715,11 +784,14 @@ async fn test_common_lisp() {
                 .get_language("Common LISP")
                 .unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -745,11 +817,14 @@ async fn test_scala() {
             &script,
             compile_and_exe_settings.get_language("Scala").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -775,11 +850,14 @@ async fn test_tcl() {
             &script,
             compile_and_exe_settings.get_language("Tcl").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -805,11 +883,14 @@ async fn test_octave() {
             &script,
             compile_and_exe_settings.get_language("Octave").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
@@ -835,11 +916,14 @@ async fn test_pypy2() {
             &script,
             compile_and_exe_settings.get_language("Pypy 2").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
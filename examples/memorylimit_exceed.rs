@@ -1,7 +1,10 @@
 use emjudge_judgecore::{
     quantity::{MemorySize, TimeSpan},
     settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
-    {program::RawCode, test::OnlyRun},
+    {
+        program::RawCode,
+        test::{OnlyRun, ProgramLimits, RunOptions},
+    },
 };
 use tokio::io::AsyncReadExt;
 
@@ -25,11 +28,14 @@ async fn main() {
             &script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_megabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_megabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(1),
+        &RunOptions::new(MemorySize::from_megabytes(1)),
     )
     .await;
     println!("Result: {}", result);
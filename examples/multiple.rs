@@ -3,7 +3,7 @@ use emjudge_judgecore::{
     settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
     {
         program::RawCode,
-        test::{AnsAndEval, OnlyRun, RunAndEval, RunAndInteract},
+        test::{AnsAndEval, OnlyRun, ProgramLimits, RunAndEval, RunAndInteract, RunOptions},
     },
 };
 use tokio::io::AsyncReadExt;
@@ -44,12 +44,15 @@ async fn test_the_same(compile_and_exe_settings: &CompileAndExeSettings) {
             &eval_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        eval_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            eval_uid,
+        ),
         &tested_anses,
         &std_anses,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Results:");
@@ -88,19 +91,25 @@ async fn test_aplusb(compile_and_exe_settings: &CompileAndExeSettings) {
             &tested_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &RawCode::new(
             &eval_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        eval_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            eval_uid,
+        ),
         &inputs,
         &outputs,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Results:");
@@ -137,18 +146,25 @@ async fn test_guess_number(compile_and_exe_settings: &CompileAndExeSettings) {
             &tested_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &RawCode::new(
             &interactor_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_gigabytes(1),
-        interarctor_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            interarctor_uid,
+        ),
         &inputs,
-        MemorySize::from_megabytes(10),
+        None,
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Results:");
@@ -174,11 +190,14 @@ async fn test_mle(compile_and_exe_settings: &CompileAndExeSettings) {
             &tested_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_seconds(1),
-        MemorySize::from_megabytes(512),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_megabytes(512),
+            256,
+            code_uid,
+        ),
         &inputs,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Results:");
@@ -204,11 +223,14 @@ async fn test_tle(compile_and_exe_settings: &CompileAndExeSettings) {
             &tested_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_milliseconds(100),
-        MemorySize::from_megabytes(512),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(100),
+            MemorySize::from_megabytes(512),
+            256,
+            code_uid,
+        ),
         &inputs,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Results:");
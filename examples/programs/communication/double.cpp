@@ -0,0 +1,7 @@
+#include <cstdio>
+int main() {
+    long long x;
+    scanf("%lld", &x);
+    printf("%lld\n", 2 * x);
+    return 0;
+}
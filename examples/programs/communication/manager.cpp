@@ -0,0 +1,26 @@
+#include <cstdio>
+#include <vector>
+using namespace std;
+int main(int argc, char **argv) {
+    int k = (argc - 1) / 2;
+    long long x;
+    scanf("%lld", &x);
+    vector<FILE *> from(k), to(k);
+    for (int i = 0; i < k; i++) {
+        from[i] = fopen(argv[1 + 2 * i], "r");
+        to[i] = fopen(argv[2 + 2 * i], "w");
+    }
+    for (int i = 0; i < k; i++) {
+        fprintf(to[i], "%lld\n", x + i);
+        fflush(to[i]);
+    }
+    for (int i = 0; i < k; i++) {
+        long long y;
+        if (fscanf(from[i], "%lld", &y) != 1 || y != 2 * (x + i)) {
+            printf("WA instance %d did not double %lld\n", i, x + i);
+            return 1;
+        }
+    }
+    printf("AC with %d instances\n", k);
+    return 0;
+}
@@ -0,0 +1,8 @@
+#include <cstdio>
+using namespace std;
+int main() {
+    while (true) {
+        putchar('a');
+    }
+    return 0;
+}
@@ -0,0 +1,13 @@
+#include <sys/wait.h>
+#include <unistd.h>
+int main() {
+    for (int i = 0; i < 16; i++) {
+        if (fork() == 0) {
+            sleep(1);
+            return 0;
+        }
+    }
+    while (wait(NULL) > 0) {
+    }
+    return 0;
+}
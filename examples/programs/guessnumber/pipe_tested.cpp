@@ -0,0 +1,10 @@
+#include <cstdio>
+int main() {
+    int l, r;
+    scanf("%d %d", &l, &r);
+    while (1) {
+        printf("? %d\n", l);
+        fflush(stdout);
+    }
+    return 0;
+}
@@ -0,0 +1,17 @@
+#include <cstdio>
+#include <fstream>
+using namespace std;
+int main() {
+    int l, r, x;
+    ifstream interactor_in("interactorin");
+    ofstream interactor_out("interactorout");
+    interactor_in >> l >> r >> x;
+    printf("%d %d\n", l, r);
+    fflush(stdout);
+    char c;
+    int y;
+    scanf(" %c %d", &c, &y);
+    interactor_out << "WA no guess may be at the left end\n";
+    interactor_out.close();
+    return 1;
+}
@@ -0,0 +1,10 @@
+#include <cstdio>
+
+int solve(int a, int b);
+
+int main() {
+    int a, b;
+    if (scanf("%d %d", &a, &b) != 2) return 1;
+    printf("%d\n", solve(a, b));
+    return 0;
+}
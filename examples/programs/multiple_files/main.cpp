@@ -0,0 +1,4 @@
+#include "grader.h"
+#include "lib/add.h"
+
+int solve(int a, int b) { return add(a, b); }
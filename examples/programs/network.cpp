@@ -0,0 +1,23 @@
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <sys/socket.h>
+#include <unistd.h>
+int try_connect(const char *ip, int port) {
+    int fd = socket(AF_INET, SOCK_DGRAM, 0);
+    sockaddr_in addr = {};
+    addr.sin_family = AF_INET;
+    addr.sin_port = htons(port);
+    inet_pton(AF_INET, ip, &addr.sin_addr);
+    int ret = connect(fd, (sockaddr *)&addr, sizeof(addr));
+    close(fd);
+    return ret;
+}
+int main() {
+    if (try_connect("127.0.0.1", 1) != 0) {
+        return 2;
+    }
+    if (try_connect("8.8.8.8", 53) != 0) {
+        return 1;
+    }
+    return 0;
+}
@@ -0,0 +1,8 @@
+#include <cstdio>
+#include <cstdlib>
+
+int main() {
+    const char *value = getenv("EMJUDGE_VALUE");
+    printf("%s %s\n", value ? value : "unset", getenv("EMJUDGE_SECRET") ? "leaked" : "clean");
+    return 0;
+}
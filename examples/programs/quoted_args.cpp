@@ -0,0 +1,6 @@
+#include <cstdio>
+
+int main(int argc, char **argv) {
+    printf("%s %s\n", GREETING, argc > 1 ? argv[1] : "");
+    return 0;
+}
@@ -0,0 +1,7 @@
+#include <cstdio>
+int main() {
+    if (fopen("/etc/passwd", "r") == NULL) {
+        return 1;
+    }
+    return 0;
+}
@@ -0,0 +1,23 @@
+#include <iostream>
+#include <string>
+using namespace std;
+int main() {
+    string mode, value;
+    cin >> mode >> value;
+    if (mode == "encode") {
+        long long x = stoll(value);
+        string bits;
+        do {
+            bits = char('0' + x % 2) + bits;
+            x /= 2;
+        } while (x > 0);
+        cout << bits << endl;
+    } else {
+        long long x = 0;
+        for (char c : value) {
+            x = x * 2 + (c - '0');
+        }
+        cout << x << endl;
+    }
+    return 0;
+}
@@ -0,0 +1,9 @@
+#include <iostream>
+#include <string>
+using namespace std;
+int main() {
+    string bits;
+    cin >> bits;
+    cout << "decode " << bits << endl;
+    return 0;
+}
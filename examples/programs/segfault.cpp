@@ -0,0 +1,5 @@
+#include <signal.h>
+int main() {
+    raise(SIGSEGV);
+    return 0;
+}
\ No newline at end of file
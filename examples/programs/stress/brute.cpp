@@ -0,0 +1,9 @@
+#include <cstdio>
+using namespace std;
+int main() {
+    long long a, b;
+    while (scanf("%lld %lld", &a, &b) != EOF) {
+        printf("%lld\n", a + b);
+    }
+    return 0;
+}
@@ -0,0 +1,16 @@
+#include <cstdio>
+#include <random>
+using namespace std;
+long long number(mt19937 &rng) {
+    long long limit = rng() % 2 ? 2000000000LL : 1000000000LL;
+    return rng() % limit;
+}
+int main() {
+    unsigned seed;
+    scanf("%u", &seed);
+    mt19937 rng(seed);
+    long long a = number(rng);
+    long long b = number(rng);
+    printf("%lld %lld\n", a, b);
+    return 0;
+}
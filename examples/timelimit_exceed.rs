@@ -1,7 +1,10 @@
 use emjudge_judgecore::{
     quantity::{MemorySize, TimeSpan},
     settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
-    {program::RawCode, test::OnlyRun},
+    {
+        program::RawCode,
+        test::{OnlyRun, ProgramLimits, RunOptions},
+    },
 };
 use tokio::io::AsyncReadExt;
 
@@ -25,11 +28,14 @@ async fn main() {
             &script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_milliseconds(1000),
-        MemorySize::from_gigabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_gigabytes(1),
+            256,
+            code_uid,
+        ),
         &vec![],
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     println!("Result: {}", result);
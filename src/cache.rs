@@ -0,0 +1,455 @@
+use crate::{
+    error::JudgeError,
+    program::{ExeCode, RawCode},
+    quantity::{MemorySize, TimeSpan},
+    result::CompileResult,
+    settings::CompileAndExeSetting,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone)]
+pub struct CompileCache {
+    inner: Arc<Mutex<CompileCacheInner>>,
+    dir: Option<PathBuf>,
+}
+
+struct CompileCacheInner {
+    memory_limit: usize,
+    memory_usage: usize,
+    memory: HashMap<String, MemoryEntry>,
+    disk_limit: usize,
+    disk_usage: usize,
+    disk: HashMap<String, DiskEntry>,
+    language_infos: HashMap<String, String>,
+    clock: u64,
+    hit_count: u64,
+    miss_count: u64,
+}
+
+struct MemoryEntry {
+    key_material: Vec<u8>,
+    exe_code: ExeCode,
+    size: usize,
+    last_used: u64,
+}
+
+struct DiskEntry {
+    size: usize,
+    last_used: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskManifest {
+    compile_and_exe_setting: CompileAndExeSetting,
+    exe_files: Vec<String>,
+}
+
+impl CompileCache {
+    pub fn new(memory_limit: MemorySize) -> Self {
+        CompileCache {
+            inner: Arc::new(Mutex::new(CompileCacheInner {
+                memory_limit: memory_limit.as_bytes(),
+                memory_usage: 0,
+                memory: HashMap::new(),
+                disk_limit: 0,
+                disk_usage: 0,
+                disk: HashMap::new(),
+                language_infos: HashMap::new(),
+                clock: 0,
+                hit_count: 0,
+                miss_count: 0,
+            })),
+            dir: None,
+        }
+    }
+
+    pub fn new_with_disk(
+        memory_limit: MemorySize,
+        dir: &str,
+        disk_limit: MemorySize,
+    ) -> Result<Self, JudgeError> {
+        let mut result = Self::new(memory_limit);
+        if let Err(result) = std::fs::create_dir_all(dir) {
+            return Err(JudgeError::io("create compile cache dir", result));
+        }
+        let read_dir = match std::fs::read_dir(dir) {
+            Err(result) => {
+                return Err(JudgeError::io("read compile cache dir", result));
+            }
+            Ok(result) => result,
+        };
+        {
+            let mut inner = result.inner.lock().unwrap();
+            inner.disk_limit = disk_limit.as_bytes();
+            for entry in read_dir.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                // Leftovers of an interrupted store are never renamed into place,
+                // and entries not named by a sha256 are from an older version.
+                if name.starts_with('.')
+                    || name.len() != 64
+                    || !name.bytes().all(|x| x.is_ascii_hexdigit())
+                {
+                    let _ = std::fs::remove_dir_all(entry.path());
+                    continue;
+                }
+                match scan_disk_entry(entry.path().as_path()) {
+                    None => {
+                        let _ = std::fs::remove_dir_all(entry.path());
+                    }
+                    Some(disk_entry) => {
+                        inner.disk_usage += disk_entry.size;
+                        inner.disk.insert(name, disk_entry);
+                    }
+                }
+            }
+            inner.evict_disk(dir);
+        }
+        result.dir = Some(PathBuf::from(dir));
+        Ok(result)
+    }
+
+    pub fn hit_count(&self) -> u64 {
+        self.inner.lock().unwrap().hit_count
+    }
+
+    pub fn miss_count(&self) -> u64 {
+        self.inner.lock().unwrap().miss_count
+    }
+
+    // The uid is only used to compile on a miss. What is compiled does not
+    // depend on who compiled it, so a hit hands out the same ExeCode to
+    // every uid.
+    pub async fn compile(
+        &self,
+        raw_code: &RawCode,
+        uid: u32,
+        time_limit: TimeSpan,
+        memory_limit: MemorySize,
+        process_limit: usize,
+        output_limit: MemorySize,
+    ) -> CompileResult {
+        let language_info = match self.language_info(&raw_code.compile_and_exe_setting).await {
+            Err(result) => return CompileResult::InternalError(result),
+            Ok(result) => result,
+        };
+        let source_files = match raw_code
+            .source_files(
+                &raw_code.compile_and_exe_setting.raw_code,
+                raw_code.compile_and_exe_setting.archive_limit,
+            )
+            .await
+        {
+            Err(result) => return result,
+            Ok(result) => result,
+        };
+        let (key, key_material) = match cache_key(
+            &source_files,
+            &raw_code.compile_and_exe_setting,
+            language_info.as_str(),
+        ) {
+            Err(result) => return CompileResult::InternalError(result),
+            Ok(result) => result,
+        };
+        let is_on_disk = {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(exe_code) = inner.get_memory(key.as_str(), &key_material) {
+                inner.hit_count += 1;
+                return CompileResult::Ok(Box::new(exe_code));
+            }
+            inner.disk.contains_key(key.as_str())
+        };
+        if let (true, Some(dir)) = (is_on_disk, &self.dir) {
+            let entry_dir = dir.join(key.as_str());
+            match load_from_disk(entry_dir.as_path(), &key_material).await {
+                None => {
+                    let _ = tokio::fs::remove_dir_all(entry_dir.as_path()).await;
+                    let mut inner = self.inner.lock().unwrap();
+                    if let Some(disk_entry) = inner.disk.remove(key.as_str()) {
+                        inner.disk_usage -= disk_entry.size;
+                    }
+                }
+                Some(exe_code) => {
+                    let mut inner = self.inner.lock().unwrap();
+                    inner.hit_count += 1;
+                    inner.touch_disk(key.as_str(), entry_dir.as_path());
+                    inner.insert_memory(key, key_material, exe_code.clone());
+                    return CompileResult::Ok(Box::new(exe_code));
+                }
+            }
+        }
+        let result = raw_code
+            .compile(uid, time_limit, memory_limit, process_limit, output_limit)
+            .await;
+        self.inner.lock().unwrap().miss_count += 1;
+        if let CompileResult::Ok(exe_code) = &result {
+            if let Some(dir) = &self.dir {
+                if let Some(size) =
+                    store_to_disk(dir.as_path(), key.as_str(), &key_material, exe_code).await
+                {
+                    let mut inner = self.inner.lock().unwrap();
+                    let last_used = now_in_milliseconds();
+                    if let Some(disk_entry) = inner
+                        .disk
+                        .insert(key.clone(), DiskEntry { size, last_used })
+                    {
+                        inner.disk_usage -= disk_entry.size;
+                    }
+                    inner.disk_usage += size;
+                    inner.evict_disk(dir);
+                }
+            }
+            self.inner
+                .lock()
+                .unwrap()
+                .insert_memory(key, key_material, exe_code.as_ref().clone());
+        }
+        result
+    }
+
+    // The compiler version only changes when the host is updated, so it is
+    // looked up once per language_info_command for the lifetime of the cache.
+    async fn language_info(&self, setting: &CompileAndExeSetting) -> Result<String, JudgeError> {
+        if setting.language_info_command.is_empty() {
+            return Ok(String::new());
+        }
+        if let Some(result) = self
+            .inner
+            .lock()
+            .unwrap()
+            .language_infos
+            .get(setting.language_info_command.as_str())
+        {
+            return Ok(result.clone());
+        }
+        let language_info = {
+            let setting = setting.clone();
+            match tokio::task::spawn_blocking(move || setting.get_language_info()).await {
+                Err(result) => return Err(JudgeError::Internal(result.to_string())),
+                Ok(result) => result?,
+            }
+        };
+        self.inner
+            .lock()
+            .unwrap()
+            .language_infos
+            .insert(setting.language_info_command.clone(), language_info.clone());
+        Ok(language_info)
+    }
+}
+
+impl CompileCacheInner {
+    fn get_memory(&mut self, key: &str, key_material: &[u8]) -> Option<ExeCode> {
+        self.clock += 1;
+        let clock = self.clock;
+        match self.memory.get_mut(key) {
+            Some(entry) if entry.key_material == key_material => {
+                entry.last_used = clock;
+                Some(entry.exe_code.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn insert_memory(&mut self, key: String, key_material: Vec<u8>, exe_code: ExeCode) {
+        let size = key_material.len() + exe_code_size(&exe_code);
+        if size > self.memory_limit {
+            return;
+        }
+        self.clock += 1;
+        if let Some(entry) = self.memory.insert(
+            key,
+            MemoryEntry {
+                key_material,
+                exe_code,
+                size,
+                last_used: self.clock,
+            },
+        ) {
+            self.memory_usage -= entry.size;
+        }
+        self.memory_usage += size;
+        while self.memory_usage > self.memory_limit {
+            let oldest = match self.memory.iter().min_by_key(|(_, entry)| entry.last_used) {
+                None => break,
+                Some((key, _)) => key.clone(),
+            };
+            if let Some(entry) = self.memory.remove(oldest.as_str()) {
+                self.memory_usage -= entry.size;
+            }
+        }
+    }
+
+    fn touch_disk(&mut self, key: &str, entry_dir: &Path) {
+        let last_used = now_in_milliseconds();
+        if let Some(entry) = self.disk.get_mut(key) {
+            entry.last_used = last_used;
+        }
+        if let Ok(file) = std::fs::File::options()
+            .write(true)
+            .open(entry_dir.join("key"))
+        {
+            let _ = file.set_modified(SystemTime::now());
+        }
+    }
+
+    fn evict_disk<P: AsRef<Path>>(&mut self, dir: P) {
+        while self.disk_usage > self.disk_limit {
+            let oldest = match self.disk.iter().min_by_key(|(_, entry)| entry.last_used) {
+                None => break,
+                Some((key, _)) => key.clone(),
+            };
+            if let Some(entry) = self.disk.remove(oldest.as_str()) {
+                self.disk_usage -= entry.size;
+                let _ = std::fs::remove_dir_all(dir.as_ref().join(oldest.as_str()));
+            }
+        }
+    }
+}
+
+// The hash only names the entry; the full key material is kept next to it and
+// compared on every hit, so a colliding submission can never be handed a
+// binary that was compiled from something else.
+fn cache_key(
+    source_files: &BTreeMap<String, Vec<u8>>,
+    setting: &CompileAndExeSetting,
+    language_info: &str,
+) -> Result<(String, Vec<u8>), JudgeError> {
+    // serde_json keeps object keys sorted, which makes maps in the setting
+    // encode the same way every time.
+    let setting = match serde_json::to_value(setting).and_then(|value| serde_json::to_vec(&value)) {
+        Err(result) => return Err(JudgeError::Internal(result.to_string())),
+        Ok(result) => result,
+    };
+    let mut parts = vec![];
+    // Problem files are part of the sources, so editing one on the host
+    // changes the key even though the setting still names the same path.
+    for (path, content) in source_files {
+        parts.push(path.as_bytes());
+        parts.push(content.as_slice());
+    }
+    parts.push(setting.as_slice());
+    parts.push(language_info.as_bytes());
+    let mut key_material = vec![];
+    for part in parts {
+        key_material.extend_from_slice(&(part.len() as u64).to_le_bytes());
+        key_material.extend_from_slice(part);
+    }
+    let key = Sha256::digest(&key_material)
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect();
+    Ok((key, key_material))
+}
+
+fn exe_code_size(exe_code: &ExeCode) -> usize {
+    exe_code
+        .exe_files
+        .iter()
+        .map(|(name, content)| name.len() + content.len())
+        .sum()
+}
+
+fn now_in_milliseconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn scan_disk_entry(entry_dir: &Path) -> Option<DiskEntry> {
+    let last_used = std::fs::metadata(entry_dir.join("key"))
+        .and_then(|metadata| metadata.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_millis() as u64;
+    let mut size = 0;
+    for file in std::fs::read_dir(entry_dir).ok()?.flatten() {
+        size += file.metadata().ok()?.len() as usize;
+    }
+    Some(DiskEntry { size, last_used })
+}
+
+async fn load_from_disk(entry_dir: &Path, key_material: &[u8]) -> Option<ExeCode> {
+    if tokio::fs::read(entry_dir.join("key")).await.ok()? != key_material {
+        return None;
+    }
+    let manifest: DiskManifest = serde_json::from_slice(
+        &tokio::fs::read(entry_dir.join("manifest.json"))
+            .await
+            .ok()?,
+    )
+    .ok()?;
+    let mut exe_files = HashMap::new();
+    for (index, exe_file) in manifest.exe_files.into_iter().enumerate() {
+        let content = tokio::fs::read(entry_dir.join(index.to_string()))
+            .await
+            .ok()?;
+        exe_files.insert(exe_file, content);
+    }
+    Some(ExeCode {
+        exe_files,
+        compile_and_exe_setting: manifest.compile_and_exe_setting,
+    })
+}
+
+// Entries are written to a hidden directory first and renamed into place, so
+// that other workers sharing the cache dir never see a half written entry.
+async fn store_to_disk(
+    dir: &Path,
+    key: &str,
+    key_material: &[u8],
+    exe_code: &ExeCode,
+) -> Option<usize> {
+    let tmp_dir = dir.join(format!(".{}", uuid::Uuid::new_v4().simple()));
+    let result = write_disk_entry(tmp_dir.as_path(), key_material, exe_code).await;
+    let size = match result {
+        None => {
+            let _ = tokio::fs::remove_dir_all(tmp_dir.as_path()).await;
+            return None;
+        }
+        Some(result) => result,
+    };
+    if tokio::fs::rename(tmp_dir.as_path(), dir.join(key))
+        .await
+        .is_err()
+    {
+        let _ = tokio::fs::remove_dir_all(tmp_dir.as_path()).await;
+        return None;
+    }
+    Some(size)
+}
+
+async fn write_disk_entry(
+    entry_dir: &Path,
+    key_material: &[u8],
+    exe_code: &ExeCode,
+) -> Option<usize> {
+    tokio::fs::create_dir(entry_dir).await.ok()?;
+    let mut manifest = DiskManifest {
+        compile_and_exe_setting: exe_code.compile_and_exe_setting.clone(),
+        exe_files: vec![],
+    };
+    let mut size = key_material.len();
+    for (index, (exe_file, content)) in exe_code.exe_files.iter().enumerate() {
+        tokio::fs::write(entry_dir.join(index.to_string()), content)
+            .await
+            .ok()?;
+        manifest.exe_files.push(exe_file.clone());
+        size += content.len();
+    }
+    let manifest = serde_json::to_vec(&manifest).ok()?;
+    size += manifest.len();
+    tokio::fs::write(entry_dir.join("manifest.json"), manifest)
+        .await
+        .ok()?;
+    tokio::fs::write(entry_dir.join("key"), key_material)
+        .await
+        .ok()?;
+    Some(size)
+}
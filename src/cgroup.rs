@@ -1,3 +1,4 @@
+use crate::error::JudgeError;
 use crate::quantity::MemorySize;
 
 extern crate libc;
@@ -41,6 +42,10 @@ extern "C" {
     fn cgroup_attach_task_pid(cgroup: *mut libc::c_void, pid: libc::pid_t) -> libc::c_int;
     fn cgroup_free(cgroup: *mut *mut libc::c_void);
     fn cgroup_delete_cgroup_ext(cgroup: *mut libc::c_void, flags: libc::c_int) -> libc::c_int;
+    fn cgroup_get_subsys_mount_point(
+        controller: *const libc::c_char,
+        mount_point: *mut *mut libc::c_char,
+    ) -> libc::c_int;
     #[cfg(feature = "cgroup_v2")]
     fn cgroup_setup_mode() -> libc::c_int;
 }
@@ -48,19 +53,31 @@ extern "C" {
 pub struct Cgroup {
     cgroup_name: std::ffi::CString,
     mem_controller: *mut libc::c_void,
+    cpu_controller: *mut libc::c_void,
+    pids_controller: *mut libc::c_void,
+    with_pids: bool,
     cgroup: *mut libc::c_void,
     oom: u64,
+    pids_max: u64,
+    memory_limit: MemorySize,
     is_v2: bool,
+    cgroup_dirs: Vec<std::path::PathBuf>,
 }
 
 impl Cgroup {
-    pub fn new(cgroup_name: &str, memory_limit: MemorySize) -> Result<Self, String> {
+    pub fn new(
+        cgroup_name: &str,
+        memory_limit: MemorySize,
+        process_limit: usize,
+    ) -> Result<Self, JudgeError> {
         if check_admin_privilege() == false {
-            return Err("check_admin_privilege() failed".to_string());
+            return Err(JudgeError::PermissionDenied(
+                "cgroups can only be managed by root".to_string(),
+            ));
         }
         unsafe {
             if cgroup_init() != 0 {
-                return Err("cgroup_init() failed".to_string());
+                return Err(JudgeError::Unsupported("cgroup_init() failed".to_string()));
             }
         }
         let cgroup_name = std::ffi::CString::new(cgroup_name).unwrap();
@@ -74,7 +91,7 @@ impl Cgroup {
             false
         };
         if cgroup.is_null() {
-            return Err("cgroup_new_cgroup() failed".to_string());
+            return Err(JudgeError::Cgroup("cgroup_new_cgroup() failed".to_string()));
         }
         let memory_string = std::ffi::CString::new("memory").unwrap();
         let mem_controller = unsafe { cgroup_add_controller(cgroup, memory_string.as_ptr()) };
@@ -82,7 +99,9 @@ impl Cgroup {
             unsafe {
                 cgroup_free(&mut cgroup);
             }
-            return Err("cgroup_add_controller() failed".to_string());
+            return Err(JudgeError::Cgroup(
+                "cgroup_add_controller() failed".to_string(),
+            ));
         }
 
         let memory_limit_in_bytes_string = if is_v2 {
@@ -101,7 +120,70 @@ impl Cgroup {
             unsafe {
                 cgroup_free(&mut cgroup);
             }
-            return Err("cgroup_set_value_uint64() failed".to_string());
+            return Err(JudgeError::Cgroup(
+                "cgroup_set_value_uint64() failed".to_string(),
+            ));
+        }
+
+        let cpu_string = if is_v2 {
+            std::ffi::CString::new("cpu").unwrap()
+        } else {
+            std::ffi::CString::new("cpuacct").unwrap()
+        };
+        let cpu_controller = unsafe { cgroup_add_controller(cgroup, cpu_string.as_ptr()) };
+        if cpu_controller.is_null() {
+            unsafe {
+                cgroup_free(&mut cgroup);
+            }
+            return Err(JudgeError::Cgroup(
+                "cgroup_add_controller() failed".to_string(),
+            ));
+        }
+
+        // A process limit of 0 means none was asked for, in which case the pids
+        // controller is left out, as not every host has it.
+        let pids_string = std::ffi::CString::new("pids").unwrap();
+        let with_pids = process_limit != 0;
+        let mut pids_controller = std::ptr::null_mut();
+        if with_pids {
+            let mut value: *mut libc::c_char = std::ptr::null_mut();
+            if unsafe { cgroup_get_subsys_mount_point(pids_string.as_ptr(), &mut value) } != 0 {
+                unsafe {
+                    cgroup_free(&mut cgroup);
+                }
+                return Err(JudgeError::Unsupported(
+                    "the pids controller is not available, so a process limit can not be set"
+                        .to_string(),
+                ));
+            }
+            unsafe {
+                libc::free(value as *mut libc::c_void);
+            }
+            pids_controller = unsafe { cgroup_add_controller(cgroup, pids_string.as_ptr()) };
+            if pids_controller.is_null() {
+                unsafe {
+                    cgroup_free(&mut cgroup);
+                }
+                return Err(JudgeError::Cgroup(
+                    "cgroup_add_controller() failed".to_string(),
+                ));
+            }
+            let pids_max_string = std::ffi::CString::new("pids.max").unwrap();
+            let ret = unsafe {
+                cgroup_add_value_uint64(
+                    pids_controller,
+                    pids_max_string.as_ptr(),
+                    process_limit as u64,
+                )
+            };
+            if ret != 0 {
+                unsafe {
+                    cgroup_free(&mut cgroup);
+                }
+                return Err(JudgeError::Cgroup(
+                    "cgroup_add_value_uint64() failed".to_string(),
+                ));
+            }
         }
 
         let ret = unsafe { cgroup_create_cgroup(cgroup, 0) };
@@ -109,40 +191,82 @@ impl Cgroup {
             unsafe {
                 cgroup_free(&mut cgroup);
             }
-            return Err("cgroup_create_cgroup() failed".to_string());
+            return Err(JudgeError::Cgroup(
+                "cgroup_create_cgroup() failed".to_string(),
+            ));
+        }
+        // v1 mounts every controller in its own hierarchy, so a task has to be
+        // moved in each of them, while v2 has a single one for all of them.
+        let mut cgroup_dirs: Vec<std::path::PathBuf> = vec![];
+        let controllers = if with_pids {
+            vec![&memory_string, &cpu_string, &pids_string]
+        } else {
+            vec![&memory_string, &cpu_string]
+        };
+        for controller in controllers {
+            let mut value: *mut libc::c_char = std::ptr::null_mut();
+            if unsafe { cgroup_get_subsys_mount_point(controller.as_ptr(), &mut value) } != 0 {
+                unsafe {
+                    cgroup_delete_cgroup_ext(cgroup, 0);
+                    cgroup_free(&mut cgroup);
+                }
+                return Err(JudgeError::Cgroup(
+                    "cgroup_get_subsys_mount_point() failed".to_string(),
+                ));
+            }
+            let mount_point = unsafe { std::ffi::CStr::from_ptr(value) }
+                .to_string_lossy()
+                .into_owned();
+            unsafe {
+                libc::free(value as *mut libc::c_void);
+            }
+            let cgroup_dir =
+                std::path::Path::new(&mount_point).join(cgroup_name.to_string_lossy().as_ref());
+            if !cgroup_dirs.contains(&cgroup_dir) {
+                cgroup_dirs.push(cgroup_dir);
+            }
         }
         let mut result = Cgroup {
             cgroup_name: cgroup_name,
             mem_controller: mem_controller,
+            cpu_controller,
+            pids_controller,
+            with_pids,
             cgroup: cgroup,
             oom: 0,
+            pids_max: 0,
+            memory_limit,
             is_v2: is_v2,
+            cgroup_dirs,
         };
         result.update_cgroup_and_controller()?;
         if result.update_oom().is_err() {
-            return Err("update_oom() failed".to_string());
+            return Err(JudgeError::Cgroup("update_oom() failed".to_string()));
+        }
+        if result.update_pids_max().is_err() {
+            return Err(JudgeError::Cgroup("update_pids_max() failed".to_string()));
         }
         Ok(result)
     }
 
-    pub fn new_tmp(memory_limit: MemorySize) -> Result<Self, String> {
+    pub fn new_tmp(memory_limit: MemorySize, process_limit: usize) -> Result<Self, JudgeError> {
         let name = format!(
             "emjudge-judgecore-cgroup-{}",
             uuid::Uuid::new_v4().to_string()
         );
-        Self::new(name.as_str(), memory_limit)
+        Self::new(name.as_str(), memory_limit, process_limit)
     }
 
-    pub fn update_cgroup_and_controller(&mut self) -> Result<(), String> {
+    pub fn update_cgroup_and_controller(&mut self) -> Result<(), JudgeError> {
         let mut cgroup = unsafe { cgroup_new_cgroup(self.cgroup_name.as_ptr()) };
         if cgroup.is_null() {
-            return Err("cgroup_new_cgroup() failed".to_string());
+            return Err(JudgeError::Cgroup("cgroup_new_cgroup() failed".to_string()));
         }
         if unsafe { cgroup_get_cgroup(cgroup) } != 0 {
             unsafe {
                 cgroup_free(&mut cgroup);
             }
-            return Err("cgroup_get_cgroup() failed".to_string());
+            return Err(JudgeError::Cgroup("cgroup_get_cgroup() failed".to_string()));
         }
         let memory_string = std::ffi::CString::new("memory").unwrap();
         let mem_controller = unsafe { cgroup_get_controller(cgroup, memory_string.as_ptr()) };
@@ -150,17 +274,48 @@ impl Cgroup {
             unsafe {
                 cgroup_free(&mut cgroup);
             }
-            return Err("cgroup_add_controller() failed".to_string());
+            return Err(JudgeError::Cgroup(
+                "cgroup_add_controller() failed".to_string(),
+            ));
+        }
+        let cpu_string = if self.is_v2 {
+            std::ffi::CString::new("cpu").unwrap()
+        } else {
+            std::ffi::CString::new("cpuacct").unwrap()
+        };
+        let cpu_controller = unsafe { cgroup_get_controller(cgroup, cpu_string.as_ptr()) };
+        if cpu_controller.is_null() {
+            unsafe {
+                cgroup_free(&mut cgroup);
+            }
+            return Err(JudgeError::Cgroup(
+                "cgroup_get_controller() failed".to_string(),
+            ));
+        }
+        let mut pids_controller = std::ptr::null_mut();
+        if self.with_pids {
+            let pids_string = std::ffi::CString::new("pids").unwrap();
+            pids_controller = unsafe { cgroup_get_controller(cgroup, pids_string.as_ptr()) };
+            if pids_controller.is_null() {
+                unsafe {
+                    cgroup_free(&mut cgroup);
+                }
+                return Err(JudgeError::Cgroup(
+                    "cgroup_get_controller() failed".to_string(),
+                ));
+            }
         }
         unsafe {
             cgroup_free(&mut self.cgroup);
         }
         self.cgroup = cgroup;
         self.mem_controller = mem_controller;
+        self.cpu_controller = cpu_controller;
+        self.pids_controller = pids_controller;
         Ok(())
     }
 
-    pub fn update_oom(&mut self) -> Result<(), String> {
+    pub fn update_oom(&mut self) -> Result<(), JudgeError> {
         let mut value: *mut libc::c_char = std::ptr::null_mut();
         if self.is_v2 {
             let memory_oom_control_string = std::ffi::CString::new("memory.events").unwrap();
@@ -172,7 +327,9 @@ impl Cgroup {
                 )
             };
             if ret != 0 {
-                return Err("cgroup_get_value_string() failed".to_string());
+                return Err(JudgeError::Cgroup(
+                    "cgroup_get_value_string() failed".to_string(),
+                ));
             }
             let string_value = unsafe { std::ffi::CString::from_raw(value) }
                 .into_string()
@@ -194,7 +351,9 @@ impl Cgroup {
                 )
             };
             if ret != 0 {
-                return Err("cgroup_get_value_string() failed".to_string());
+                return Err(JudgeError::Cgroup(
+                    "cgroup_get_value_string() failed".to_string(),
+                ));
             }
             let string_value = unsafe { std::ffi::CString::from_raw(value) }
                 .into_string()
@@ -210,14 +369,55 @@ impl Cgroup {
         Ok(())
     }
 
-    pub fn update_cgroup_and_controller_and_check_oom(&mut self) -> Result<bool, String> {
+    pub fn update_pids_max(&mut self) -> Result<(), JudgeError> {
+        if !self.with_pids {
+            return Ok(());
+        }
+        let mut value: *mut libc::c_char = std::ptr::null_mut();
+        let pids_events_string = std::ffi::CString::new("pids.events").unwrap();
+        let ret = unsafe {
+            cgroup_get_value_string(
+                self.pids_controller,
+                pids_events_string.as_ptr(),
+                &mut value,
+            )
+        };
+        if ret != 0 {
+            return Err(JudgeError::Cgroup(
+                "cgroup_get_value_string() failed".to_string(),
+            ));
+        }
+        let string_value = unsafe { std::ffi::CString::from_raw(value) }
+            .into_string()
+            .unwrap();
+        let value_vec = string_value.split_whitespace().collect::<Vec<&str>>();
+        for i in 0..value_vec.len() {
+            if value_vec[i] == "max" {
+                self.pids_max = value_vec[i + 1].parse::<u64>().unwrap();
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_pids_max(&mut self) -> Result<bool, JudgeError> {
+        let last_pids_max = self.pids_max;
+        self.update_pids_max()?;
+        Ok(last_pids_max != self.pids_max)
+    }
+
+    pub fn update_cgroup_and_controller_and_check_oom(&mut self) -> Result<bool, JudgeError> {
         let last_oom = self.oom;
         self.update_cgroup_and_controller()?;
         self.update_oom()?;
         Ok(last_oom != self.oom)
     }
 
-    pub fn get_max_usage_in_bytes(&mut self) -> Result<u64, String> {
+    pub fn get_memory_limit(&self) -> MemorySize {
+        self.memory_limit
+    }
+
+    pub fn get_max_usage_in_bytes(&mut self) -> Result<u64, JudgeError> {
         let mut value: u64 = 0;
         let memory_max_usage_in_bytes_string = if self.is_v2 {
             std::ffi::CString::new("memory.peak").unwrap()
@@ -232,12 +432,14 @@ impl Cgroup {
             )
         };
         if ret != 0 {
-            return Err("cgroup_get_value_uint64() failed".to_string());
+            return Err(JudgeError::Cgroup(
+                "cgroup_get_value_uint64() failed".to_string(),
+            ));
         }
         Ok(value)
     }
 
-    pub fn reset_max_usage_in_bytes(&mut self) -> Result<(), String> {
+    pub fn reset_max_usage_in_bytes(&mut self) -> Result<(), JudgeError> {
         let memory_max_usage_in_bytes_string = if self.is_v2 {
             std::ffi::CString::new("memory.peak").unwrap()
         } else {
@@ -251,18 +453,132 @@ impl Cgroup {
             )
         };
         if ret != 0 {
-            return Err("cgroup_set_value_uint64() failed".to_string());
+            return Err(JudgeError::Cgroup(
+                "cgroup_set_value_uint64() failed".to_string(),
+            ));
         }
         if unsafe { cgroup_modify_cgroup(self.cgroup) } != 0 {
-            return Err("cgroup_modify_cgroup() failed".to_string());
+            return Err(JudgeError::Cgroup(
+                "cgroup_modify_cgroup() failed".to_string(),
+            ));
         }
         Ok(())
     }
 
-    pub fn add_task(&mut self, pid: libc::pid_t) -> Result<(), String> {
+    pub fn get_cpu_usage_in_nanoseconds(&mut self) -> Result<u64, JudgeError> {
+        if self.is_v2 {
+            let mut value: *mut libc::c_char = std::ptr::null_mut();
+            let cpu_stat_string = std::ffi::CString::new("cpu.stat").unwrap();
+            let ret = unsafe {
+                cgroup_get_value_string(self.cpu_controller, cpu_stat_string.as_ptr(), &mut value)
+            };
+            if ret != 0 {
+                return Err(JudgeError::Cgroup(
+                    "cgroup_get_value_string() failed".to_string(),
+                ));
+            }
+            let string_value = unsafe { std::ffi::CString::from_raw(value) }
+                .into_string()
+                .unwrap();
+            let value_vec = string_value.split_whitespace().collect::<Vec<&str>>();
+            for i in 0..value_vec.len() {
+                if value_vec[i] == "usage_usec" {
+                    return match value_vec[i + 1].parse::<u64>() {
+                        Ok(result) => Ok(result * 1_000),
+                        Err(result) => Err(JudgeError::Cgroup(result.to_string())),
+                    };
+                }
+            }
+            Err(JudgeError::Cgroup(
+                "usage_usec is not found in cpu.stat".to_string(),
+            ))
+        } else {
+            let mut value: u64 = 0;
+            let cpuacct_usage_string = std::ffi::CString::new("cpuacct.usage").unwrap();
+            let ret = unsafe {
+                cgroup_get_value_uint64(
+                    self.cpu_controller,
+                    cpuacct_usage_string.as_ptr(),
+                    &mut value,
+                )
+            };
+            if ret != 0 {
+                return Err(JudgeError::Cgroup(
+                    "cgroup_get_value_uint64() failed".to_string(),
+                ));
+            }
+            Ok(value)
+        }
+    }
+
+    pub fn add_task(&mut self, pid: libc::pid_t) -> Result<(), JudgeError> {
         let ret = unsafe { cgroup_attach_task_pid(self.cgroup, pid) };
         if ret != 0 {
-            return Err("cgroup_attach_task_pid() failed".to_string());
+            return Err(JudgeError::Cgroup(
+                "cgroup_attach_task_pid() failed".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Writing a pid into every one of these files moves that process into the
+    // cgroup. They are opened up front, so that a child can join itself before
+    // exec without allocating anything.
+    pub fn open_procs(&self) -> Result<Vec<std::fs::File>, JudgeError> {
+        let mut result = vec![];
+        for cgroup_dir in &self.cgroup_dirs {
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .open(cgroup_dir.join("cgroup.procs"))
+            {
+                Err(error) => {
+                    return Err(JudgeError::Cgroup(format!("open cgroup.procs: {}", error)))
+                }
+                Ok(file) => result.push(file),
+            }
+        }
+        Ok(result)
+    }
+
+    fn get_tasks(&self) -> Result<Vec<libc::pid_t>, JudgeError> {
+        let mut result = vec![];
+        for cgroup_dir in &self.cgroup_dirs {
+            let content = match std::fs::read_to_string(cgroup_dir.join("cgroup.procs")) {
+                Err(error) => {
+                    return Err(JudgeError::Cgroup(format!("read cgroup.procs: {}", error)))
+                }
+                Ok(content) => content,
+            };
+            for pid in content.split_whitespace() {
+                if let Ok(pid) = pid.parse::<libc::pid_t>() {
+                    if !result.contains(&pid) {
+                        result.push(pid);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn has_tasks(&self) -> Result<bool, JudgeError> {
+        Ok(!self.get_tasks()?.is_empty())
+    }
+
+    // Sends SIGKILL to every process in the cgroup, grandchildren included.
+    // Processes may still be exiting afterwards, see has_tasks().
+    pub fn kill_tasks(&mut self) -> Result<(), JudgeError> {
+        if self.is_v2 {
+            if let Some(cgroup_dir) = self.cgroup_dirs.first() {
+                // cgroup.kill only exists since Linux 5.14.
+                if std::fs::write(cgroup_dir.join("cgroup.kill"), "1").is_ok() {
+                    return Ok(());
+                }
+            }
+        }
+        for pid in self.get_tasks()? {
+            unsafe {
+                libc::kill(pid, libc::SIGKILL);
+            }
         }
         Ok(())
     }
@@ -0,0 +1,259 @@
+use crate::{
+    quantity::ProcessResource,
+    result::{AnsAndEvalResult, EvalStatus, EvalVerdict},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Comparator {
+    // The output must be byte for byte the answer.
+    Exact,
+    // Tokens are separated by any whitespace, including line breaks.
+    Tokens,
+    // Lines are compared after dropping their trailing whitespace, and
+    // trailing empty lines are ignored.
+    Lines,
+    // Tokens that are both numbers may differ by the absolute or the relative
+    // epsilon, any other token must match exactly.
+    Float { absolute: f64, relative: f64 },
+    // Tokens are compared ignoring ASCII case.
+    CaseInsensitive,
+    // The lines, compared as in Lines, may come in any order.
+    UnorderedLines,
+}
+
+// Tokens quoted in a message are cut to this many characters.
+const QUOTE_LIMIT: usize = 32;
+
+struct Token<'a> {
+    text: &'a [u8],
+    line: usize,
+    index: usize,
+}
+
+impl Comparator {
+    pub fn compare(&self, output: &[u8], answer: &[u8]) -> EvalVerdict {
+        let result = match self {
+            Comparator::Exact => compare_exact(output, answer),
+            Comparator::Tokens => compare_tokens(output, answer, |output, answer| output == answer),
+            Comparator::Lines => compare_lines(output, answer),
+            Comparator::Float { absolute, relative } => {
+                compare_tokens(output, answer, |output, answer| {
+                    equal_floats(output, answer, *absolute, *relative)
+                })
+            }
+            Comparator::CaseInsensitive => compare_tokens(output, answer, |output, answer| {
+                output.eq_ignore_ascii_case(answer)
+            }),
+            Comparator::UnorderedLines => compare_unordered_lines(output, answer),
+        };
+        match result {
+            Ok(_) => EvalVerdict {
+                status: EvalStatus::Accepted,
+                score: 1.0,
+                message: String::new(),
+            },
+            Err(message) => EvalVerdict {
+                status: EvalStatus::WrongAnswer,
+                score: 0.0,
+                message,
+            },
+        }
+    }
+
+    // The same result AnsAndEval gives, with no checker process behind it.
+    pub fn ans_and_eval(&self, tested_ans: &[u8], std_ans: &[u8]) -> AnsAndEvalResult {
+        AnsAndEvalResult::Ok(
+            ProcessResource::default(),
+            self.compare(tested_ans, std_ans),
+        )
+    }
+}
+
+fn quote(text: &[u8]) -> String {
+    let text = String::from_utf8_lossy(text);
+    match text.chars().nth(QUOTE_LIMIT) {
+        None => format!("`{}`", text),
+        Some(_) => format!(
+            "`{}...`",
+            text.chars().take(QUOTE_LIMIT).collect::<String>()
+        ),
+    }
+}
+
+fn compare_exact(output: &[u8], answer: &[u8]) -> Result<(), String> {
+    let position = match output.iter().zip(answer.iter()).position(|(a, b)| a != b) {
+        Some(result) => result,
+        None if output.len() == answer.len() => return Ok(()),
+        None => output.len().min(answer.len()),
+    };
+    let line = output[..position].iter().filter(|c| **c == b'\n').count() + 1;
+    let column = match output[..position].iter().rposition(|c| *c == b'\n') {
+        None => position + 1,
+        Some(result) => position - result,
+    };
+    match (output.get(position), answer.get(position)) {
+        (None, _) => Err(format!(
+            "first difference at line {}, column {}: output ended early",
+            line, column
+        )),
+        (_, None) => Err(format!(
+            "first difference at line {}, column {}: extra output",
+            line, column
+        )),
+        (Some(found), Some(expected)) => Err(format!(
+            "first difference at line {}, column {}: expected {}, found {}",
+            line,
+            column,
+            quote(&[*expected]),
+            quote(&[*found])
+        )),
+    }
+}
+
+fn tokens(text: &[u8]) -> Vec<Token<'_>> {
+    let mut result = vec![];
+    for (line, content) in text.split(|c| *c == b'\n').enumerate() {
+        for (index, token) in content
+            .split(|c| c.is_ascii_whitespace())
+            .filter(|token| !token.is_empty())
+            .enumerate()
+        {
+            result.push(Token {
+                text: token,
+                line: line + 1,
+                index: index + 1,
+            });
+        }
+    }
+    result
+}
+
+fn compare_tokens<F>(output: &[u8], answer: &[u8], equal: F) -> Result<(), String>
+where
+    F: Fn(&[u8], &[u8]) -> bool,
+{
+    let output = tokens(output);
+    let answer = tokens(answer);
+    for (found, expected) in output.iter().zip(answer.iter()) {
+        if !equal(found.text, expected.text) {
+            return Err(format!(
+                "first difference at line {}, token {}: expected {}, found {}",
+                found.line,
+                found.index,
+                quote(expected.text),
+                quote(found.text)
+            ));
+        }
+    }
+    if output.len() < answer.len() {
+        let expected = &answer[output.len()];
+        return Err(format!(
+            "output ended after {} tokens, expected {} at line {}, token {} of the answer",
+            output.len(),
+            quote(expected.text),
+            expected.line,
+            expected.index
+        ));
+    }
+    if output.len() > answer.len() {
+        let found = &output[answer.len()];
+        return Err(format!(
+            "extra token {} at line {}, token {}",
+            quote(found.text),
+            found.line,
+            found.index
+        ));
+    }
+    Ok(())
+}
+
+fn equal_floats(output: &[u8], answer: &[u8], absolute: f64, relative: f64) -> bool {
+    if output == answer {
+        return true;
+    }
+    let parse = |text: &[u8]| {
+        std::str::from_utf8(text)
+            .ok()
+            .and_then(|text| text.parse::<f64>().ok())
+    };
+    match (parse(output), parse(answer)) {
+        (Some(found), Some(expected)) if found.is_finite() && expected.is_finite() => {
+            let difference = (found - expected).abs();
+            difference <= absolute || difference <= relative * expected.abs()
+        }
+        _ => false,
+    }
+}
+
+fn lines(text: &[u8]) -> Vec<&[u8]> {
+    let mut result: Vec<&[u8]> = text
+        .split(|c| *c == b'\n')
+        .map(|line| {
+            let end = line
+                .iter()
+                .rposition(|c| !c.is_ascii_whitespace())
+                .map_or(0, |result| result + 1);
+            &line[..end]
+        })
+        .collect();
+    while result.last().is_some_and(|line| line.is_empty()) {
+        result.pop();
+    }
+    result
+}
+
+fn compare_lines(output: &[u8], answer: &[u8]) -> Result<(), String> {
+    let output = lines(output);
+    let answer = lines(answer);
+    for (index, (found, expected)) in output.iter().zip(answer.iter()).enumerate() {
+        if found != expected {
+            return Err(format!(
+                "first difference at line {}: expected {}, found {}",
+                index + 1,
+                quote(expected),
+                quote(found)
+            ));
+        }
+    }
+    if output.len() < answer.len() {
+        return Err(format!(
+            "output ended after {} lines, expected {} at line {}",
+            output.len(),
+            quote(answer[output.len()]),
+            output.len() + 1
+        ));
+    }
+    if output.len() > answer.len() {
+        return Err(format!(
+            "extra line {} at line {}",
+            quote(output[answer.len()]),
+            answer.len() + 1
+        ));
+    }
+    Ok(())
+}
+
+fn compare_unordered_lines(output: &[u8], answer: &[u8]) -> Result<(), String> {
+    let mut output: Vec<(&[u8], usize)> = lines(output)
+        .into_iter()
+        .enumerate()
+        .map(|(index, line)| (line, index + 1))
+        .collect();
+    let mut answer = lines(answer);
+    output.sort();
+    answer.sort();
+    let mut expected = answer.iter().peekable();
+    for (found, line) in output.iter() {
+        if let Some(result) = expected.next_if(|expected| *expected < found) {
+            return Err(format!("missing line {}", quote(result)));
+        }
+        if expected.next_if(|expected| *expected == found).is_none() {
+            return Err(format!("unexpected line {} at line {}", quote(found), line));
+        }
+    }
+    match expected.next() {
+        None => Ok(()),
+        Some(result) => Err(format!("missing line {}", quote(result))),
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum JudgeErrorKind {
+    PermissionDenied,
+    Setting,
+    Unsupported,
+    Cgroup,
+    Io,
+    Process,
+    Isolation,
+    Internal,
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum JudgeError {
+    #[error("{0}")]
+    PermissionDenied(String),
+    #[error("{0}")]
+    Setting(String),
+    #[error("invalid setting: {source}")]
+    Config {
+        #[source]
+        source: Arc<config::ConfigError>,
+    },
+    #[error("{0}")]
+    Unsupported(String),
+    #[error("{0}")]
+    Cgroup(String),
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: Arc<std::io::Error>,
+    },
+    #[error("failed to run {program}: {source}")]
+    Spawn {
+        program: String,
+        #[source]
+        source: Arc<std::io::Error>,
+    },
+    #[error("process isolation failed: {source}")]
+    Isolation {
+        #[source]
+        source: Arc<std::io::Error>,
+    },
+    #[error("{0}")]
+    Internal(String),
+    #[error("{message}")]
+    Remote {
+        kind: JudgeErrorKind,
+        message: String,
+        retryable: bool,
+    },
+}
+
+impl JudgeError {
+    pub fn io(context: &str, source: std::io::Error) -> Self {
+        JudgeError::Io {
+            context: context.to_string(),
+            source: Arc::new(source),
+        }
+    }
+
+    pub fn spawn(program: &str, source: std::io::Error) -> Self {
+        JudgeError::Spawn {
+            program: program.to_string(),
+            source: Arc::new(source),
+        }
+    }
+
+    pub fn isolation(source: std::io::Error) -> Self {
+        JudgeError::Isolation {
+            source: Arc::new(source),
+        }
+    }
+
+    pub fn kind(&self) -> JudgeErrorKind {
+        match self {
+            JudgeError::PermissionDenied(_) => JudgeErrorKind::PermissionDenied,
+            JudgeError::Setting(_) => JudgeErrorKind::Setting,
+            JudgeError::Config { .. } => JudgeErrorKind::Setting,
+            JudgeError::Unsupported(_) => JudgeErrorKind::Unsupported,
+            JudgeError::Cgroup(_) => JudgeErrorKind::Cgroup,
+            JudgeError::Io { .. } => JudgeErrorKind::Io,
+            JudgeError::Spawn { .. } => JudgeErrorKind::Process,
+            JudgeError::Isolation { .. } => JudgeErrorKind::Isolation,
+            JudgeError::Internal(_) => JudgeErrorKind::Internal,
+            JudgeError::Remote { kind, .. } => *kind,
+        }
+    }
+
+    // Cgroup calls race with processes exiting and temporary files can run into
+    // short-lived resource shortages, so those are worth another attempt. A
+    // missing compiler, bad settings or a host without the needed kernel
+    // features will fail the same way again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            JudgeError::Cgroup(_) => true,
+            JudgeError::Remote { retryable, .. } => *retryable,
+            JudgeError::Io { source, .. } | JudgeError::Spawn { source, .. } => !matches!(
+                source.kind(),
+                std::io::ErrorKind::NotFound
+                    | std::io::ErrorKind::PermissionDenied
+                    | std::io::ErrorKind::InvalidInput
+                    | std::io::ErrorKind::Unsupported
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl From<config::ConfigError> for JudgeError {
+    fn from(source: config::ConfigError) -> Self {
+        JudgeError::Config {
+            source: Arc::new(source),
+        }
+    }
+}
+
+// Only the kind, the retry decision and the rendered message survive a round
+// trip, so a deserialized error always comes back as JudgeError::Remote.
+#[derive(Serialize, Deserialize)]
+struct JudgeErrorRepr {
+    kind: JudgeErrorKind,
+    message: String,
+    retryable: bool,
+}
+
+impl Serialize for JudgeError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        JudgeErrorRepr {
+            kind: self.kind(),
+            message: self.to_string(),
+            retryable: self.is_retryable(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for JudgeError {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let repr = JudgeErrorRepr::deserialize(deserializer)?;
+        Ok(JudgeError::Remote {
+            kind: repr.kind,
+            message: repr.message,
+            retryable: repr.retryable,
+        })
+    }
+}
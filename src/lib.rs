@@ -1,11 +1,20 @@
+#[cfg(feature = "cache")]
+pub mod cache;
 #[cfg(feature = "cgroup")]
 pub mod cgroup;
+#[cfg(feature = "compare")]
+pub mod compare;
+pub mod error;
 #[cfg(feature = "program")]
 pub mod program;
 #[cfg(feature = "quantity")]
 pub mod quantity;
 #[cfg(feature = "result")]
 pub mod result;
+#[cfg(feature = "score")]
+pub mod score;
+#[cfg(feature = "seccomp")]
+pub mod seccomp;
 #[cfg(feature = "settings")]
 pub mod settings;
 #[cfg(feature = "test")]
@@ -1,3 +1,8 @@
+#[cfg(any(feature = "compile", feature = "run"))]
+use crate::error::JudgeError;
+#[cfg(feature = "compile")]
+use crate::settings::check_relative_path;
+pub use crate::settings::turn_command_into_command_and_args;
 use crate::settings::CompileAndExeSetting;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -5,146 +10,455 @@ use tempfile::TempDir;
 
 #[cfg(feature = "compile")]
 use crate::result::CompileResult;
+#[cfg(any(feature = "compile", feature = "run"))]
+use crate::{
+    cgroup::Cgroup,
+    quantity::{MemorySize, TimeSpan},
+};
+#[cfg(any(feature = "compile", feature = "archive"))]
+use std::io::Read;
 #[cfg(feature = "compile")]
-use std::process::Stdio;
+use std::{collections::BTreeMap, io::Seek, process::Stdio};
+#[cfg(any(feature = "compile", feature = "run"))]
+use std::{
+    os::fd::AsRawFd,
+    time::{Duration, Instant},
+};
 #[cfg(any(feature = "compile", feature = "run"))]
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 #[cfg(feature = "run")]
 use crate::{
-    cgroup::Cgroup,
-    quantity::{MemorySize, ProcessResource, TimeSpan},
-    result::{InitExeResourceResult, RunToEndResult, RunWithInteractorResult},
+    quantity::{InstancesResource, ProcessResource, Transcript, TranscriptDirection},
+    result::{
+        EvalVerdict, InitExeResourceResult, RunToEndResult, RunWithInteractorResult,
+        RunWithManagerResult, TimeLimitKind,
+    },
+    seccomp::{get_profile_syscalls, SeccompFilter},
 };
 #[cfg(feature = "run")]
 use std::{
-    os::fd::FromRawFd,
-    os::unix::fs::PermissionsExt,
-    time::{Duration, Instant},
+    future::Future,
+    os::fd::{FromRawFd, OwnedFd},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    os::unix::process::ExitStatusExt,
+    sync::{Arc, Mutex},
+    task::Poll,
 };
 
+#[serde_with::serde_as]
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct RawCode {
+    #[cfg_attr(feature = "base64", serde_as(as = "serde_with::base64::Base64"))]
     pub code: Vec<u8>,
+    #[cfg_attr(
+        feature = "base64",
+        serde_as(as = "HashMap<_, serde_with::base64::Base64>")
+    )]
+    #[serde(default)]
+    pub files: HashMap<String, Vec<u8>>,
+    #[cfg_attr(
+        feature = "base64",
+        serde_as(as = "Option<serde_with::base64::Base64>")
+    )]
+    #[serde(default)]
+    pub archive: Option<Vec<u8>>,
     pub compile_and_exe_setting: CompileAndExeSetting,
 }
 
-pub fn turn_command_into_command_and_args(command: &str) -> (String, Vec<String>) {
-    let mut command = command.split_whitespace();
-    let result_command = command.next().unwrap().to_string();
-    let mut result_args = vec![];
-    for arg in command {
-        result_args.push(arg.to_string());
-    }
-    (result_command, result_args)
-}
-
 impl RawCode {
     pub fn new(code: &Vec<u8>, compile_and_exe_setting: &CompileAndExeSetting) -> Self {
         Self {
             code: code.clone(),
+            files: HashMap::new(),
+            archive: None,
+            compile_and_exe_setting: compile_and_exe_setting.clone(),
+        }
+    }
+
+    pub fn new_with_files(
+        code: &[u8],
+        files: &HashMap<String, Vec<u8>>,
+        compile_and_exe_setting: &CompileAndExeSetting,
+    ) -> Self {
+        Self {
+            code: code.to_vec(),
+            files: files.clone(),
+            archive: None,
+            compile_and_exe_setting: compile_and_exe_setting.clone(),
+        }
+    }
+
+    pub fn new_with_archive(
+        archive: &[u8],
+        compile_and_exe_setting: &CompileAndExeSetting,
+    ) -> Self {
+        Self {
+            code: vec![],
+            files: HashMap::new(),
+            archive: Some(archive.to_vec()),
             compile_and_exe_setting: compile_and_exe_setting.clone(),
         }
     }
 
+    // Collects everything that goes into the compile dir, keyed by relative
+    // path. The archive is unpacked first, then the listed files and the main
+    // code are laid over it, and the problem files win over all of them.
+    #[cfg(feature = "compile")]
+    pub async fn source_files(
+        &self,
+        main_path: &str,
+        archive_limit: MemorySize,
+    ) -> Result<BTreeMap<String, Vec<u8>>, CompileResult> {
+        let mut source_files = BTreeMap::new();
+        if let Some(archive) = &self.archive {
+            #[cfg(feature = "archive")]
+            match unpack_archive(archive, archive_limit.as_bytes()) {
+                Err(result) => return Err(CompileResult::CompileError(result)),
+                Ok(result) => source_files.extend(result),
+            }
+            #[cfg(not(feature = "archive"))]
+            {
+                let _ = (archive, archive_limit);
+                return Err(CompileResult::InternalError(JudgeError::Unsupported(
+                    "archive support is not enabled".to_string(),
+                )));
+            }
+        }
+        for (path, content) in &self.files {
+            source_files.insert(path.clone(), content.clone());
+        }
+        if !self.code.is_empty() || source_files.is_empty() {
+            source_files.insert(main_path.to_string(), self.code.clone());
+        }
+        for path in source_files.keys() {
+            if let Err(result) = check_relative_path(path) {
+                return Err(CompileResult::CompileError(result.to_string()));
+            }
+        }
+        for (path, host_path) in &self.compile_and_exe_setting.problem_files {
+            if check_relative_path(path).is_err() {
+                return Err(CompileResult::SettingError);
+            }
+            match tokio::fs::read(host_path).await {
+                Err(result) => {
+                    return Err(CompileResult::InternalError(JudgeError::io(
+                        format!("read problem file {}", host_path).as_str(),
+                        result,
+                    )));
+                }
+                Ok(result) => {
+                    source_files.insert(path.clone(), result);
+                }
+            }
+        }
+        // A path can not be both a file and the dir of another file.
+        for path in source_files.keys() {
+            for ancestor in std::path::Path::new(path).ancestors().skip(1) {
+                if let Some(ancestor) = ancestor.to_str() {
+                    if source_files.contains_key(ancestor) {
+                        return Err(CompileResult::CompileError(format!(
+                            "{} conflicts with {}",
+                            path, ancestor
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(source_files)
+    }
+
     #[cfg(feature = "compile")]
-    pub async fn compile(&self) -> CompileResult {
+    pub async fn compile(
+        &self,
+        uid: u32,
+        time_limit: TimeSpan,
+        memory_limit: MemorySize,
+        process_limit: usize,
+        output_limit: MemorySize,
+    ) -> CompileResult {
         if self.compile_and_exe_setting.compile_command.is_empty() {
             if self.compile_and_exe_setting.exe_files.is_empty()
                 || self.compile_and_exe_setting.exe_files.len() > 1
             {
                 return CompileResult::SettingError;
             }
-            let mut exe_files = HashMap::new();
-            exe_files.insert(
-                self.compile_and_exe_setting.exe_files[0].clone(),
-                self.code.clone(),
-            );
-            return CompileResult::Ok(ExeCode {
+            let exe_files = match self
+                .source_files(
+                    &self.compile_and_exe_setting.exe_files[0],
+                    self.compile_and_exe_setting.archive_limit,
+                )
+                .await
+            {
+                Err(result) => return result,
+                Ok(result) => result.into_iter().collect(),
+            };
+            return CompileResult::Ok(Box::new(ExeCode {
                 exe_files: exe_files,
                 compile_and_exe_setting: self.compile_and_exe_setting.clone(),
-            });
+            }));
         }
+        let (command, args) = match turn_command_into_command_and_args(
+            self.compile_and_exe_setting.compile_command.as_str(),
+            &self
+                .compile_and_exe_setting
+                .command_placeholders(memory_limit),
+        ) {
+            Err(_) => {
+                return CompileResult::SettingError;
+            }
+            Ok(result) => result,
+        };
+        let mut cgroup = match Cgroup::new_tmp(memory_limit, process_limit) {
+            Err(result) => {
+                return CompileResult::InternalError(result);
+            }
+            Ok(result) => result,
+        };
         let id = format!(
             "emjudge-judgecore-compile-{}",
             uuid::Uuid::new_v4().simple().to_string()
         );
         let compile_dir = match TempDir::with_prefix(id.as_str()) {
             Err(result) => {
-                return CompileResult::InternalError(result.to_string());
+                return CompileResult::InternalError(JudgeError::io("create compile dir", result));
             }
             Ok(result) => result,
         };
-        let compile_command = self.compile_and_exe_setting.compile_command.as_str();
-        let raw_code_path = format!(
-            "{}/{}",
-            compile_dir.path().to_str().unwrap(),
-            self.compile_and_exe_setting.raw_code
-        );
-        let raw_code_path = raw_code_path.as_str();
-        match tokio::fs::File::create(raw_code_path).await {
+        let source_files = match self
+            .source_files(
+                &self.compile_and_exe_setting.raw_code,
+                self.compile_and_exe_setting.archive_limit,
+            )
+            .await
+        {
+            Err(result) => return result,
+            Ok(result) => result,
+        };
+        // Everything is written before the dir is handed over to the compiler
+        // user, so no link planted by the submission can redirect a write.
+        for (path, content) in &source_files {
+            let source_path = compile_dir.path().join(path);
+            if let Some(parent) = source_path.parent() {
+                if let Err(result) = tokio::fs::create_dir_all(parent).await {
+                    return CompileResult::InternalError(JudgeError::io(
+                        "create source dir",
+                        result,
+                    ));
+                }
+            }
+            match tokio::fs::File::create(source_path).await {
+                Err(result) => {
+                    return CompileResult::InternalError(JudgeError::io(
+                        "create source file",
+                        result,
+                    ));
+                }
+                Ok(mut file) => {
+                    if let Err(result) = file.write_all(content).await {
+                        return CompileResult::InternalError(JudgeError::io(
+                            "write source file",
+                            result,
+                        ));
+                    }
+                }
+            };
+        }
+        match tokio::process::Command::new("chown")
+            .arg("-R")
+            .arg(uid.to_string())
+            .arg(compile_dir.path())
+            .spawn()
+        {
+            Err(result) => {
+                return CompileResult::InternalError(JudgeError::spawn("chown", result));
+            }
+            Ok(mut p) => {
+                if let Err(result) = p.wait().await {
+                    return CompileResult::InternalError(JudgeError::io("wait for chown", result));
+                }
+            }
+        };
+        // The diagnostics file lives outside of the compile dir so that the
+        // unprivileged compiler can not swap it for a link to a host file.
+        let mut stderr = match tempfile::tempfile() {
             Err(result) => {
-                return CompileResult::InternalError(result.to_string());
+                return CompileResult::InternalError(JudgeError::io(
+                    "create compile diagnostics file",
+                    result,
+                ));
             }
-            Ok(mut file) => match file.write_all(&self.code).await {
+            Ok(result) => result,
+        };
+        let p = {
+            let stderr = match stderr.try_clone() {
                 Err(result) => {
-                    return CompileResult::InternalError(result.to_string());
+                    return CompileResult::InternalError(JudgeError::io(
+                        "clone compile diagnostics file",
+                        result,
+                    ))
                 }
-                Ok(_) => {}
-            },
+                Ok(result) => result,
+            };
+            let mut command = tokio::process::Command::new(command);
+            command
+                .stdout(Stdio::null())
+                .stderr(stderr)
+                .args(args)
+                .current_dir(compile_dir.path().to_str().unwrap());
+            set_env(
+                &mut command,
+                self.compile_and_exe_setting.inherit_env,
+                &self.compile_and_exe_setting.compile_env,
+            );
+            if let Err(result) = join_cgroup(&mut command, &cgroup) {
+                return CompileResult::InternalError(result);
+            }
+            drop_privileges(&mut command, uid);
+            command.spawn()
         };
-        let (command, args) = turn_command_into_command_and_args(compile_command);
-        let p = tokio::process::Command::new(command)
-            .stdout(Stdio::null())
-            .stderr(Stdio::piped())
-            .args(args)
-            .current_dir(compile_dir.path().to_str().unwrap())
-            .spawn();
         let mut p = match p {
-            Err(result) => return CompileResult::InternalError(result.to_string()),
+            Err(result) => {
+                return CompileResult::InternalError(JudgeError::spawn("compile_command", result))
+            }
             Ok(result) => result,
         };
-        if let Err(result) = p.wait().await {
-            return CompileResult::InternalError(result.to_string());
+        let start_time = Instant::now();
+        let result = tokio::time::timeout(Duration::from(time_limit), p.wait()).await;
+        let runtime = TimeSpan::from(start_time.elapsed());
+        let killed = kill_cgroup(&mut cgroup).await;
+        let _ = p.kill().await;
+        let _ = p.wait().await;
+        if let Err(result) = killed {
+            return CompileResult::InternalError(result);
         }
-        let mut stderr_output = String::new();
-        if let Err(result) = p
-            .stderr
-            .take()
-            .unwrap()
-            .read_to_string(&mut stderr_output)
-            .await
-        {
-            return CompileResult::InternalError(result.to_string());
+        let is_oom = match cgroup.update_cgroup_and_controller_and_check_oom() {
+            Err(result) => {
+                return CompileResult::InternalError(result);
+            }
+            Ok(result) => result,
+        };
+        let stderr_output = {
+            let mut buf = vec![];
+            if let Err(result) = stderr.rewind() {
+                return CompileResult::InternalError(JudgeError::io(
+                    "rewind compile diagnostics file",
+                    result,
+                ));
+            }
+            if let Err(result) = (&mut stderr)
+                .take(output_limit.as_bytes() as u64)
+                .read_to_end(&mut buf)
+            {
+                return CompileResult::InternalError(JudgeError::io(
+                    "read compile diagnostics file",
+                    result,
+                ));
+            }
+            String::from_utf8_lossy(&buf).to_string()
+        };
+        if is_oom {
+            return CompileResult::CompileMemoryLimitExceeded(stderr_output);
+        }
+        match result {
+            Err(_) => {
+                return CompileResult::CompileTimeLimitExceeded(stderr_output);
+            }
+            Ok(Err(result)) => {
+                return CompileResult::InternalError(JudgeError::io("wait for compiler", result));
+            }
+            Ok(Ok(_)) => {
+                if runtime > time_limit {
+                    return CompileResult::CompileTimeLimitExceeded(stderr_output);
+                }
+            }
         }
+        // The compile dir belongs to the submission user by now, so the files
+        // are opened without following links and must be regular files, and
+        // together they are capped by the memory limit as they are kept in
+        // memory afterwards.
         let mut exe_files = HashMap::new();
+        let mut exe_files_size = 0;
         for exe_file in &self.compile_and_exe_setting.exe_files {
             let mut exe_code_file = Vec::new();
-            let exe_code_path = format!("{}/{}", compile_dir.path().to_str().unwrap(), exe_file);
-            let exe_code_path = exe_code_path.as_str();
-            let _ = match tokio::fs::File::open(exe_code_path).await {
-                Ok(mut result) => result.read_to_end(&mut exe_code_file).await,
+            let mut exe_code_path = compile_dir.path().to_path_buf();
+            for component in std::path::Path::new(exe_file).components() {
+                match tokio::fs::symlink_metadata(&exe_code_path).await {
+                    Ok(metadata) if metadata.is_dir() => {}
+                    _ => return CompileResult::CompileError(stderr_output),
+                }
+                exe_code_path.push(component);
+            }
+            let file = match tokio::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+                .open(&exe_code_path)
+                .await
+            {
+                Ok(result) => result,
                 Err(result) => match result.kind() {
                     std::io::ErrorKind::NotFound => {
                         return CompileResult::CompileError(stderr_output);
                     }
+                    _ if result.raw_os_error() == Some(libc::ELOOP) => {
+                        return CompileResult::CompileError(stderr_output);
+                    }
                     _ => {
-                        return CompileResult::InternalError(result.to_string());
+                        return CompileResult::InternalError(JudgeError::io(
+                            "open compiled file",
+                            result,
+                        ));
                     }
                 },
             };
+            match file.metadata().await {
+                Err(result) => {
+                    return CompileResult::InternalError(JudgeError::io(
+                        "stat compiled file",
+                        result,
+                    ));
+                }
+                Ok(metadata) => {
+                    if !metadata.is_file() {
+                        return CompileResult::CompileError(stderr_output);
+                    }
+                }
+            }
+            let limit = memory_limit.as_bytes().saturating_sub(exe_files_size);
+            if let Err(result) = file
+                .take(limit as u64 + 1)
+                .read_to_end(&mut exe_code_file)
+                .await
+            {
+                return CompileResult::InternalError(JudgeError::io("read compiled file", result));
+            }
+            exe_files_size += exe_code_file.len();
+            if exe_files_size > memory_limit.as_bytes() {
+                return CompileResult::CompileMemoryLimitExceeded(stderr_output);
+            }
             exe_files.insert(exe_file.clone(), exe_code_file);
         }
         if exe_files.is_empty() {
             return CompileResult::SettingError;
         }
-        CompileResult::Ok(ExeCode {
+        CompileResult::Ok(Box::new(ExeCode {
             exe_files: exe_files,
             compile_and_exe_setting: self.compile_and_exe_setting.clone(),
-        })
+        }))
     }
 }
 
+// The limits one run of a program is under, apart from the memory and process
+// limits, which belong to the cgroup it runs in.
+#[cfg(feature = "run")]
+#[derive(Debug, Clone, Copy)]
+pub struct RunLimits {
+    pub time_limit: TimeSpan,
+    pub cpu_time_limit: TimeSpan,
+    pub output_limit: MemorySize,
+    pub network_isolation: bool,
+}
+
 #[derive(Debug)]
 pub struct ExeResources {
     pub uid: u32,
@@ -155,6 +469,15 @@ pub struct ExeResources {
     pub stderr_path: String,
     pub interactorin_path: String,
     pub interactorout_path: String,
+    pub input_path: String,
+    pub output_path: String,
+    pub answer_path: String,
+    pub report_path: String,
+    pub jail_dir: Option<TempDir>,
+    pub jail_mounts: Vec<String>,
+    pub seccomp_syscalls: Vec<i64>,
+    pub compile_and_exe_setting: CompileAndExeSetting,
+    pub cpu_affinity: Option<usize>,
 }
 
 #[cfg(feature = "run")]
@@ -163,17 +486,29 @@ impl ExeResources {
         uid: u32,
         exe_files: &HashMap<String, Vec<u8>>,
         compile_and_exe_setting: &CompileAndExeSetting,
+        jail_mounts: Option<&Vec<String>>,
     ) -> InitExeResourceResult {
         if check_admin_privilege() == false {
             return InitExeResourceResult::PermissionDenied;
         }
+        if turn_command_into_command_and_args(
+            compile_and_exe_setting.exe_command.as_str(),
+            &compile_and_exe_setting.command_placeholders(MemorySize::default()),
+        )
+        .is_err()
+        {
+            return InitExeResourceResult::SettingError;
+        }
         let id = format!(
             "emjudge-judgecore-exe-{}",
             uuid::Uuid::new_v4().simple().to_string()
         );
         let exe_dir = match TempDir::with_prefix(id.as_str()) {
             Err(result) => {
-                return InitExeResourceResult::InternalError(result.to_string());
+                return InitExeResourceResult::InternalError(JudgeError::io(
+                    "create exe dir",
+                    result,
+                ));
             }
             Ok(result) => result,
         };
@@ -188,23 +523,36 @@ impl ExeResources {
         let stderr_path = format!("{}/stderr", exe_dir_path);
         let interactorin_path = format!("{}/interactorin", exe_dir_path);
         let interactorout_path = format!("{}/interactorout", exe_dir_path);
+        let input_path = format!("{}/input", exe_dir_path);
+        let output_path = format!("{}/output", exe_dir_path);
+        let answer_path = format!("{}/answer", exe_dir_path);
+        let report_path = format!("{}/report.xml", exe_dir_path);
         let mut all_file_path_vec = vec![interactorin_path.clone(), interactorout_path.clone()];
         match tokio::fs::File::create(interactorin_path.as_str()).await {
             Err(result) => {
-                return InitExeResourceResult::InternalError(result.to_string());
+                return InitExeResourceResult::InternalError(JudgeError::io(
+                    "create interactorin file",
+                    result,
+                ));
             }
             Ok(_) => {}
         };
         match tokio::fs::metadata(interactorin_path.as_str()).await {
             Err(result) => {
-                return InitExeResourceResult::InternalError(result.to_string());
+                return InitExeResourceResult::InternalError(JudgeError::io(
+                    "read interactorin metadata",
+                    result,
+                ));
             }
             Ok(metadata) => {
                 let mut permissions = metadata.permissions();
                 permissions.set_mode(0o700);
                 match tokio::fs::set_permissions(&interactorin_path, permissions.clone()).await {
                     Err(result) => {
-                        return InitExeResourceResult::InternalError(result.to_string());
+                        return InitExeResourceResult::InternalError(JudgeError::io(
+                            "set interactorin permissions",
+                            result,
+                        ));
                     }
                     Ok(_) => {}
                 }
@@ -213,20 +561,29 @@ impl ExeResources {
 
         match tokio::fs::File::create(interactorout_path.as_str()).await {
             Err(result) => {
-                return InitExeResourceResult::InternalError(result.to_string());
+                return InitExeResourceResult::InternalError(JudgeError::io(
+                    "create interactorout file",
+                    result,
+                ));
             }
             Ok(_) => {}
         };
         match tokio::fs::metadata(interactorout_path.as_str()).await {
             Err(result) => {
-                return InitExeResourceResult::InternalError(result.to_string());
+                return InitExeResourceResult::InternalError(JudgeError::io(
+                    "read interactorout metadata",
+                    result,
+                ));
             }
             Ok(metadata) => {
                 let mut permissions = metadata.permissions();
                 permissions.set_mode(0o700);
                 match tokio::fs::set_permissions(&interactorout_path, permissions.clone()).await {
                     Err(result) => {
-                        return InitExeResourceResult::InternalError(result.to_string());
+                        return InitExeResourceResult::InternalError(JudgeError::io(
+                            "set interactorout permissions",
+                            result,
+                        ));
                     }
                     Ok(_) => {}
                 }
@@ -235,27 +592,47 @@ impl ExeResources {
 
         for (exe_file, script) in exe_files {
             let exe_code_path = format!("{}/{}", exe_dir_path, exe_file);
+            if let Some(parent) = std::path::Path::new(exe_code_path.as_str()).parent() {
+                if let Err(result) = tokio::fs::create_dir_all(parent).await {
+                    return InitExeResourceResult::InternalError(JudgeError::io(
+                        "create exe dir",
+                        result,
+                    ));
+                }
+            }
             match tokio::fs::File::create(exe_code_path.as_str()).await {
                 Err(result) => {
-                    return InitExeResourceResult::InternalError(result.to_string());
+                    return InitExeResourceResult::InternalError(JudgeError::io(
+                        "create exe file",
+                        result,
+                    ));
                 }
                 Ok(mut file) => match file.write_all(script).await {
                     Err(result) => {
-                        return InitExeResourceResult::InternalError(result.to_string());
+                        return InitExeResourceResult::InternalError(JudgeError::io(
+                            "write exe file",
+                            result,
+                        ));
                     }
                     Ok(_) => {}
                 },
             };
             match tokio::fs::metadata(exe_code_path.as_str()).await {
                 Err(result) => {
-                    return InitExeResourceResult::InternalError(result.to_string());
+                    return InitExeResourceResult::InternalError(JudgeError::io(
+                        "read exe file metadata",
+                        result,
+                    ));
                 }
                 Ok(metadata) => {
                     let mut permissions = metadata.permissions();
                     permissions.set_mode(0o500);
                     match tokio::fs::set_permissions(&exe_code_path, permissions.clone()).await {
                         Err(result) => {
-                            return InitExeResourceResult::InternalError(result.to_string());
+                            return InitExeResourceResult::InternalError(JudgeError::io(
+                                "set exe file permissions",
+                                result,
+                            ));
                         }
                         Ok(_) => {}
                     }
@@ -269,15 +646,42 @@ impl ExeResources {
             .spawn()
         {
             Err(result) => {
-                return InitExeResourceResult::InternalError(result.to_string());
+                return InitExeResourceResult::InternalError(JudgeError::spawn("chown", result));
             }
             Ok(mut p) => match p.wait().await {
-                Err(result) => return InitExeResourceResult::InternalError(result.to_string()),
+                Err(result) => {
+                    return InitExeResourceResult::InternalError(JudgeError::io(
+                        "wait for chown",
+                        result,
+                    ))
+                }
                 Ok(_) => {}
             },
         };
 
-        InitExeResourceResult::Ok(Self {
+        let seccomp_syscalls =
+            match get_profile_syscalls(compile_and_exe_setting.seccomp_profile.as_str()) {
+                Err(result) => {
+                    return InitExeResourceResult::InternalError(result);
+                }
+                Ok(result) => result,
+            };
+
+        let (jail_dir, jail_mounts) = match jail_mounts {
+            None => (None, vec![]),
+            Some(jail_mounts) => {
+                let mut jail_mounts = jail_mounts.clone();
+                jail_mounts.extend(compile_and_exe_setting.jail_mounts.iter().cloned());
+                match prepare_jail(exe_dir_path.as_str(), &jail_mounts).await {
+                    Err(result) => {
+                        return InitExeResourceResult::InternalError(result);
+                    }
+                    Ok(result) => (Some(result), jail_mounts),
+                }
+            }
+        };
+
+        InitExeResourceResult::Ok(Box::new(Self {
             uid: uid,
             exe_dir: exe_dir,
             exe_command: compile_and_exe_setting.exe_command.clone(),
@@ -286,187 +690,341 @@ impl ExeResources {
             stderr_path: stderr_path,
             interactorin_path: interactorin_path,
             interactorout_path: interactorout_path,
-        })
+            input_path,
+            output_path,
+            answer_path,
+            report_path,
+            jail_dir,
+            jail_mounts,
+            seccomp_syscalls,
+            compile_and_exe_setting: compile_and_exe_setting.clone(),
+            cpu_affinity: None,
+        }))
     }
 
-    async fn read_stdout(&self) -> Result<Vec<u8>, String> {
-        let mut buf = vec![];
-        match tokio::fs::File::open(self.stdout_path.as_str()).await {
-            Err(result) => {
-                return Err(result.to_string());
-            }
-            Ok(mut file) => match file.read_to_end(&mut buf).await {
-                Err(result) => {
-                    return Err(result.to_string());
-                }
-                Ok(_) => Ok(buf),
-            },
-        }
+    fn command_and_args(
+        &self,
+        cgroup: &Cgroup,
+        extra_args: &[&str],
+    ) -> Result<(String, Vec<String>), JudgeError> {
+        let (command, mut args) = turn_command_into_command_and_args(
+            self.exe_command.as_str(),
+            &self
+                .compile_and_exe_setting
+                .command_placeholders(cgroup.get_memory_limit()),
+        )?;
+        args.extend(extra_args.iter().map(|arg| arg.to_string()));
+        Ok((command, args))
     }
 
-    async fn read_stderr(&self) -> Result<Vec<u8>, String> {
-        let mut buf = vec![];
-        match tokio::fs::File::open(self.stderr_path.as_str()).await {
-            Err(result) => {
-                return Err(result.to_string());
-            }
-            Ok(mut file) => match file.read_to_end(&mut buf).await {
-                Err(result) => {
-                    return Err(result.to_string());
-                }
-                Ok(_) => Ok(buf),
-            },
+    fn seccomp_filter(&self) -> Result<Option<SeccompFilter>, JudgeError> {
+        if self.seccomp_syscalls.is_empty() {
+            return Ok(None);
         }
+        SeccompFilter::new(&self.seccomp_syscalls).map(Some)
     }
 
-    async fn read_interactorout(&self) -> Result<Vec<u8>, String> {
-        let mut buf = vec![];
-        match tokio::fs::File::open(self.interactorout_path.as_str()).await {
-            Err(result) => {
-                return Err(result.to_string());
-            }
-            Ok(mut file) => match file.read_to_end(&mut buf).await {
-                Err(result) => {
-                    return Err(result.to_string());
-                }
-                Ok(_) => Ok(buf),
-            },
+    fn jail(&self) -> Result<Option<Jail>, JudgeError> {
+        let jail_dir = match &self.jail_dir {
+            None => return Ok(None),
+            Some(result) => result,
+        };
+        let to_c_string = |path: &str| match std::ffi::CString::new(path) {
+            Err(result) => Err(JudgeError::Setting(result.to_string())),
+            Ok(result) => Ok(result),
+        };
+        let exe_dir_path = self.exe_dir.path().to_string_lossy().to_string();
+        let mut mounts = vec![];
+        for jail_mount in &self.jail_mounts {
+            mounts.push((
+                to_c_string(jail_mount.as_str())?,
+                to_c_string(
+                    format!("{}{}", jail_dir.path().to_string_lossy(), jail_mount).as_str(),
+                )?,
+                false,
+            ));
+        }
+        mounts.push((
+            to_c_string(exe_dir_path.as_str())?,
+            to_c_string(format!("{}{}", jail_dir.path().to_string_lossy(), exe_dir_path).as_str())?,
+            true,
+        ));
+        for device in JAIL_DEVICES {
+            mounts.push((
+                to_c_string(device)?,
+                to_c_string(format!("{}{}", jail_dir.path().to_string_lossy(), device).as_str())?,
+                true,
+            ));
         }
+        Ok(Some(Jail {
+            root: to_c_string(jail_dir.path().to_string_lossy().as_ref())?,
+            mounts,
+            work_dir: to_c_string(exe_dir_path.as_str())?,
+        }))
     }
 
     pub async fn run_to_end(
         &mut self,
         input: &Vec<u8>,
         cgroup: &mut Cgroup,
-        time_limit: TimeSpan,
-        output_limit: MemorySize,
+        limits: RunLimits,
+    ) -> RunToEndResult {
+        self.run_to_end_with_args(input, cgroup, limits, &[]).await
+    }
+
+    async fn run_to_end_with_args(
+        &mut self,
+        input: &[u8],
+        cgroup: &mut Cgroup,
+        limits: RunLimits,
+        extra_args: &[&str],
     ) -> RunToEndResult {
+        let RunLimits {
+            time_limit,
+            cpu_time_limit,
+            output_limit,
+            network_isolation,
+        } = limits;
         match tokio::fs::File::create(self.stdin_path.as_str()).await {
             Err(result) => {
-                return RunToEndResult::InternalError(result.to_string());
+                return RunToEndResult::InternalError(JudgeError::io("create stdin file", result));
             }
             Ok(mut file) => match file.write_all(input).await {
                 Err(result) => {
-                    return RunToEndResult::InternalError(result.to_string());
+                    return RunToEndResult::InternalError(JudgeError::io(
+                        "write stdin file",
+                        result,
+                    ));
                 }
                 Ok(_) => {}
             },
         };
         match cgroup.reset_max_usage_in_bytes() {
             Err(result) => {
-                return RunToEndResult::InternalError(result.to_string());
+                return RunToEndResult::InternalError(result);
             }
             Ok(_) => {}
         }
+        let cpu_usage_before = match cgroup
+            .update_cgroup_and_controller()
+            .and_then(|_| cgroup.get_cpu_usage_in_nanoseconds())
+        {
+            Err(result) => {
+                return RunToEndResult::InternalError(result);
+            }
+            Ok(result) => result,
+        };
+        let seccomp = match self.seccomp_filter() {
+            Err(result) => {
+                return RunToEndResult::InternalError(result);
+            }
+            Ok(result) => result,
+        };
         let p = {
             let stdin = match std::fs::File::open(self.stdin_path.as_str()) {
-                Err(result) => return RunToEndResult::InternalError(result.to_string()),
+                Err(result) => {
+                    return RunToEndResult::InternalError(JudgeError::io("open stdin file", result))
+                }
                 Ok(result) => result,
             };
             let stdout = match std::fs::File::create(self.stdout_path.as_str()) {
-                Err(result) => return RunToEndResult::InternalError(result.to_string()),
+                Err(result) => {
+                    return RunToEndResult::InternalError(JudgeError::io(
+                        "create stdout file",
+                        result,
+                    ))
+                }
                 Ok(result) => result,
             };
             let stderr = match std::fs::File::create(self.stderr_path.as_str()) {
-                Err(result) => return RunToEndResult::InternalError(result.to_string()),
+                Err(result) => {
+                    return RunToEndResult::InternalError(JudgeError::io(
+                        "create stderr file",
+                        result,
+                    ))
+                }
+                Ok(result) => result,
+            };
+            let jail = match self.jail() {
+                Err(result) => return RunToEndResult::InternalError(result),
                 Ok(result) => result,
             };
-            let (command, args) = turn_command_into_command_and_args(self.exe_command.as_str());
-            tokio::process::Command::new(command)
+            let (command, args) = match self.command_and_args(cgroup, extra_args) {
+                Err(result) => return RunToEndResult::InternalError(result),
+                Ok(result) => result,
+            };
+            let mut command = tokio::process::Command::new(command);
+            command
                 .stdin(stdin)
                 .stdout(stdout)
                 .stderr(stderr)
                 .args(args)
-                .current_dir(self.exe_dir.path())
-                .uid(self.uid)
+                .current_dir(self.exe_dir.path());
+            set_env(
+                &mut command,
+                self.compile_and_exe_setting.inherit_env,
+                &self.compile_and_exe_setting.env,
+            );
+            if let Err(result) = join_cgroup(&mut command, cgroup) {
+                return RunToEndResult::InternalError(result);
+            }
+            let isolation = match set_uid(&mut command, self.uid, network_isolation, jail) {
+                Err(result) => return RunToEndResult::InternalError(result),
+                Ok(result) => result,
+            };
+            limit_cpu_time(&mut command, cpu_time_limit);
+            limit_file_size(&mut command, output_limit);
+            pin_to_cpu(&mut command, self.cpu_affinity);
+            if let Some(seccomp) = &seccomp {
+                seccomp.apply(&mut command);
+            }
+            command
                 .spawn()
+                .map_err(|result| isolation.spawn_error("exe_command", result))
         };
         match p {
-            Err(result) => {
-                return RunToEndResult::InternalError(result.to_string());
-            }
+            Err(result) => RunToEndResult::InternalError(result),
             Ok(mut p) => {
                 let start_time = Instant::now();
-                match cgroup.add_task(p.id().unwrap() as i32) {
-                    Err(result) => {
-                        let _ = p.kill().await;
-                        return RunToEndResult::InternalError(result.to_string());
-                    }
-                    Ok(_) => {}
-                }
+                let seccomp_monitor = match seccomp {
+                    None => None,
+                    Some(seccomp) => match seccomp.monitor(p.id().unwrap() as i32) {
+                        Err(result) => {
+                            let _ = p.kill().await;
+                            return RunToEndResult::InternalError(result);
+                        }
+                        Ok(result) => Some(result),
+                    },
+                };
                 let result = tokio::time::timeout(Duration::from(time_limit), p.wait()).await;
                 let runtime = TimeSpan::from(start_time.elapsed());
+                let killed = kill_cgroup(cgroup).await;
                 let _ = p.kill().await;
-                let _ = p.wait().await;
+                let exit_status = p.wait().await.ok();
+                let restricted_syscall = match seccomp_monitor {
+                    None => None,
+                    Some(seccomp_monitor) => seccomp_monitor.stop().await,
+                };
+                if let Err(result) = killed {
+                    return RunToEndResult::InternalError(result);
+                }
+                let exit_code = exit_status.and_then(|status| status.code());
+                let signal = exit_status.and_then(|status| status.signal());
+                let core_dumped = exit_status.is_some_and(|status| status.core_dumped());
                 let is_oom = match cgroup.update_cgroup_and_controller_and_check_oom() {
                     Err(result) => {
-                        return RunToEndResult::InternalError(result.to_string());
+                        return RunToEndResult::InternalError(result);
+                    }
+                    Ok(result) => result,
+                };
+                let process_limit_exceeded = match cgroup.check_pids_max() {
+                    Err(result) => {
+                        return RunToEndResult::InternalError(result);
                     }
                     Ok(result) => result,
                 };
                 let memory = match cgroup.get_max_usage_in_bytes() {
                     Err(result) => {
-                        return RunToEndResult::InternalError(result.to_string());
+                        return RunToEndResult::InternalError(result);
                     }
                     Ok(result) => MemorySize::from_bytes(result as usize),
                 };
-                let stdout = {
-                    match check_file_limit(self.stdout_path.as_str(), output_limit).await {
-                        Err(result) => {
-                            return RunToEndResult::InternalError(result);
-                        }
-                        Ok(result) => {
-                            if result != "" {
-                                return RunToEndResult::OutputLimitExceeded(ProcessResource {
-                                    memory: memory,
-                                    runtime: runtime,
-                                    stdout: vec![],
-                                    stderr: vec![],
-                                });
-                            }
-                        }
-                    }
-                    match self.read_stdout().await {
-                        Ok(result) => result,
-                        Err(result) => return RunToEndResult::InternalError(result),
+                let cputime = match cgroup.get_cpu_usage_in_nanoseconds() {
+                    Err(result) => {
+                        return RunToEndResult::InternalError(result);
                     }
+                    Ok(result) => TimeSpan::from(Duration::from_nanos(
+                        result.saturating_sub(cpu_usage_before),
+                    )),
                 };
-                let stderr = {
-                    match check_file_limit(self.stderr_path.as_str(), output_limit).await {
-                        Err(result) => {
-                            return RunToEndResult::InternalError(result);
-                        }
-                        Ok(result) => {
-                            if result != "" {
-                                return RunToEndResult::OutputLimitExceeded(ProcessResource {
-                                    memory: memory,
-                                    runtime: runtime,
-                                    stdout: vec![],
-                                    stderr: vec![],
-                                });
-                            }
-                        }
-                    }
-                    match self.read_stderr().await {
+                let (stdout, stdout_exceeded) =
+                    match read_file_with_limit(self.stdout_path.as_str(), output_limit).await {
+                        Err(result) => return RunToEndResult::InternalError(result),
                         Ok(result) => result,
+                    };
+                let (stderr, stderr_exceeded) =
+                    match read_file_with_limit(self.stderr_path.as_str(), output_limit).await {
                         Err(result) => return RunToEndResult::InternalError(result),
-                    }
-                };
+                        Ok(result) => result,
+                    };
+                if stdout_exceeded || stderr_exceeded {
+                    return RunToEndResult::OutputLimitExceeded(ProcessResource {
+                        memory,
+                        runtime,
+                        cputime,
+                        exit_code,
+                        signal,
+                        core_dumped,
+                        process_limit_exceeded,
+                        stdout,
+                        stderr,
+                        transcript: None,
+                    });
+                }
+                if let Some(syscall) = restricted_syscall {
+                    return RunToEndResult::RestrictedFunction(
+                        syscall,
+                        ProcessResource {
+                            memory,
+                            runtime,
+                            cputime,
+                            exit_code,
+                            signal,
+                            core_dumped,
+                            process_limit_exceeded,
+                            stdout,
+                            stderr,
+                            transcript: None,
+                        },
+                    );
+                }
                 if is_oom {
                     return RunToEndResult::MemoryLimitExceeded(ProcessResource {
                         memory: memory,
                         runtime: runtime,
+                        cputime,
+                        exit_code,
+                        signal,
+                        core_dumped,
+                        process_limit_exceeded,
                         stdout: stdout,
                         stderr: stderr,
+                        transcript: None,
                     });
                 }
+                if cputime > cpu_time_limit {
+                    return RunToEndResult::TimeLimitExceeded(
+                        TimeLimitKind::CpuTime,
+                        ProcessResource {
+                            memory,
+                            runtime,
+                            cputime,
+                            exit_code,
+                            signal,
+                            core_dumped,
+                            process_limit_exceeded,
+                            stdout,
+                            stderr,
+                            transcript: None,
+                        },
+                    );
+                }
                 let in_time_result = if result.is_err() || runtime > time_limit {
-                    return RunToEndResult::TimeLimitExceeded(ProcessResource {
-                        memory: memory,
-                        runtime: runtime,
-                        stdout: stdout,
-                        stderr: stderr,
-                    });
+                    return RunToEndResult::TimeLimitExceeded(
+                        TimeLimitKind::WallTime,
+                        ProcessResource {
+                            memory,
+                            runtime,
+                            cputime,
+                            exit_code,
+                            signal,
+                            core_dumped,
+                            process_limit_exceeded,
+                            stdout,
+                            stderr,
+                            transcript: None,
+                        },
+                    );
                 } else {
                     result.unwrap()
                 };
@@ -474,151 +1032,472 @@ impl ExeResources {
                     return RunToEndResult::Ok(ProcessResource {
                         memory: memory,
                         runtime: runtime,
+                        cputime,
+                        exit_code,
+                        signal,
+                        core_dumped,
+                        process_limit_exceeded,
                         stdout: stdout,
                         stderr: stderr,
+                        transcript: None,
                     });
                 } else {
                     return RunToEndResult::RuntimeError(ProcessResource {
                         memory: memory,
                         runtime: runtime,
+                        cputime,
+                        exit_code,
+                        signal,
+                        core_dumped,
+                        process_limit_exceeded,
                         stdout: stdout,
                         stderr: stderr,
+                        transcript: None,
                     });
                 }
             }
         }
     }
 
+    // Runs a checker on one test. A plain checker gets every part as a length
+    // prefixed blob on stdin, the input being left out when there is none,
+    // while a testlib checker reads them from files named in its arguments.
+    pub async fn run_eval(
+        &mut self,
+        input: Option<&Vec<u8>>,
+        output: &Vec<u8>,
+        answer: &Vec<u8>,
+        testlib: bool,
+        cgroup: &mut Cgroup,
+        limits: RunLimits,
+    ) -> RunToEndResult {
+        let mut eval_input = vec![];
+        if testlib {
+            for (path, content) in [
+                (&self.input_path, input.map(|input| input.as_slice())),
+                (&self.output_path, Some(output.as_slice())),
+                (&self.answer_path, Some(answer.as_slice())),
+                (&self.report_path, None),
+            ] {
+                if let Err(result) =
+                    write_testlib_file(path, content.unwrap_or_default(), self.uid).await
+                {
+                    return RunToEndResult::InternalError(result);
+                }
+            }
+        } else {
+            for part in input.into_iter().chain([output, answer]) {
+                eval_input.append(&mut Vec::from((part.len() as u64).to_le_bytes()));
+                eval_input.append(&mut part.clone());
+            }
+        }
+        self.run_to_end_with_args(
+            &eval_input,
+            cgroup,
+            limits,
+            if testlib { &TESTLIB_CHECKER_ARGS } else { &[] },
+        )
+        .await
+        .into_eval(testlib)
+    }
+
+    pub async fn eval_verdict(
+        &self,
+        eval: &ProcessResource,
+        testlib: bool,
+        output_limit: MemorySize,
+    ) -> Result<EvalVerdict, JudgeError> {
+        if !testlib {
+            return Ok(EvalVerdict::from_process_resource(eval));
+        }
+        let (report, _) = read_file_with_limit(self.report_path.as_str(), output_limit).await?;
+        Ok(EvalVerdict::from_testlib(
+            eval,
+            String::from_utf8_lossy(&report).as_ref(),
+        ))
+    }
+
     pub async fn run_with_interactor(
         &mut self,
         cgroup: &mut Cgroup,
-        time_limit: TimeSpan,
+        limits: RunLimits,
         interactor_exe_resources: &mut ExeResources,
         interactor_cgroup: &mut Cgroup,
         interactor_extra_time_limit: TimeSpan,
         interactor_input: &Vec<u8>,
-        output_limit: MemorySize,
+        interactor_testlib: bool,
+        transcript_limit: Option<MemorySize>,
     ) -> RunWithInteractorResult {
+        let RunLimits {
+            time_limit,
+            cpu_time_limit,
+            output_limit,
+            network_isolation,
+        } = limits;
         match cgroup.reset_max_usage_in_bytes() {
             Err(result) => {
-                return RunWithInteractorResult::InternalError(result.to_string());
+                return RunWithInteractorResult::InternalError(result);
             }
             Ok(_) => {}
         }
 
         match interactor_cgroup.reset_max_usage_in_bytes() {
             Err(result) => {
-                return RunWithInteractorResult::InternalError(result.to_string());
+                return RunWithInteractorResult::InternalError(result);
             }
             Ok(_) => {}
         }
 
-        let (pipe_to_interactor_read, pipe_to_interactor_write) = match nix::unistd::pipe() {
+        let cpu_usage_before = match cgroup
+            .update_cgroup_and_controller()
+            .and_then(|_| cgroup.get_cpu_usage_in_nanoseconds())
+        {
             Err(result) => {
-                return RunWithInteractorResult::InternalError(result.to_string());
+                return RunWithInteractorResult::InternalError(result);
             }
             Ok(result) => result,
         };
 
-        let (pipe_from_interactor_read, pipe_from_interactor_write) = match nix::unistd::pipe() {
+        let interactor_cpu_usage_before = match interactor_cgroup
+            .update_cgroup_and_controller()
+            .and_then(|_| interactor_cgroup.get_cpu_usage_in_nanoseconds())
+        {
             Err(result) => {
-                return RunWithInteractorResult::InternalError(result.to_string());
+                return RunWithInteractorResult::InternalError(result);
             }
             Ok(result) => result,
         };
 
+        let (pipe_to_interactor_read, pipe_to_interactor_write) =
+            match pipe(nix::fcntl::OFlag::O_CLOEXEC) {
+                Err(result) => return RunWithInteractorResult::InternalError(result),
+                Ok(result) => result,
+            };
+
+        let (pipe_from_interactor_read, pipe_from_interactor_write) =
+            match pipe(nix::fcntl::OFlag::O_CLOEXEC) {
+                Err(result) => return RunWithInteractorResult::InternalError(result),
+                Ok(result) => result,
+            };
+
+        // To record a transcript the tested program gets its own pair of
+        // pipes, and everything is forwarded from one pair to the other.
+        let (tested_stdin, tested_stdout, tee_ends) = match transcript_limit {
+            None => (pipe_from_interactor_read, pipe_to_interactor_write, None),
+            Some(transcript_limit) => {
+                let (pipe_to_tested_read, pipe_to_tested_write) =
+                    match pipe(nix::fcntl::OFlag::O_CLOEXEC) {
+                        Err(result) => return RunWithInteractorResult::InternalError(result),
+                        Ok(result) => result,
+                    };
+                let (pipe_from_tested_read, pipe_from_tested_write) =
+                    match pipe(nix::fcntl::OFlag::O_CLOEXEC) {
+                        Err(result) => return RunWithInteractorResult::InternalError(result),
+                        Ok(result) => result,
+                    };
+                match tee_ends(
+                    pipe_from_tested_read,
+                    pipe_to_interactor_write,
+                    pipe_from_interactor_read,
+                    pipe_to_tested_write,
+                ) {
+                    Err(result) => {
+                        return RunWithInteractorResult::InternalError(JudgeError::io(
+                            "set up transcript pipes",
+                            result,
+                        ));
+                    }
+                    Ok((from_tested, to_interactor, from_interactor, to_tested)) => (
+                        pipe_to_tested_read,
+                        pipe_from_tested_write,
+                        Some((
+                            from_tested,
+                            to_interactor,
+                            from_interactor,
+                            to_tested,
+                            Arc::new(Mutex::new(Transcript::new(transcript_limit))),
+                        )),
+                    ),
+                }
+            }
+        };
+
         match tokio::fs::File::create(interactor_exe_resources.interactorin_path.as_str()).await {
             Err(result) => {
-                return RunWithInteractorResult::InternalError(result.to_string());
+                return RunWithInteractorResult::InternalError(JudgeError::io(
+                    "create interactorin file",
+                    result,
+                ));
             }
             Ok(mut file) => match file.write_all(interactor_input).await {
                 Err(result) => {
-                    return RunWithInteractorResult::InternalError(result.to_string());
+                    return RunWithInteractorResult::InternalError(JudgeError::io(
+                        "write interactorin file",
+                        result,
+                    ));
                 }
                 Ok(_) => {}
             },
         };
 
-        let mut interactor_p = {
+        if interactor_testlib {
+            for path in [
+                &interactor_exe_resources.answer_path,
+                &interactor_exe_resources.report_path,
+            ] {
+                if let Err(result) =
+                    write_testlib_file(path, &[], interactor_exe_resources.uid).await
+                {
+                    return RunWithInteractorResult::InternalError(result);
+                }
+            }
+        }
+
+        let (mut interactor_p, interactor_seccomp) = {
             let stderr = match std::fs::File::create(interactor_exe_resources.stderr_path.as_str())
             {
                 Err(result) => {
-                    return RunWithInteractorResult::InternalError(result.to_string());
+                    return RunWithInteractorResult::InternalError(JudgeError::io(
+                        "create interactor stderr file",
+                        result,
+                    ));
                 }
                 Ok(result) => result,
             };
+            let jail = match interactor_exe_resources.jail() {
+                Err(result) => return RunWithInteractorResult::InternalError(result),
+                Ok(result) => result,
+            };
+            let seccomp = match interactor_exe_resources.seccomp_filter() {
+                Err(result) => return RunWithInteractorResult::InternalError(result),
+                Ok(result) => result,
+            };
+            let testlib_args: &[&str] = if interactor_testlib {
+                &TESTLIB_INTERACTOR_ARGS
+            } else {
+                &[]
+            };
             let (command, args) =
-                turn_command_into_command_and_args(interactor_exe_resources.exe_command.as_str());
+                match interactor_exe_resources.command_and_args(interactor_cgroup, testlib_args) {
+                    Err(result) => return RunWithInteractorResult::InternalError(result),
+                    Ok(result) => result,
+                };
 
-            match tokio::process::Command::new(command)
-                .stdin(unsafe { std::fs::File::from_raw_fd(pipe_to_interactor_read) })
-                .stdout(unsafe { std::fs::File::from_raw_fd(pipe_from_interactor_write) })
+            let mut command = tokio::process::Command::new(command);
+            command
+                .stdin(pipe_to_interactor_read)
+                .stdout(pipe_from_interactor_write)
                 .stderr(stderr)
                 .args(args)
-                .uid(interactor_exe_resources.uid)
-                .current_dir(interactor_exe_resources.exe_dir.path())
-                .spawn()
-            {
+                .current_dir(interactor_exe_resources.exe_dir.path());
+            set_env(
+                &mut command,
+                interactor_exe_resources.compile_and_exe_setting.inherit_env,
+                &interactor_exe_resources.compile_and_exe_setting.env,
+            );
+            if let Err(result) = join_cgroup(&mut command, interactor_cgroup) {
+                return RunWithInteractorResult::InternalError(result);
+            }
+            let isolation = match set_uid(
+                &mut command,
+                interactor_exe_resources.uid,
+                network_isolation,
+                jail,
+            ) {
+                Err(result) => return RunWithInteractorResult::InternalError(result),
+                Ok(result) => result,
+            };
+            limit_file_size(&mut command, output_limit);
+            pin_to_cpu(&mut command, interactor_exe_resources.cpu_affinity);
+            if let Some(seccomp) = &seccomp {
+                seccomp.apply(&mut command);
+            }
+            match command.spawn() {
                 Err(result) => {
-                    return RunWithInteractorResult::InternalError(result.to_string());
+                    return RunWithInteractorResult::InternalError(
+                        isolation.spawn_error("exe_command", result),
+                    );
                 }
-                Ok(result) => result,
+                Ok(result) => (result, seccomp),
             }
         };
 
         let interactor_start_time = Instant::now();
-        match interactor_cgroup.add_task(interactor_p.id().unwrap() as i32) {
-            Err(result) => {
-                let _ = interactor_p.kill().await;
-                return RunWithInteractorResult::InternalError(result.to_string());
-            }
-            Ok(_) => {}
+        let tee_tasks = tee_ends.map(
+            |(from_tested, to_interactor, from_interactor, to_tested, transcript)| {
+                (
+                    [
+                        tokio::spawn(tee(
+                            from_tested,
+                            to_interactor,
+                            TranscriptDirection::ToInteractor,
+                            interactor_start_time,
+                            transcript.clone(),
+                        )),
+                        tokio::spawn(tee(
+                            from_interactor,
+                            to_tested,
+                            TranscriptDirection::FromInteractor,
+                            interactor_start_time,
+                            transcript.clone(),
+                        )),
+                    ],
+                    transcript,
+                )
+            },
+        );
+        let interactor_seccomp_monitor = match interactor_seccomp {
+            None => None,
+            Some(seccomp) => match seccomp.monitor(interactor_p.id().unwrap() as i32) {
+                Err(result) => {
+                    let _ = interactor_p.kill().await;
+                    return RunWithInteractorResult::InternalError(result);
+                }
+                Ok(result) => Some(result),
+            },
         };
 
-        let mut p = {
+        let (mut p, seccomp) = {
             let stderr = match std::fs::File::create(self.stderr_path.as_str()) {
                 Err(result) => {
                     let _ = interactor_p.kill().await;
-                    return RunWithInteractorResult::InternalError(result.to_string());
+                    return RunWithInteractorResult::InternalError(JudgeError::io(
+                        "create stderr file",
+                        result,
+                    ));
+                }
+                Ok(result) => result,
+            };
+            let jail = match self.jail() {
+                Err(result) => {
+                    let _ = interactor_p.kill().await;
+                    return RunWithInteractorResult::InternalError(result);
+                }
+                Ok(result) => result,
+            };
+            let seccomp = match self.seccomp_filter() {
+                Err(result) => {
+                    let _ = interactor_p.kill().await;
+                    return RunWithInteractorResult::InternalError(result);
                 }
                 Ok(result) => result,
             };
-            let (command, args) = turn_command_into_command_and_args(self.exe_command.as_str());
-            match tokio::process::Command::new(command)
-                .stdin(unsafe { std::fs::File::from_raw_fd(pipe_from_interactor_read) })
-                .stdout(unsafe { std::fs::File::from_raw_fd(pipe_to_interactor_write) })
+            let (command, args) = match self.command_and_args(cgroup, &[]) {
+                Err(result) => {
+                    let _ = interactor_p.kill().await;
+                    return RunWithInteractorResult::InternalError(result);
+                }
+                Ok(result) => result,
+            };
+            let mut command = tokio::process::Command::new(command);
+            command
+                .stdin(tested_stdin)
+                .stdout(tested_stdout)
                 .stderr(stderr)
                 .args(args)
-                .uid(self.uid)
-                .current_dir(self.exe_dir.path())
-                .spawn()
-            {
+                .current_dir(self.exe_dir.path());
+            set_env(
+                &mut command,
+                self.compile_and_exe_setting.inherit_env,
+                &self.compile_and_exe_setting.env,
+            );
+            if let Err(result) = join_cgroup(&mut command, cgroup) {
+                let _ = interactor_p.kill().await;
+                return RunWithInteractorResult::InternalError(result);
+            }
+            let isolation = match set_uid(&mut command, self.uid, network_isolation, jail) {
                 Err(result) => {
                     let _ = interactor_p.kill().await;
-                    return RunWithInteractorResult::InternalError(result.to_string());
+                    return RunWithInteractorResult::InternalError(result);
                 }
                 Ok(result) => result,
+            };
+            limit_cpu_time(&mut command, cpu_time_limit);
+            limit_file_size(&mut command, output_limit);
+            pin_to_cpu(&mut command, self.cpu_affinity);
+            if let Some(seccomp) = &seccomp {
+                seccomp.apply(&mut command);
+            }
+            match command.spawn() {
+                Err(result) => {
+                    let _ = interactor_p.kill().await;
+                    return RunWithInteractorResult::InternalError(
+                        isolation.spawn_error("exe_command", result),
+                    );
+                }
+                Ok(result) => (result, seccomp),
             }
         };
         let start_time = Instant::now();
 
-        match cgroup.add_task(p.id().unwrap() as i32) {
+        let seccomp_monitor = match seccomp {
+            None => None,
+            Some(seccomp) => match seccomp.monitor(p.id().unwrap() as i32) {
+                Err(result) => {
+                    let _ = p.kill().await;
+                    let _ = interactor_p.kill().await;
+                    return RunWithInteractorResult::InternalError(result);
+                }
+                Ok(result) => Some(result),
+            },
+        };
+
+        // The interactor is watched while the tested program runs, since
+        // whichever of them exits first decides whose judgement counts. It is
+        // polled first, because a tested program killed by SIGPIPE right after
+        // the interactor gave up is often reaped in the same wake-up.
+        let mut interactor_exited_first = false;
+        let result = {
+            let tested_wait = tokio::time::timeout(Duration::from(time_limit), p.wait());
+            tokio::pin!(tested_wait);
+            loop {
+                tokio::select! {
+                    biased;
+                    result = interactor_p.wait(), if !interactor_exited_first => match result {
+                        Err(result) => break Err(result),
+                        Ok(_) => interactor_exited_first = true,
+                    },
+                    result = &mut tested_wait => break Ok(result),
+                }
+            }
+        };
+        let result = match result {
             Err(result) => {
                 let _ = p.kill().await;
                 let _ = interactor_p.kill().await;
-                return RunWithInteractorResult::InternalError(result.to_string());
+                return RunWithInteractorResult::InternalError(JudgeError::io(
+                    "wait for interactor",
+                    result,
+                ));
             }
-            Ok(_) => {}
+            Ok(result) => result,
         };
-
-        let result = tokio::time::timeout(Duration::from(time_limit), p.wait()).await;
         let runtime = TimeSpan::from(start_time.elapsed());
+        let killed = kill_cgroup(cgroup).await;
         let _ = p.kill().await;
-        let _ = p.wait().await;
+        let exit_status = p.wait().await.ok();
+        let restricted_syscall = match seccomp_monitor {
+            None => None,
+            Some(seccomp_monitor) => seccomp_monitor.stop().await,
+        };
+        if let Err(result) = killed {
+            let _ = interactor_p.kill().await;
+            return RunWithInteractorResult::InternalError(result);
+        }
+        let exit_code = exit_status.and_then(|status| status.code());
+        let signal = exit_status.and_then(|status| status.signal());
+        let core_dumped = exit_status.is_some_and(|status| status.core_dumped());
         let is_oom = match cgroup.update_cgroup_and_controller_and_check_oom() {
             Err(result) => {
                 let _ = interactor_p.kill().await;
-                return RunWithInteractorResult::InternalError(result.to_string());
+                return RunWithInteractorResult::InternalError(result);
+            }
+            Ok(result) => result,
+        };
+        let process_limit_exceeded = match cgroup.check_pids_max() {
+            Err(result) => {
+                let _ = interactor_p.kill().await;
+                return RunWithInteractorResult::InternalError(result);
             }
             Ok(result) => result,
         };
@@ -628,176 +1507,1531 @@ impl ExeResources {
         )
         .await;
         let interactor_runtime = TimeSpan::from(interactor_start_time.elapsed());
+        let interactor_killed = kill_cgroup(interactor_cgroup).await;
         let _ = interactor_p.kill().await;
-        let _ = interactor_p.wait().await;
+        let interactor_exit_status = interactor_p.wait().await.ok();
+        let interactor_restricted_syscall = match interactor_seccomp_monitor {
+            None => None,
+            Some(seccomp_monitor) => seccomp_monitor.stop().await,
+        };
+        if let Err(result) = interactor_killed {
+            return RunWithInteractorResult::InternalError(result);
+        }
+        // Both ends are gone by now, unless something they left behind still
+        // holds a pipe, so forwarding is only waited for a moment.
+        let transcript = match tee_tasks {
+            None => None,
+            Some((tee_tasks, transcript)) => {
+                for mut tee_task in tee_tasks {
+                    if tokio::time::timeout(TEE_DRAIN_TIMEOUT, &mut tee_task)
+                        .await
+                        .is_err()
+                    {
+                        tee_task.abort();
+                    }
+                }
+                let transcript = match transcript.lock() {
+                    Err(result) => result.into_inner().clone(),
+                    Ok(result) => result.clone(),
+                };
+                Some(transcript)
+            }
+        };
+        let interactor_exit_code = interactor_exit_status.and_then(|status| status.code());
+        let interactor_signal = interactor_exit_status.and_then(|status| status.signal());
+        let interactor_core_dumped =
+            interactor_exit_status.is_some_and(|status| status.core_dumped());
         let interactor_is_oom = match interactor_cgroup.update_cgroup_and_controller_and_check_oom()
         {
             Err(result) => {
-                return RunWithInteractorResult::InternalError(result.to_string());
+                return RunWithInteractorResult::InternalError(result);
+            }
+            Ok(result) => result,
+        };
+        let interactor_process_limit_exceeded = match interactor_cgroup.check_pids_max() {
+            Err(result) => {
+                return RunWithInteractorResult::InternalError(result);
             }
             Ok(result) => result,
         };
         let memory = match cgroup.get_max_usage_in_bytes() {
             Err(result) => {
-                return RunWithInteractorResult::InternalError(result.to_string());
+                return RunWithInteractorResult::InternalError(result);
             }
             Ok(result) => MemorySize::from_bytes(result as usize),
         };
         let interactor_memory = match interactor_cgroup.get_max_usage_in_bytes() {
             Err(result) => {
-                return RunWithInteractorResult::InternalError(result.to_string());
+                return RunWithInteractorResult::InternalError(result);
             }
             Ok(result) => MemorySize::from_bytes(result as usize),
         };
+        let cputime = match cgroup.get_cpu_usage_in_nanoseconds() {
+            Err(result) => {
+                return RunWithInteractorResult::InternalError(result);
+            }
+            Ok(result) => TimeSpan::from(Duration::from_nanos(
+                result.saturating_sub(cpu_usage_before),
+            )),
+        };
+        let interactor_cputime = match interactor_cgroup.get_cpu_usage_in_nanoseconds() {
+            Err(result) => {
+                return RunWithInteractorResult::InternalError(result);
+            }
+            Ok(result) => TimeSpan::from(Duration::from_nanos(
+                result.saturating_sub(interactor_cpu_usage_before),
+            )),
+        };
+        let (stderr, stderr_exceeded) =
+            match read_file_with_limit(self.stderr_path.as_str(), output_limit).await {
+                Err(result) => return RunWithInteractorResult::InternalError(result),
+                Ok(result) => result,
+            };
+        let (interactor_stdout, interactor_stdout_exceeded) = match read_file_with_limit(
+            interactor_exe_resources.interactorout_path.as_str(),
+            output_limit,
+        )
+        .await
+        {
+            Err(result) => return RunWithInteractorResult::InternalError(result),
+            Ok(result) => result,
+        };
+        let (interactor_stderr, interactor_stderr_exceeded) =
+            match read_file_with_limit(interactor_exe_resources.stderr_path.as_str(), output_limit)
+                .await
+            {
+                Err(result) => return RunWithInteractorResult::InternalError(result),
+                Ok(result) => result,
+            };
         let p_resource = ProcessResource {
             memory: memory,
             runtime: runtime,
+            cputime,
+            exit_code,
+            signal,
+            core_dumped,
+            process_limit_exceeded,
             stdout: vec![],
-            stderr: {
-                match check_file_limit(self.stderr_path.as_str(), output_limit).await {
-                    Err(result) => {
-                        return RunWithInteractorResult::InternalError(result);
-                    }
-                    Ok(result) => {
-                        if result != "" {
-                            return RunWithInteractorResult::OutputLimitExceeded(
-                                ProcessResource {
-                                    memory: memory,
-                                    runtime: runtime,
-                                    stdout: vec![],
-                                    stderr: vec![],
-                                },
-                                ProcessResource {
-                                    memory: interactor_memory,
-                                    runtime: interactor_runtime,
-                                    stdout: vec![],
-                                    stderr: vec![],
-                                },
-                            );
-                        }
-                    }
-                }
-                match self.read_stderr().await {
-                    Ok(result) => result,
-                    Err(result) => return RunWithInteractorResult::InternalError(result),
-                }
-            },
+            stderr,
+            transcript,
         };
         let interactor_resource = ProcessResource {
             memory: interactor_memory,
             runtime: interactor_runtime,
-            stdout: {
-                match check_file_limit(
-                    interactor_exe_resources.interactorout_path.as_str(),
-                    output_limit,
-                )
+            cputime: interactor_cputime,
+            exit_code: interactor_exit_code,
+            signal: interactor_signal,
+            core_dumped: interactor_core_dumped,
+            process_limit_exceeded: interactor_process_limit_exceeded,
+            stdout: interactor_stdout,
+            stderr: interactor_stderr,
+            transcript: None,
+        };
+        if stderr_exceeded {
+            return RunWithInteractorResult::OutputLimitExceeded(p_resource, interactor_resource);
+        }
+        if interactor_stdout_exceeded || interactor_stderr_exceeded {
+            return RunWithInteractorResult::InteractorOutputLimitExceeded(
+                p_resource,
+                interactor_resource,
+            );
+        }
+        // An interactor exiting on its own with a verdict code has judged the
+        // exchange, any other exit is a crash of the interactor.
+        let eval_verdict = match interactor_restricted_syscall.is_none()
+            && !interactor_is_oom
+            && interactor_result.is_ok()
+            && EvalVerdict::is_verdict_exit(&interactor_resource, interactor_testlib)
+        {
+            false => None,
+            true => match interactor_exe_resources
+                .eval_verdict(&interactor_resource, interactor_testlib, output_limit)
                 .await
-                {
-                    Err(result) => {
-                        return RunWithInteractorResult::InternalError(result);
-                    }
-                    Ok(result) => {
-                        if result != "" {
-                            return RunWithInteractorResult::InteractorOutputLimitExceeded(
-                                ProcessResource {
-                                    memory: memory,
-                                    runtime: runtime,
-                                    stdout: vec![],
-                                    stderr: vec![],
-                                },
-                                ProcessResource {
-                                    memory: interactor_memory,
-                                    runtime: interactor_runtime,
-                                    stdout: vec![],
-                                    stderr: vec![],
-                                },
-                            );
-                        }
-                    }
-                }
-                match interactor_exe_resources.read_interactorout().await {
-                    Ok(result) => result,
-                    Err(result) => return RunWithInteractorResult::InternalError(result),
-                }
-            },
-            stderr: {
-                match check_file_limit(interactor_exe_resources.stderr_path.as_str(), output_limit)
-                    .await
-                {
-                    Err(result) => {
-                        return RunWithInteractorResult::InternalError(result);
-                    }
-                    Ok(result) => {
-                        if result != "" {
-                            return RunWithInteractorResult::InteractorOutputLimitExceeded(
-                                ProcessResource {
-                                    memory: memory,
-                                    runtime: runtime,
-                                    stdout: vec![],
-                                    stderr: vec![],
-                                },
-                                ProcessResource {
-                                    memory: interactor_memory,
-                                    runtime: interactor_runtime,
-                                    stdout: vec![],
-                                    stderr: vec![],
-                                },
-                            );
-                        }
-                    }
-                }
-                match interactor_exe_resources.read_stderr().await {
-                    Ok(result) => result,
-                    Err(result) => return RunWithInteractorResult::InternalError(result),
-                }
+            {
+                Err(result) => return RunWithInteractorResult::InternalError(result),
+                Ok(result) => Some(result),
             },
         };
-        if is_oom {
-            RunWithInteractorResult::MemoryLimitExceeded(p_resource, interactor_resource)
-        } else if result.is_err() || runtime > time_limit {
-            RunWithInteractorResult::TimeLimitExceeded(p_resource, interactor_resource)
-        } else if result.unwrap().is_ok_and(|status| status.success()) == false {
-            RunWithInteractorResult::RuntimeError(p_resource, interactor_resource)
+        // Once the interactor is gone the tested program is cut off, and
+        // usually dies of a broken pipe or eof. So an interactor that exited
+        // first, unless it accepted, is what decides the result.
+        let interactor_decides = interactor_exited_first
+            && !eval_verdict
+                .as_ref()
+                .is_some_and(|eval_verdict| eval_verdict.is_accepted());
+        if !interactor_decides {
+            if let Some(syscall) = restricted_syscall {
+                return RunWithInteractorResult::RestrictedFunction(
+                    syscall,
+                    p_resource,
+                    interactor_resource,
+                );
+            } else if is_oom {
+                return RunWithInteractorResult::MemoryLimitExceeded(
+                    p_resource,
+                    interactor_resource,
+                );
+            } else if cputime > cpu_time_limit {
+                return RunWithInteractorResult::TimeLimitExceeded(
+                    TimeLimitKind::CpuTime,
+                    p_resource,
+                    interactor_resource,
+                );
+            } else if result.is_err() || runtime > time_limit {
+                return RunWithInteractorResult::TimeLimitExceeded(
+                    TimeLimitKind::WallTime,
+                    p_resource,
+                    interactor_resource,
+                );
+            } else if !matches!(result, Ok(Ok(status)) if status.success()) {
+                return RunWithInteractorResult::RuntimeError(p_resource, interactor_resource);
+            }
+        }
+        if let Some(syscall) = interactor_restricted_syscall {
+            RunWithInteractorResult::InteractorRestrictedFunction(
+                syscall,
+                p_resource,
+                interactor_resource,
+            )
         } else if interactor_is_oom {
             RunWithInteractorResult::InteractorMemoryLimitExceeded(p_resource, interactor_resource)
         } else if interactor_result.is_err() {
             RunWithInteractorResult::InteractorTimeLimitExceeded(p_resource, interactor_resource)
-        } else if interactor_result
-            .unwrap()
-            .is_ok_and(|status| status.success())
-        {
-            RunWithInteractorResult::Ok(p_resource, interactor_resource)
         } else {
-            RunWithInteractorResult::InteractorRuntimeError(p_resource, interactor_resource)
+            match eval_verdict {
+                None => {
+                    RunWithInteractorResult::InteractorRuntimeError(p_resource, interactor_resource)
+                }
+                Some(eval_verdict) => {
+                    RunWithInteractorResult::Ok(p_resource, interactor_resource, eval_verdict)
+                }
+            }
         }
     }
-}
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct ExeCode {
-    pub exe_files: HashMap<String, Vec<u8>>,
-    pub compile_and_exe_setting: CompileAndExeSetting,
-}
+    // Runs a manager against several instances of this program, as in
+    // communication tasks. The manager gets its input on stdin, the names of
+    // two fifos per instance as arguments, manager_in_i to read what instance
+    // i prints and manager_out_i to write what it reads, and judges on
+    // stdout like a checker. The instances run side by side, each in its own
+    // cgroup and under its own limits. Of the manager's limits only the
+    // output limit and network isolation apply, its time being the instances'
+    // plus manager_extra_time_limit.
+    pub async fn run_with_manager(
+        &mut self,
+        cgroup: &mut Cgroup,
+        manager_extra_time_limit: TimeSpan,
+        manager_input: &[u8],
+        instances: &mut [(ExeResources, Cgroup, RunLimits)],
+        limits: RunLimits,
+    ) -> RunWithManagerResult {
+        let RunLimits {
+            output_limit,
+            network_isolation,
+            ..
+        } = limits;
+        if let Err(result) = cgroup.reset_max_usage_in_bytes() {
+            return RunWithManagerResult::InternalError(result);
+        }
+        let manager_cpu_usage_before = match cgroup
+            .update_cgroup_and_controller()
+            .and_then(|_| cgroup.get_cpu_usage_in_nanoseconds())
+        {
+            Err(result) => {
+                return RunWithManagerResult::InternalError(result);
+            }
+            Ok(result) => result,
+        };
+        let mut cpu_usages_before = vec![];
+        for (_, instance_cgroup, _) in instances.iter_mut() {
+            if let Err(result) = instance_cgroup.reset_max_usage_in_bytes() {
+                return RunWithManagerResult::InternalError(result);
+            }
+            match instance_cgroup
+                .update_cgroup_and_controller()
+                .and_then(|_| instance_cgroup.get_cpu_usage_in_nanoseconds())
+            {
+                Err(result) => {
+                    return RunWithManagerResult::InternalError(result);
+                }
+                Ok(result) => cpu_usages_before.push(result),
+            };
+        }
 
-#[cfg(feature = "run")]
-impl ExeCode {
-    pub async fn initial_exe_resources(&self, uid: u32) -> InitExeResourceResult {
-        ExeResources::new(uid, &self.exe_files, &self.compile_and_exe_setting).await
-    }
-}
+        match tokio::fs::File::create(self.stdin_path.as_str()).await {
+            Err(result) => {
+                return RunWithManagerResult::InternalError(JudgeError::io(
+                    "create stdin file",
+                    result,
+                ));
+            }
+            Ok(mut file) => {
+                if let Err(result) = file.write_all(manager_input).await {
+                    return RunWithManagerResult::InternalError(JudgeError::io(
+                        "write stdin file",
+                        result,
+                    ));
+                }
+            }
+        };
 
-#[cfg(feature = "run")]
-async fn check_file_limit(path: &str, limit: MemorySize) -> Result<String, String> {
-    let metadata = match tokio::fs::metadata(path).await {
-        Err(result) => {
-            return Err(result.to_string());
+        // The judge opens every fifo before anyone runs, so that neither side
+        // blocks in open, and keeps an extra end of each until the manager
+        // opens its own, so that no instance sees eof or a broken pipe just
+        // because the manager has not got to it yet. Past that point the kept
+        // end would hide the manager closing its end from the instance.
+        let mut fifo_names = vec![];
+        let mut instance_ends = vec![];
+        let mut kept_ends = vec![];
+        let mut fifo_paths = vec![];
+        for i in 0..instances.len() {
+            let to_manager = format!("manager_in_{}", i);
+            let from_manager = format!("manager_out_{}", i);
+            for name in [&to_manager, &from_manager] {
+                let path = self.exe_dir.path().join(name);
+                match std::fs::remove_file(&path) {
+                    Err(result) if result.kind() != std::io::ErrorKind::NotFound => {
+                        return RunWithManagerResult::InternalError(JudgeError::io(
+                            "remove fifo",
+                            result,
+                        ));
+                    }
+                    _ => {}
+                }
+                if let Err(result) = nix::unistd::mkfifo(
+                    &path,
+                    nix::sys::stat::Mode::S_IRUSR | nix::sys::stat::Mode::S_IWUSR,
+                ) {
+                    return RunWithManagerResult::InternalError(JudgeError::io(
+                        "mkfifo()",
+                        result.into(),
+                    ));
+                }
+                if let Err(result) = std::os::unix::fs::chown(&path, Some(self.uid), None) {
+                    return RunWithManagerResult::InternalError(JudgeError::io(
+                        "chown fifo",
+                        result,
+                    ));
+                }
+            }
+            match open_fifo_ends(
+                &self.exe_dir.path().join(&to_manager),
+                &self.exe_dir.path().join(&from_manager),
+            ) {
+                Err(result) => {
+                    return RunWithManagerResult::InternalError(JudgeError::io(
+                        "open fifo",
+                        result,
+                    ));
+                }
+                Ok((stdin, stdout, kept_to_manager, kept_from_manager)) => {
+                    instance_ends.push((stdin, stdout));
+                    kept_ends.push(Some(kept_to_manager));
+                    kept_ends.push(Some(kept_from_manager));
+                }
+            }
+            fifo_paths.push(self.exe_dir.path().join(&to_manager));
+            fifo_paths.push(self.exe_dir.path().join(&from_manager));
+            fifo_names.push(to_manager);
+            fifo_names.push(from_manager);
+        }
+        // The watch starts after the judge's own opens, so the only opens it
+        // sees are the manager's.
+        let fifo_watch = match FifoWatch::new(&fifo_paths) {
+            Err(result) => {
+                return RunWithManagerResult::InternalError(JudgeError::io("watch fifos", result))
+            }
+            Ok(result) => result,
+        };
+
+        let (mut manager_p, manager_seccomp) = {
+            let stdin = match std::fs::File::open(self.stdin_path.as_str()) {
+                Err(result) => {
+                    return RunWithManagerResult::InternalError(JudgeError::io(
+                        "open stdin file",
+                        result,
+                    ))
+                }
+                Ok(result) => result,
+            };
+            let stdout = match std::fs::File::create(self.stdout_path.as_str()) {
+                Err(result) => {
+                    return RunWithManagerResult::InternalError(JudgeError::io(
+                        "create stdout file",
+                        result,
+                    ))
+                }
+                Ok(result) => result,
+            };
+            let stderr = match std::fs::File::create(self.stderr_path.as_str()) {
+                Err(result) => {
+                    return RunWithManagerResult::InternalError(JudgeError::io(
+                        "create stderr file",
+                        result,
+                    ))
+                }
+                Ok(result) => result,
+            };
+            let jail = match self.jail() {
+                Err(result) => return RunWithManagerResult::InternalError(result),
+                Ok(result) => result,
+            };
+            let seccomp = match self.seccomp_filter() {
+                Err(result) => return RunWithManagerResult::InternalError(result),
+                Ok(result) => result,
+            };
+            let (command, mut args) = match self.command_and_args(cgroup, &[]) {
+                Err(result) => return RunWithManagerResult::InternalError(result),
+                Ok(result) => result,
+            };
+            args.extend(fifo_names);
+            let mut command = tokio::process::Command::new(command);
+            command
+                .stdin(stdin)
+                .stdout(stdout)
+                .stderr(stderr)
+                .args(args)
+                .current_dir(self.exe_dir.path());
+            set_env(
+                &mut command,
+                self.compile_and_exe_setting.inherit_env,
+                &self.compile_and_exe_setting.env,
+            );
+            if let Err(result) = join_cgroup(&mut command, cgroup) {
+                return RunWithManagerResult::InternalError(result);
+            }
+            let isolation = match set_uid(&mut command, self.uid, network_isolation, jail) {
+                Err(result) => return RunWithManagerResult::InternalError(result),
+                Ok(result) => result,
+            };
+            limit_file_size(&mut command, output_limit);
+            pin_to_cpu(&mut command, self.cpu_affinity);
+            if let Some(seccomp) = &seccomp {
+                seccomp.apply(&mut command);
+            }
+            match command.spawn() {
+                Err(result) => {
+                    return RunWithManagerResult::InternalError(
+                        isolation.spawn_error("exe_command", result),
+                    );
+                }
+                Ok(result) => (result, seccomp),
+            }
+        };
+        let manager_start_time = Instant::now();
+        let manager_seccomp_monitor = match manager_seccomp {
+            None => None,
+            Some(seccomp) => match seccomp.monitor(manager_p.id().unwrap() as i32) {
+                Err(result) => {
+                    let _ = manager_p.kill().await;
+                    return RunWithManagerResult::InternalError(result);
+                }
+                Ok(result) => Some(result),
+            },
+        };
+
+        let mut ps = vec![];
+        let mut start_times = vec![];
+        let mut seccomp_monitors = vec![];
+        for ((exe_resources, instance_cgroup, instance_limits), (stdin, stdout)) in
+            instances.iter_mut().zip(instance_ends)
+        {
+            let (mut p, seccomp) = {
+                let stderr = match std::fs::File::create(exe_resources.stderr_path.as_str()) {
+                    Err(result) => {
+                        kill_all(&mut manager_p, &mut ps).await;
+                        return RunWithManagerResult::InternalError(JudgeError::io(
+                            "create stderr file",
+                            result,
+                        ));
+                    }
+                    Ok(result) => result,
+                };
+                let jail = match exe_resources.jail() {
+                    Err(result) => {
+                        kill_all(&mut manager_p, &mut ps).await;
+                        return RunWithManagerResult::InternalError(result);
+                    }
+                    Ok(result) => result,
+                };
+                let seccomp = match exe_resources.seccomp_filter() {
+                    Err(result) => {
+                        kill_all(&mut manager_p, &mut ps).await;
+                        return RunWithManagerResult::InternalError(result);
+                    }
+                    Ok(result) => result,
+                };
+                let (command, args) = match exe_resources.command_and_args(instance_cgroup, &[]) {
+                    Err(result) => {
+                        kill_all(&mut manager_p, &mut ps).await;
+                        return RunWithManagerResult::InternalError(result);
+                    }
+                    Ok(result) => result,
+                };
+                let mut command = tokio::process::Command::new(command);
+                command
+                    .stdin(stdin)
+                    .stdout(stdout)
+                    .stderr(stderr)
+                    .args(args)
+                    .current_dir(exe_resources.exe_dir.path());
+                set_env(
+                    &mut command,
+                    exe_resources.compile_and_exe_setting.inherit_env,
+                    &exe_resources.compile_and_exe_setting.env,
+                );
+                if let Err(result) = join_cgroup(&mut command, instance_cgroup) {
+                    kill_all(&mut manager_p, &mut ps).await;
+                    return RunWithManagerResult::InternalError(result);
+                }
+                let isolation = match set_uid(
+                    &mut command,
+                    exe_resources.uid,
+                    instance_limits.network_isolation,
+                    jail,
+                ) {
+                    Err(result) => {
+                        kill_all(&mut manager_p, &mut ps).await;
+                        return RunWithManagerResult::InternalError(result);
+                    }
+                    Ok(result) => result,
+                };
+                limit_cpu_time(&mut command, instance_limits.cpu_time_limit);
+                limit_file_size(&mut command, instance_limits.output_limit);
+                pin_to_cpu(&mut command, exe_resources.cpu_affinity);
+                if let Some(seccomp) = &seccomp {
+                    seccomp.apply(&mut command);
+                }
+                match command.spawn() {
+                    Err(result) => {
+                        kill_all(&mut manager_p, &mut ps).await;
+                        return RunWithManagerResult::InternalError(
+                            isolation.spawn_error("exe_command", result),
+                        );
+                    }
+                    Ok(result) => (result, seccomp),
+                }
+            };
+            start_times.push(Instant::now());
+            match seccomp {
+                None => seccomp_monitors.push(None),
+                Some(seccomp) => match seccomp.monitor(p.id().unwrap() as i32) {
+                    Err(result) => {
+                        let _ = p.kill().await;
+                        kill_all(&mut manager_p, &mut ps).await;
+                        return RunWithManagerResult::InternalError(result);
+                    }
+                    Ok(result) => seccomp_monitors.push(Some(result)),
+                },
+            };
+            ps.push(p);
+        }
+
+        // As with an interactor, a manager that exits before the last
+        // instance has judged already, and the instances still waiting on it
+        // are let go at once. It is polled first for the same reason.
+        let mut manager_exited_first = false;
+        let results = {
+            let waits = join_all(
+                ps.iter_mut()
+                    .zip(start_times.iter())
+                    .zip(instances.iter())
+                    .map(|((p, start_time), (_, _, instance_limits))| async move {
+                        let deadline = *start_time + Duration::from(instance_limits.time_limit);
+                        let result = tokio::time::timeout_at(deadline.into(), p.wait()).await;
+                        (result, TimeSpan::from(start_time.elapsed()))
+                    })
+                    .collect(),
+            );
+            tokio::pin!(waits);
+            loop {
+                tokio::select! {
+                    biased;
+                    result = manager_p.wait(), if !manager_exited_first => match result {
+                        Err(result) => break Err(JudgeError::io("wait for manager", result)),
+                        Ok(_) => {
+                            manager_exited_first = true;
+                            kept_ends.clear();
+                        }
+                    },
+                    result = fifo_watch.opened(), if kept_ends.iter().any(Option::is_some) => {
+                        match result {
+                            Err(result) => break Err(JudgeError::io("watch fifos", result)),
+                            Ok(opened) => {
+                                for i in opened {
+                                    if let Some(kept_end) = kept_ends.get_mut(i) {
+                                        *kept_end = None;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    result = &mut waits => break Ok(result),
+                }
+            }
+        };
+        drop(kept_ends);
+        let results = match results {
+            Err(result) => {
+                kill_all(&mut manager_p, &mut ps).await;
+                return RunWithManagerResult::InternalError(result);
+            }
+            Ok(result) => result,
+        };
+
+        let mut instance_resources = vec![];
+        let mut instance_results = vec![];
+        for (
+            (
+                ((p, (result, runtime)), seccomp_monitor),
+                (exe_resources, instance_cgroup, instance_limits),
+            ),
+            cpu_usage_before,
+        ) in ps
+            .iter_mut()
+            .zip(results)
+            .zip(seccomp_monitors)
+            .zip(instances.iter_mut())
+            .zip(cpu_usages_before)
+        {
+            let killed = kill_cgroup(instance_cgroup).await;
+            let _ = p.kill().await;
+            let exit_status = p.wait().await.ok();
+            let restricted_syscall = match seccomp_monitor {
+                None => None,
+                Some(seccomp_monitor) => seccomp_monitor.stop().await,
+            };
+            if let Err(result) = killed {
+                let _ = manager_p.kill().await;
+                return RunWithManagerResult::InternalError(result);
+            }
+            let is_oom = match instance_cgroup.update_cgroup_and_controller_and_check_oom() {
+                Err(result) => {
+                    let _ = manager_p.kill().await;
+                    return RunWithManagerResult::InternalError(result);
+                }
+                Ok(result) => result,
+            };
+            let process_limit_exceeded = match instance_cgroup.check_pids_max() {
+                Err(result) => {
+                    let _ = manager_p.kill().await;
+                    return RunWithManagerResult::InternalError(result);
+                }
+                Ok(result) => result,
+            };
+            let memory = match instance_cgroup.get_max_usage_in_bytes() {
+                Err(result) => {
+                    let _ = manager_p.kill().await;
+                    return RunWithManagerResult::InternalError(result);
+                }
+                Ok(result) => MemorySize::from_bytes(result as usize),
+            };
+            let cputime = match instance_cgroup.get_cpu_usage_in_nanoseconds() {
+                Err(result) => {
+                    let _ = manager_p.kill().await;
+                    return RunWithManagerResult::InternalError(result);
+                }
+                Ok(result) => TimeSpan::from(Duration::from_nanos(
+                    result.saturating_sub(cpu_usage_before),
+                )),
+            };
+            let (stderr, stderr_exceeded) = match read_file_with_limit(
+                exe_resources.stderr_path.as_str(),
+                instance_limits.output_limit,
+            )
+            .await
+            {
+                Err(result) => {
+                    let _ = manager_p.kill().await;
+                    return RunWithManagerResult::InternalError(result);
+                }
+                Ok(result) => result,
+            };
+            instance_resources.push(ProcessResource {
+                memory,
+                runtime,
+                cputime,
+                exit_code: exit_status.and_then(|status| status.code()),
+                signal: exit_status.and_then(|status| status.signal()),
+                core_dumped: exit_status.is_some_and(|status| status.core_dumped()),
+                process_limit_exceeded,
+                stdout: vec![],
+                stderr,
+                transcript: None,
+            });
+            instance_results.push((
+                result,
+                restricted_syscall,
+                is_oom,
+                stderr_exceeded,
+                *instance_limits,
+            ));
+        }
+
+        let instance_resources = InstancesResource::new(instance_resources);
+
+        let manager_result =
+            tokio::time::timeout(Duration::from(manager_extra_time_limit), manager_p.wait()).await;
+        let manager_runtime = TimeSpan::from(manager_start_time.elapsed());
+        let manager_killed = kill_cgroup(cgroup).await;
+        let _ = manager_p.kill().await;
+        let manager_exit_status = manager_p.wait().await.ok();
+        let manager_restricted_syscall = match manager_seccomp_monitor {
+            None => None,
+            Some(seccomp_monitor) => seccomp_monitor.stop().await,
+        };
+        if let Err(result) = manager_killed {
+            return RunWithManagerResult::InternalError(result);
+        }
+        let manager_is_oom = match cgroup.update_cgroup_and_controller_and_check_oom() {
+            Err(result) => {
+                return RunWithManagerResult::InternalError(result);
+            }
+            Ok(result) => result,
+        };
+        let manager_process_limit_exceeded = match cgroup.check_pids_max() {
+            Err(result) => {
+                return RunWithManagerResult::InternalError(result);
+            }
+            Ok(result) => result,
+        };
+        let manager_memory = match cgroup.get_max_usage_in_bytes() {
+            Err(result) => {
+                return RunWithManagerResult::InternalError(result);
+            }
+            Ok(result) => MemorySize::from_bytes(result as usize),
+        };
+        let manager_cputime = match cgroup.get_cpu_usage_in_nanoseconds() {
+            Err(result) => {
+                return RunWithManagerResult::InternalError(result);
+            }
+            Ok(result) => TimeSpan::from(Duration::from_nanos(
+                result.saturating_sub(manager_cpu_usage_before),
+            )),
+        };
+        let (manager_stdout, manager_stdout_exceeded) =
+            match read_file_with_limit(self.stdout_path.as_str(), output_limit).await {
+                Err(result) => return RunWithManagerResult::InternalError(result),
+                Ok(result) => result,
+            };
+        let (manager_stderr, manager_stderr_exceeded) =
+            match read_file_with_limit(self.stderr_path.as_str(), output_limit).await {
+                Err(result) => return RunWithManagerResult::InternalError(result),
+                Ok(result) => result,
+            };
+        let manager_resource = ProcessResource {
+            memory: manager_memory,
+            runtime: manager_runtime,
+            cputime: manager_cputime,
+            exit_code: manager_exit_status.and_then(|status| status.code()),
+            signal: manager_exit_status.and_then(|status| status.signal()),
+            core_dumped: manager_exit_status.is_some_and(|status| status.core_dumped()),
+            process_limit_exceeded: manager_process_limit_exceeded,
+            stdout: manager_stdout,
+            stderr: manager_stderr,
+            transcript: None,
+        };
+        if let Some(i) = instance_results
+            .iter()
+            .position(|(_, _, _, stderr_exceeded, _)| *stderr_exceeded)
+        {
+            return RunWithManagerResult::OutputLimitExceeded(
+                i,
+                instance_resources,
+                manager_resource,
+            );
+        }
+        if manager_stdout_exceeded || manager_stderr_exceeded {
+            return RunWithManagerResult::ManagerOutputLimitExceeded(
+                instance_resources,
+                manager_resource,
+            );
+        }
+        let eval_verdict = match manager_restricted_syscall.is_none()
+            && !manager_is_oom
+            && manager_result.is_ok()
+            && EvalVerdict::is_verdict_exit(&manager_resource, false)
+        {
+            false => None,
+            true => match self
+                .eval_verdict(&manager_resource, false, output_limit)
+                .await
+            {
+                Err(result) => return RunWithManagerResult::InternalError(result),
+                Ok(result) => Some(result),
+            },
+        };
+        let manager_decides = manager_exited_first
+            && !eval_verdict
+                .as_ref()
+                .is_some_and(|eval_verdict| eval_verdict.is_accepted());
+        if !manager_decides {
+            for (i, (result, restricted_syscall, is_oom, _, instance_limits)) in
+                instance_results.into_iter().enumerate()
+            {
+                let resource = &instance_resources.each[i];
+                if let Some(syscall) = restricted_syscall {
+                    return RunWithManagerResult::RestrictedFunction(
+                        syscall,
+                        i,
+                        instance_resources,
+                        manager_resource,
+                    );
+                } else if is_oom {
+                    return RunWithManagerResult::MemoryLimitExceeded(
+                        i,
+                        instance_resources,
+                        manager_resource,
+                    );
+                } else if resource.cputime > instance_limits.cpu_time_limit {
+                    return RunWithManagerResult::TimeLimitExceeded(
+                        TimeLimitKind::CpuTime,
+                        i,
+                        instance_resources,
+                        manager_resource,
+                    );
+                } else if result.is_err() || resource.runtime > instance_limits.time_limit {
+                    return RunWithManagerResult::TimeLimitExceeded(
+                        TimeLimitKind::WallTime,
+                        i,
+                        instance_resources,
+                        manager_resource,
+                    );
+                } else if !matches!(result, Ok(Ok(status)) if status.success()) {
+                    return RunWithManagerResult::RuntimeError(
+                        i,
+                        instance_resources,
+                        manager_resource,
+                    );
+                }
+            }
+        }
+        if let Some(syscall) = manager_restricted_syscall {
+            RunWithManagerResult::ManagerRestrictedFunction(
+                syscall,
+                instance_resources,
+                manager_resource,
+            )
+        } else if manager_is_oom {
+            RunWithManagerResult::ManagerMemoryLimitExceeded(instance_resources, manager_resource)
+        } else if manager_result.is_err() {
+            RunWithManagerResult::ManagerTimeLimitExceeded(instance_resources, manager_resource)
+        } else {
+            match eval_verdict {
+                None => {
+                    RunWithManagerResult::ManagerRuntimeError(instance_resources, manager_resource)
+                }
+                Some(eval_verdict) => {
+                    RunWithManagerResult::Ok(instance_resources, manager_resource, eval_verdict)
+                }
+            }
+        }
+    }
+}
+
+#[serde_with::serde_as]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ExeCode {
+    #[cfg_attr(
+        feature = "base64",
+        serde_as(as = "HashMap<_, serde_with::base64::Base64>")
+    )]
+    pub exe_files: HashMap<String, Vec<u8>>,
+    pub compile_and_exe_setting: CompileAndExeSetting,
+}
+
+#[cfg(feature = "run")]
+impl ExeCode {
+    pub async fn initial_exe_resources(
+        &self,
+        uid: u32,
+        jail_mounts: Option<&Vec<String>>,
+    ) -> InitExeResourceResult {
+        ExeResources::new(
+            uid,
+            &self.exe_files,
+            &self.compile_and_exe_setting,
+            jail_mounts,
+        )
+        .await
+    }
+}
+
+#[cfg(feature = "run")]
+const TEE_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[cfg(feature = "run")]
+fn tee_ends(
+    from_tested: OwnedFd,
+    to_interactor: OwnedFd,
+    from_interactor: OwnedFd,
+    to_tested: OwnedFd,
+) -> std::io::Result<(
+    tokio::net::unix::pipe::Receiver,
+    tokio::net::unix::pipe::Sender,
+    tokio::net::unix::pipe::Receiver,
+    tokio::net::unix::pipe::Sender,
+)> {
+    Ok((
+        tokio::net::unix::pipe::Receiver::from_file(std::fs::File::from(from_tested))?,
+        tokio::net::unix::pipe::Sender::from_file(std::fs::File::from(to_interactor))?,
+        tokio::net::unix::pipe::Receiver::from_file(std::fs::File::from(from_interactor))?,
+        tokio::net::unix::pipe::Sender::from_file(std::fs::File::from(to_tested))?,
+    ))
+}
+
+// The ends are owned as soon as they exist, so that no early return can leak
+// them.
+#[cfg(feature = "run")]
+fn pipe(flags: nix::fcntl::OFlag) -> Result<(OwnedFd, OwnedFd), JudgeError> {
+    match nix::unistd::pipe2(flags) {
+        Err(result) => Err(JudgeError::io("pipe()", result.into())),
+        Ok((read, write)) => unsafe {
+            Ok((OwnedFd::from_raw_fd(read), OwnedFd::from_raw_fd(write)))
+        },
+    }
+}
+
+// Forwards one direction of an interaction, recording it on the way. A closed
+// end on either side ends forwarding, which the other side then sees as eof or
+// a broken pipe just as it would without the tee.
+#[cfg(feature = "run")]
+async fn tee(
+    mut from: tokio::net::unix::pipe::Receiver,
+    mut to: tokio::net::unix::pipe::Sender,
+    direction: TranscriptDirection,
+    start_time: Instant,
+    transcript: Arc<Mutex<Transcript>>,
+) {
+    let mut buf = vec![0u8; 65536];
+    let mut offset = 0;
+    loop {
+        let len = match from.read(&mut buf).await {
+            Err(_) | Ok(0) => break,
+            Ok(result) => result,
+        };
+        match transcript.lock() {
+            Err(_) => {}
+            Ok(mut transcript) => transcript.record(
+                direction,
+                TimeSpan::from(start_time.elapsed()),
+                offset,
+                &buf[..len],
+            ),
+        }
+        offset += len;
+        if to.write_all(&buf[..len]).await.is_err() {
+            break;
+        }
+    }
+}
+
+// Opens one instance's pair of fifos without blocking. Returns the instance's
+// stdin and stdout, then the ends the judge keeps: a reader of the fifo to the
+// manager and a writer of the one from it.
+#[cfg(feature = "run")]
+fn open_fifo_ends(
+    to_manager: &std::path::Path,
+    from_manager: &std::path::Path,
+) -> std::io::Result<(std::fs::File, std::fs::File, std::fs::File, std::fs::File)> {
+    let stdin = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(nix::fcntl::OFlag::O_NONBLOCK.bits())
+        .open(from_manager)?;
+    nix::fcntl::fcntl(
+        stdin.as_raw_fd(),
+        nix::fcntl::FcntlArg::F_SETFL(nix::fcntl::OFlag::empty()),
+    )?;
+    let kept_from_manager = std::fs::OpenOptions::new().write(true).open(from_manager)?;
+    let kept_to_manager = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(nix::fcntl::OFlag::O_NONBLOCK.bits())
+        .open(to_manager)?;
+    let stdout = std::fs::OpenOptions::new().write(true).open(to_manager)?;
+    Ok((stdin, stdout, kept_to_manager, kept_from_manager))
+}
+
+// Reports the opens of a set of fifos, by their index in that set.
+#[cfg(feature = "run")]
+struct FifoWatch {
+    fd: tokio::io::unix::AsyncFd<OwnedFd>,
+    watches: HashMap<i32, usize>,
+}
+
+#[cfg(feature = "run")]
+impl FifoWatch {
+    fn new(paths: &[std::path::PathBuf]) -> std::io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mut watches = HashMap::new();
+        for (i, path) in paths.iter().enumerate() {
+            let path = match std::ffi::CString::new(path.as_os_str().as_encoded_bytes()) {
+                Err(result) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        result,
+                    ))
+                }
+                Ok(result) => result,
+            };
+            let watch =
+                unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), libc::IN_OPEN) };
+            if watch < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            watches.insert(watch, i);
+        }
+        Ok(FifoWatch {
+            fd: tokio::io::unix::AsyncFd::new(fd)?,
+            watches,
+        })
+    }
+
+    async fn opened(&self) -> std::io::Result<Vec<usize>> {
+        let mut buf = [0u8; 4096];
+        loop {
+            let mut guard = self.fd.readable().await?;
+            let len = match guard.try_io(|fd| {
+                let len = unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+                if len < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(len as usize)
+            }) {
+                Err(_) => continue,
+                Ok(result) => result?,
+            };
+            let mut opened = vec![];
+            let mut offset = 0;
+            while offset + std::mem::size_of::<libc::inotify_event>() <= len {
+                let event = unsafe {
+                    std::ptr::read_unaligned(buf[offset..].as_ptr().cast::<libc::inotify_event>())
+                };
+                if event.mask & libc::IN_OPEN != 0 {
+                    if let Some(i) = self.watches.get(&event.wd) {
+                        opened.push(*i);
+                    }
+                }
+                offset += std::mem::size_of::<libc::inotify_event>() + event.len as usize;
+            }
+            return Ok(opened);
+        }
+    }
+}
+
+#[cfg(feature = "run")]
+async fn kill_all(manager_p: &mut tokio::process::Child, ps: &mut [tokio::process::Child]) {
+    let _ = manager_p.kill().await;
+    for p in ps.iter_mut() {
+        let _ = p.kill().await;
+    }
+}
+
+// Cgroups are not Send, so processes and workers are driven side by side on
+// the current task instead of being spawned. They mostly wait on child
+// processes anyway.
+#[cfg(feature = "run")]
+pub(crate) async fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+    let mut futures: Vec<_> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+    std::future::poll_fn(|cx| {
+        let mut is_pending = false;
+        for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if output.is_none() {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(result) => *output = Some(result),
+                    Poll::Pending => is_pending = true,
+                }
+            }
+        }
+        match is_pending {
+            true => Poll::Pending,
+            false => Poll::Ready(()),
+        }
+    })
+    .await;
+    outputs.into_iter().map(|output| output.unwrap()).collect()
+}
+
+// testlib programs open their files by name, so they are written into the exe
+// dir for each run, and the report has to be writable by the program.
+#[cfg(feature = "run")]
+async fn write_testlib_file(path: &str, content: &[u8], uid: u32) -> Result<(), JudgeError> {
+    if let Err(result) = tokio::fs::write(path, content).await {
+        return Err(JudgeError::io("write testlib file", result));
+    }
+    if let Err(result) =
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).await
+    {
+        return Err(JudgeError::io("set testlib file permissions", result));
+    }
+    if let Err(result) = std::os::unix::fs::chown(path, Some(uid), Some(uid)) {
+        return Err(JudgeError::io("chown testlib file", result));
+    }
+    Ok(())
+}
+
+// The arguments testlib expects, relative to the exe dir.
+#[cfg(feature = "run")]
+const TESTLIB_CHECKER_ARGS: [&str; 5] = ["input", "output", "answer", "report.xml", "-appes"];
+#[cfg(feature = "run")]
+const TESTLIB_INTERACTOR_ARGS: [&str; 5] = [
+    "interactorin",
+    "interactorout",
+    "answer",
+    "report.xml",
+    "-appes",
+];
+
+#[cfg(feature = "run")]
+struct Jail {
+    root: std::ffi::CString,
+    mounts: Vec<(std::ffi::CString, std::ffi::CString, bool)>,
+    work_dir: std::ffi::CString,
+}
+
+// Killed processes leave the cgroup within a few milliseconds, unless one
+// is stuck in the kernel, which is given about a second.
+#[cfg(any(feature = "compile", feature = "run"))]
+const CGROUP_KILL_RETRIES: usize = 1000;
+
+// The devices programs commonly expect, bound into every jail.
+#[cfg(feature = "run")]
+const JAIL_DEVICES: [&str; 3] = ["/dev/null", "/dev/zero", "/dev/urandom"];
+
+#[cfg(any(feature = "compile", feature = "run"))]
+fn set_env(
+    command: &mut tokio::process::Command,
+    inherit_env: bool,
+    env: &HashMap<String, String>,
+) {
+    // Without a PATH the program itself could not be looked up, so a fixed
+    // one is provided unless the setting declares its own.
+    if !inherit_env {
+        command.env_clear();
+        command.env("PATH", crate::settings::DEFAULT_PATH);
+    }
+    command.envs(env);
+}
+
+#[cfg(feature = "run")]
+async fn prepare_jail(exe_dir_path: &str, jail_mounts: &[String]) -> Result<TempDir, JudgeError> {
+    let id = format!("emjudge-judgecore-jail-{}", uuid::Uuid::new_v4().simple());
+    let jail_dir = match TempDir::with_prefix(id.as_str()) {
+        Err(result) => {
+            return Err(JudgeError::io("create jail dir", result));
+        }
+        Ok(result) => result,
+    };
+    if let Err(result) =
+        tokio::fs::set_permissions(jail_dir.path(), std::fs::Permissions::from_mode(0o755)).await
+    {
+        return Err(JudgeError::io("set jail dir permissions", result));
+    }
+    for jail_mount in jail_mounts
+        .iter()
+        .map(|x| x.as_str())
+        .chain([exe_dir_path])
+        .chain(JAIL_DEVICES)
+    {
+        if !jail_mount.starts_with('/') || jail_mount.split('/').any(|x| x == "..") {
+            return Err(JudgeError::Setting(format!(
+                "{} is not a valid jail mount",
+                jail_mount
+            )));
+        }
+        let target = format!("{}{}", jail_dir.path().to_string_lossy(), jail_mount);
+        let is_dir = match tokio::fs::metadata(jail_mount).await {
+            Err(result) => {
+                return Err(JudgeError::io(jail_mount, result));
+            }
+            Ok(result) => result.is_dir(),
+        };
+        if is_dir {
+            if let Err(result) = tokio::fs::create_dir_all(target.as_str()).await {
+                return Err(JudgeError::io("create jail mount point", result));
+            }
+        } else {
+            if let Some(parent) = std::path::Path::new(target.as_str()).parent() {
+                if let Err(result) = tokio::fs::create_dir_all(parent).await {
+                    return Err(JudgeError::io("create jail mount point", result));
+                }
+            }
+            if let Err(result) = tokio::fs::File::create(target.as_str()).await {
+                return Err(JudgeError::io("create jail mount point", result));
+            }
+        }
+    }
+    Ok(jail_dir)
+}
+
+// pre_exec errors reach the parent as a bare errno, so the namespace and jail
+// setup marks its own failures in a pipe, to tell them apart from a failing
+// cgroup join, uid switch or exec.
+#[cfg(feature = "run")]
+struct IsolationReport(Option<OwnedFd>);
+
+#[cfg(feature = "run")]
+impl IsolationReport {
+    fn spawn_error(&self, program: &str, source: std::io::Error) -> JudgeError {
+        if let Some(read) = &self.0 {
+            let mut byte = 0u8;
+            if unsafe { libc::read(read.as_raw_fd(), (&mut byte as *mut u8).cast(), 1) } == 1 {
+                return JudgeError::isolation(source);
+            }
+        }
+        JudgeError::spawn(program, source)
+    }
+}
+
+#[cfg(feature = "run")]
+fn set_uid(
+    command: &mut tokio::process::Command,
+    uid: u32,
+    network_isolation: bool,
+    jail: Option<Jail>,
+) -> Result<IsolationReport, JudgeError> {
+    // Command::uid() drops privileges before the pre_exec hooks run, but creating
+    // namespaces and joining the cgroup needs root, so the uid is switched by
+    // hand afterwards.
+    let mut report = IsolationReport(None);
+    if network_isolation || jail.is_some() {
+        let (read, write) = pipe(nix::fcntl::OFlag::O_CLOEXEC | nix::fcntl::OFlag::O_NONBLOCK)?;
+        report.0 = Some(read);
+        unsafe {
+            command.pre_exec(move || {
+                let result = (|| -> std::io::Result<()> {
+                    if network_isolation {
+                        nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNET)?;
+                        bring_up_loopback()?;
+                    }
+                    if let Some(jail) = &jail {
+                        enter_jail(jail)?;
+                    }
+                    Ok(())
+                })();
+                if result.is_err() {
+                    libc::write(write.as_raw_fd(), b"1".as_ptr().cast(), 1);
+                }
+                result
+            });
+        }
+    }
+    drop_privileges(command, uid);
+    Ok(report)
+}
+
+#[cfg(any(feature = "compile", feature = "run"))]
+fn drop_privileges(command: &mut tokio::process::Command, uid: u32) {
+    unsafe {
+        command.pre_exec(move || {
+            nix::unistd::setgroups(&[])?;
+            nix::unistd::setgid(nix::unistd::Gid::from_raw(uid))?;
+            nix::unistd::setuid(nix::unistd::Uid::from_raw(uid))?;
+            Ok(())
+        });
+    }
+}
+
+#[cfg(any(feature = "compile", feature = "run"))]
+fn join_cgroup(command: &mut tokio::process::Command, cgroup: &Cgroup) -> Result<(), JudgeError> {
+    // The child moves itself into the cgroup before exec, so that nothing it
+    // forks can get around the memory and process limits.
+    let procs = cgroup.open_procs()?;
+    unsafe {
+        command.pre_exec(move || {
+            let mut buf = [0u8; 16];
+            let mut start = buf.len();
+            let mut pid = libc::getpid() as u32;
+            loop {
+                start -= 1;
+                buf[start] = b'0' + (pid % 10) as u8;
+                pid /= 10;
+                if pid == 0 {
+                    break;
+                }
+            }
+            for file in &procs {
+                let pid = &buf[start..];
+                if libc::write(file.as_raw_fd(), pid.as_ptr().cast(), pid.len()) < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(any(feature = "compile", feature = "run"))]
+async fn kill_cgroup(cgroup: &mut Cgroup) -> Result<(), JudgeError> {
+    // Killing the child alone would leave whatever it forked running, so
+    // everything in the cgroup is killed and waited for before usage is read.
+    for _ in 0..CGROUP_KILL_RETRIES {
+        cgroup.kill_tasks()?;
+        if !cgroup.has_tasks()? {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    Err(JudgeError::Cgroup(
+        "processes are left in the cgroup after being killed".to_string(),
+    ))
+}
+
+#[cfg(feature = "run")]
+fn enter_jail(jail: &Jail) -> std::io::Result<()> {
+    unsafe {
+        if libc::unshare(libc::CLONE_NEWNS) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if libc::mount(
+            std::ptr::null(),
+            c"/".as_ptr(),
+            std::ptr::null(),
+            libc::MS_REC | libc::MS_PRIVATE,
+            std::ptr::null(),
+        ) != 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+        // pivot_root needs the new root to be a mount point of its own.
+        if libc::mount(
+            jail.root.as_ptr(),
+            jail.root.as_ptr(),
+            std::ptr::null(),
+            libc::MS_BIND | libc::MS_REC,
+            std::ptr::null(),
+        ) != 0
+        {
+            return Err(std::io::Error::last_os_error());
+        }
+        for (source, target, writable) in &jail.mounts {
+            if libc::mount(
+                source.as_ptr(),
+                target.as_ptr(),
+                std::ptr::null(),
+                libc::MS_BIND | libc::MS_REC,
+                std::ptr::null(),
+            ) != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+            // A remount only changes the top mount of a bind, so the flags are
+            // set on every mount below it too, or a submount of a read-only
+            // dir would still be writable.
+            let mut attr: libc::mount_attr = std::mem::zeroed();
+            attr.attr_set = if *writable {
+                libc::MOUNT_ATTR_NOSUID
+            } else {
+                libc::MOUNT_ATTR_NOSUID | libc::MOUNT_ATTR_RDONLY
+            };
+            if libc::syscall(
+                libc::SYS_mount_setattr,
+                libc::AT_FDCWD,
+                target.as_ptr(),
+                libc::AT_RECURSIVE,
+                &attr as *const libc::mount_attr,
+                std::mem::size_of::<libc::mount_attr>(),
+            ) != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        // Unlike chroot, pivot_root leaves no way back to the old root, not even
+        // through a dir fd opened before it, once the old root is unmounted.
+        if libc::chdir(jail.root.as_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if libc::syscall(libc::SYS_pivot_root, c".".as_ptr(), c".".as_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if libc::umount2(c".".as_ptr(), libc::MNT_DETACH) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if libc::chdir(jail.work_dir.as_ptr()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "run")]
+fn bring_up_loopback() -> std::io::Result<()> {
+    unsafe {
+        let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut ifr: libc::ifreq = std::mem::zeroed();
+        for (i, c) in b"lo".iter().enumerate() {
+            ifr.ifr_name[i] = *c as libc::c_char;
+        }
+        if libc::ioctl(fd, libc::SIOCGIFFLAGS, &mut ifr) < 0 {
+            let result = std::io::Error::last_os_error();
+            libc::close(fd);
+            return Err(result);
+        }
+        ifr.ifr_ifru.ifru_flags |= libc::IFF_UP as libc::c_short;
+        if libc::ioctl(fd, libc::SIOCSIFFLAGS, &ifr) < 0 {
+            let result = std::io::Error::last_os_error();
+            libc::close(fd);
+            return Err(result);
+        }
+        libc::close(fd);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "run")]
+fn limit_cpu_time(command: &mut tokio::process::Command, cpu_time_limit: TimeSpan) {
+    // RLIMIT_CPU only has a granularity of seconds, so it is just a backstop
+    // for spinning processes; the verdict is decided by the cgroup cpu usage.
+    let seconds = std::cmp::max(cpu_time_limit.as_milliseconds().div_ceil(1000), 1);
+    unsafe {
+        command.pre_exec(move || {
+            nix::sys::resource::setrlimit(
+                nix::sys::resource::Resource::RLIMIT_CPU,
+                seconds,
+                seconds + 1,
+            )
+            .map_err(std::io::Error::from)
+        });
+    }
+}
+
+#[cfg(feature = "run")]
+fn limit_file_size(command: &mut tokio::process::Command, output_limit: MemorySize) {
+    // Output files are capped while the process runs, one byte past the limit
+    // so that an overflow can still be told apart from output that fits.
+    let bytes = output_limit.as_bytes() as u64 + 1;
+    unsafe {
+        command.pre_exec(move || {
+            nix::sys::resource::setrlimit(nix::sys::resource::Resource::RLIMIT_FSIZE, bytes, bytes)
+                .map_err(std::io::Error::from)
+        });
+    }
+}
+
+#[cfg(feature = "run")]
+fn pin_to_cpu(command: &mut tokio::process::Command, cpu: Option<usize>) {
+    // Parallel runners give every worker its own core, so that tests running
+    // side by side do not steal time from each other.
+    let cpu = match cpu {
+        None => return,
+        Some(result) => result,
+    };
+    unsafe {
+        command.pre_exec(move || {
+            let mut cpu_set = nix::sched::CpuSet::new();
+            cpu_set.set(cpu).map_err(std::io::Error::from)?;
+            nix::sched::sched_setaffinity(nix::unistd::Pid::from_raw(0), &cpu_set)
+                .map_err(std::io::Error::from)
+        });
+    }
+}
+
+#[cfg(feature = "run")]
+async fn read_file_with_limit(
+    path: &str,
+    limit: MemorySize,
+) -> Result<(Vec<u8>, bool), JudgeError> {
+    let mut buf = vec![];
+    match tokio::fs::File::open(path).await {
+        Err(result) => {
+            return Err(JudgeError::io("open output file", result));
+        }
+        Ok(file) => {
+            if let Err(result) = file
+                .take(limit.as_bytes() as u64 + 1)
+                .read_to_end(&mut buf)
+                .await
+            {
+                return Err(JudgeError::io("read output file", result));
+            }
+        }
+    }
+    let is_exceeded = buf.len() > limit.as_bytes();
+    buf.truncate(limit.as_bytes());
+    Ok((buf, is_exceeded))
+}
+
+// Archives are either zip or tar, the latter optionally gzipped. Only regular
+// files and dirs are taken, and the unpacked size is capped by `limit` so a
+// small archive can not expand into something the disk can not hold.
+#[cfg(feature = "archive")]
+#[cfg_attr(not(feature = "compile"), allow(dead_code))]
+fn unpack_archive(archive: &[u8], limit: usize) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut result = vec![];
+    let mut total = 0;
+    let mut read_entry = |path: String, reader: &mut dyn Read| -> Result<(), String> {
+        let mut content = vec![];
+        if let Err(result) = reader
+            .take((limit - total) as u64 + 1)
+            .read_to_end(&mut content)
+        {
+            return Err(format!("invalid archive: {}", result));
+        }
+        total += content.len();
+        if total > limit {
+            return Err("archive is too large".to_string());
+        }
+        result.push((path, content));
+        Ok(())
+    };
+    if archive.starts_with(b"PK") {
+        let mut zip = match zip::ZipArchive::new(std::io::Cursor::new(archive)) {
+            Err(result) => return Err(format!("invalid archive: {}", result)),
+            Ok(result) => result,
+        };
+        for i in 0..zip.len() {
+            let mut file = match zip.by_index(i) {
+                Err(result) => return Err(format!("invalid archive: {}", result)),
+                Ok(result) => result,
+            };
+            if file.is_dir() {
+                continue;
+            }
+            if let Some(mode) = file.unix_mode() {
+                if mode & libc::S_IFMT != libc::S_IFREG && mode & libc::S_IFMT != 0 {
+                    return Err(format!("{} is not a regular file", file.name()));
+                }
+            }
+            let path = file.name().to_string();
+            read_entry(path, &mut file)?;
+        }
+    } else {
+        let reader: Box<dyn Read> = if archive.starts_with(&[0x1f, 0x8b]) {
+            Box::new(flate2::read::GzDecoder::new(archive))
+        } else {
+            Box::new(archive)
+        };
+        let mut tar = tar::Archive::new(reader);
+        let entries = match tar.entries() {
+            Err(result) => return Err(format!("invalid archive: {}", result)),
+            Ok(result) => result,
+        };
+        for entry in entries {
+            let mut entry = match entry {
+                Err(result) => return Err(format!("invalid archive: {}", result)),
+                Ok(result) => result,
+            };
+            let path = match entry.path() {
+                Err(result) => return Err(format!("invalid archive: {}", result)),
+                Ok(result) => result.to_string_lossy().to_string(),
+            };
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => continue,
+                tar::EntryType::Regular | tar::EntryType::Continuous => {}
+                _ => return Err(format!("{} is not a regular file", path)),
+            }
+            read_entry(path, &mut entry)?;
         }
-        Ok(result) => result,
-    };
-    if metadata.len() > limit.as_bytes() as u64 {
-        return Ok("Exceed Limit".to_string());
     }
-    Ok(String::new())
+    Ok(result)
 }
 
 pub fn check_admin_privilege() -> bool {
@@ -279,23 +279,134 @@ impl Sub for TimeSpan {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TranscriptDirection {
+    ToInteractor,
+    FromInteractor,
+}
+
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TranscriptChunk {
+    pub direction: TranscriptDirection,
+    // Since the interactor was started.
+    pub time: TimeSpan,
+    // Of the first byte, counted over everything sent in this direction.
+    pub offset: usize,
+    #[cfg_attr(feature = "base64", serde_as(as = "serde_with::base64::Base64"))]
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Transcript {
+    pub chunks: Vec<TranscriptChunk>,
+    pub size: MemorySize,
+    pub limit: MemorySize,
+    pub truncated: bool,
+}
+
+impl Transcript {
+    pub fn new(limit: MemorySize) -> Self {
+        Transcript {
+            chunks: vec![],
+            size: MemorySize::default(),
+            limit,
+            truncated: false,
+        }
+    }
+
+    // Whatever goes past the limit is dropped, the exchange itself goes on.
+    pub fn record(
+        &mut self,
+        direction: TranscriptDirection,
+        time: TimeSpan,
+        offset: usize,
+        data: &[u8],
+    ) {
+        let room = self.limit.as_bytes().saturating_sub(self.size.as_bytes());
+        if data.len() > room {
+            self.truncated = true;
+        }
+        let data = &data[..data.len().min(room)];
+        if data.is_empty() {
+            return;
+        }
+        self.size = MemorySize::from_bytes(self.size.as_bytes() + data.len());
+        self.chunks.push(TranscriptChunk {
+            direction,
+            time,
+            offset,
+            data: data.to_vec(),
+        });
+    }
+}
+
+// Fields added after the first release default when missing, so that results
+// stored before them can still be read.
+#[serde_with::serde_as]
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct ProcessResource {
     pub runtime: TimeSpan,
+    #[serde(default)]
+    pub cputime: TimeSpan,
     pub memory: MemorySize,
+    #[cfg_attr(feature = "base64", serde_as(as = "serde_with::base64::Base64"))]
     pub stdout: Vec<u8>,
+    #[cfg_attr(feature = "base64", serde_as(as = "serde_with::base64::Base64"))]
     pub stderr: Vec<u8>,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub signal: Option<i32>,
+    #[serde(default)]
+    pub core_dumped: bool,
+    #[serde(default)]
+    pub process_limit_exceeded: bool,
+    // Only set for a program run against an interactor while recording.
+    #[serde(default)]
+    pub transcript: Option<Transcript>,
 }
 
 impl ProcessResource {
     pub fn default() -> Self {
         ProcessResource {
             runtime: TimeSpan::default(),
+            cputime: TimeSpan::default(),
             memory: MemorySize::default(),
             stdout: vec![],
             stderr: vec![],
+            exit_code: None,
+            signal: None,
+            core_dumped: false,
+            process_limit_exceeded: false,
+            transcript: None,
         }
     }
+
+    // Several instances of a program taken as a whole: the wall time of the
+    // slowest one, and the cpu time and memory all of them used together.
+    pub fn total(resources: &[ProcessResource]) -> Self {
+        let mut result = ProcessResource::default();
+        for resource in resources {
+            if resource.runtime > result.runtime {
+                result.runtime = resource.runtime;
+            }
+            result.cputime = result.cputime + resource.cputime;
+            result.memory = result.memory + resource.memory;
+            result.core_dumped = result.core_dumped || resource.core_dumped;
+            result.process_limit_exceeded =
+                result.process_limit_exceeded || resource.process_limit_exceeded;
+        }
+        result
+    }
+
+    pub fn signal_name(&self) -> Option<String> {
+        self.signal
+            .map(|signal| match nix::sys::signal::Signal::try_from(signal) {
+                Ok(result) => result.as_str().to_string(),
+                Err(_) => format!("SIG{}", signal),
+            })
+    }
 }
 
 impl fmt::Display for ProcessResource {
@@ -327,10 +438,59 @@ impl fmt::Display for ProcessResource {
             stderr_escaped
         };
 
+        let exit_code = match self.exit_code {
+            Some(result) => result.to_string(),
+            None => "None".to_string(),
+        };
+        let signal = match self.signal_name() {
+            Some(result) if self.core_dumped => format!("{} (core dumped)", result),
+            Some(result) => result,
+            None => "None".to_string(),
+        };
+
         write!(
             f,
-            "Runtime: {}, Memory: {}, Stdout: {}, Stderr: {}",
-            self.runtime, self.memory, stdout_escaped, stderr_escaped
+            "Runtime: {}, CPU Time: {}, Memory: {}, Exit Code: {}, Signal: {}, Process Limit Exceeded: {}, Stdout: {}, Stderr: {}",
+            self.runtime,
+            self.cputime,
+            self.memory,
+            exit_code,
+            signal,
+            self.process_limit_exceeded,
+            stdout_escaped,
+            stderr_escaped
         )
     }
 }
+
+// One stage of a program run several times in a row: the run itself, and the
+// transformer that turned its output into the next stage's input, if any.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StageResource {
+    pub tested: ProcessResource,
+    pub transformer: Option<ProcessResource>,
+}
+
+// Several instances of a program run side by side: each of them on its own,
+// and all of them taken as a whole.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct InstancesResource {
+    pub each: Vec<ProcessResource>,
+    pub total: ProcessResource,
+}
+
+impl InstancesResource {
+    pub fn new(each: Vec<ProcessResource>) -> Self {
+        let total = ProcessResource::total(&each);
+        InstancesResource { each, total }
+    }
+}
+
+impl fmt::Display for StageResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.transformer {
+            None => write!(f, "{{{}}}", self.tested),
+            Some(transformer) => write!(f, "{{{}}} -> {{{}}}", self.tested, transformer),
+        }
+    }
+}
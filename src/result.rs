@@ -1,22 +1,41 @@
 use core::panic;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
+    error::JudgeError,
     program::{ExeCode, ExeResources},
-    quantity::ProcessResource,
+    quantity::{InstancesResource, ProcessResource, StageResource},
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeLimitKind {
+    CpuTime,
+    WallTime,
+}
+
+impl std::fmt::Display for TimeLimitKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeLimitKind::CpuTime => write!(f, "CpuTime"),
+            TimeLimitKind::WallTime => write!(f, "WallTime"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum CompileResult {
     SettingError,
-    InternalError(String),
+    InternalError(JudgeError),
     CompileError(String),
-    Ok(ExeCode),
+    CompileTimeLimitExceeded(String),
+    CompileMemoryLimitExceeded(String),
+    Ok(Box<ExeCode>),
 }
 
 impl CompileResult {
     pub fn unwrap(self) -> ExeCode {
         match self {
-            CompileResult::Ok(i) => i,
+            CompileResult::Ok(i) => *i,
             CompileResult::SettingError => panic!("CompileResult::SettingError is not allowed"),
             CompileResult::InternalError(i) => {
                 panic!("CompileResult::InternalError({}) is not allowed", i)
@@ -24,6 +43,18 @@ impl CompileResult {
             CompileResult::CompileError(i) => {
                 panic!("CompileResult::CompileError({}) is not allowed", i)
             }
+            CompileResult::CompileTimeLimitExceeded(i) => {
+                panic!(
+                    "CompileResult::CompileTimeLimitExceeded({}) is not allowed",
+                    i
+                )
+            }
+            CompileResult::CompileMemoryLimitExceeded(i) => {
+                panic!(
+                    "CompileResult::CompileMemoryLimitExceeded({}) is not allowed",
+                    i
+                )
+            }
         }
     }
 
@@ -41,6 +72,12 @@ impl std::fmt::Display for CompileResult {
             CompileResult::SettingError => write!(f, "SettingError"),
             CompileResult::InternalError(i) => write!(f, "InternalError({})", i),
             CompileResult::CompileError(i) => write!(f, "CompileError({})", i),
+            CompileResult::CompileTimeLimitExceeded(i) => {
+                write!(f, "CompileTimeLimitExceeded({})", i)
+            }
+            CompileResult::CompileMemoryLimitExceeded(i) => {
+                write!(f, "CompileMemoryLimitExceeded({})", i)
+            }
             CompileResult::Ok(_) => write!(f, "Ok"),
         }
     }
@@ -49,17 +86,21 @@ impl std::fmt::Display for CompileResult {
 #[derive(Debug)]
 pub enum InitExeResourceResult {
     PermissionDenied,
-    InternalError(String),
-    Ok(ExeResources),
+    SettingError,
+    InternalError(JudgeError),
+    Ok(Box<ExeResources>),
 }
 
 impl InitExeResourceResult {
     pub fn unwrap(self) -> ExeResources {
         match self {
-            InitExeResourceResult::Ok(i) => i,
+            InitExeResourceResult::Ok(i) => *i,
             InitExeResourceResult::PermissionDenied => {
                 panic!("InitExeResourceResult::PermissionDenied is not allowed")
             }
+            InitExeResourceResult::SettingError => {
+                panic!("InitExeResourceResult::SettingError is not allowed")
+            }
             InitExeResourceResult::InternalError(i) => {
                 panic!("InitExeResourceResult::InternalError({}) is not allowed", i)
             }
@@ -78,6 +119,7 @@ impl std::fmt::Display for InitExeResourceResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             InitExeResourceResult::PermissionDenied => write!(f, "PermissionDenied"),
+            InitExeResourceResult::SettingError => write!(f, "SettingError"),
             InitExeResourceResult::InternalError(i) => write!(f, "InternalError({})", i),
             InitExeResourceResult::Ok(_) => write!(f, "Ok"),
         }
@@ -86,11 +128,12 @@ impl std::fmt::Display for InitExeResourceResult {
 
 #[derive(Debug)]
 pub enum RunToEndResult {
-    InternalError(String),
+    InternalError(JudgeError),
     RuntimeError(ProcessResource),
     MemoryLimitExceeded(ProcessResource),
-    TimeLimitExceeded(ProcessResource),
+    TimeLimitExceeded(TimeLimitKind, ProcessResource),
     OutputLimitExceeded(ProcessResource),
+    RestrictedFunction(i64, ProcessResource),
     Ok(ProcessResource),
 }
 
@@ -107,12 +150,21 @@ impl RunToEndResult {
             RunToEndResult::MemoryLimitExceeded(i) => {
                 panic!("RunToEndResult::MemoryLimitExceeded({}) is not allowed", i)
             }
-            RunToEndResult::TimeLimitExceeded(i) => {
-                panic!("RunToEndResult::TimeLimitExceeded({}) is not allowed", i)
+            RunToEndResult::TimeLimitExceeded(k, i) => {
+                panic!(
+                    "RunToEndResult::TimeLimitExceeded({},{}) is not allowed",
+                    k, i
+                )
             }
             RunToEndResult::OutputLimitExceeded(i) => {
                 panic!("RunToEndResult::OutputLimitExceeded({}) is not allowed", i)
             }
+            RunToEndResult::RestrictedFunction(k, i) => {
+                panic!(
+                    "RunToEndResult::RestrictedFunction({},{}) is not allowed",
+                    k, i
+                )
+            }
         }
     }
 
@@ -122,6 +174,17 @@ impl RunToEndResult {
             _ => false,
         }
     }
+
+    // A checker that exits with a verdict has judged the answer, so that exit
+    // is not a runtime error of the checker.
+    pub fn into_eval(self, testlib: bool) -> Self {
+        match self {
+            RunToEndResult::RuntimeError(i) if EvalVerdict::is_verdict_exit(&i, testlib) => {
+                RunToEndResult::Ok(i)
+            }
+            i => i,
+        }
+    }
 }
 
 impl std::fmt::Display for RunToEndResult {
@@ -130,31 +193,315 @@ impl std::fmt::Display for RunToEndResult {
             RunToEndResult::InternalError(i) => write!(f, "InternalError({})", i),
             RunToEndResult::RuntimeError(i) => write!(f, "RuntimeError({})", i),
             RunToEndResult::MemoryLimitExceeded(i) => write!(f, "MemoryLimitExceeded({})", i),
-            RunToEndResult::TimeLimitExceeded(i) => write!(f, "TimeLimitExceeded({})", i),
+            RunToEndResult::TimeLimitExceeded(k, i) => write!(f, "TimeLimitExceeded({},{})", k, i),
             RunToEndResult::Ok(i) => write!(f, "Ok({})", i),
             RunToEndResult::OutputLimitExceeded(i) => write!(f, "OutputLimitExceeded({})", i),
+            RunToEndResult::RestrictedFunction(k, i) => {
+                write!(f, "RestrictedFunction({},{})", k, i)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvalStatus {
+    Accepted,
+    WrongAnswer,
+    PresentationError,
+    Partial,
+    Fail,
+}
+
+impl std::fmt::Display for EvalStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalStatus::Accepted => write!(f, "AC"),
+            EvalStatus::WrongAnswer => write!(f, "WA"),
+            EvalStatus::PresentationError => write!(f, "PE"),
+            EvalStatus::Partial => write!(f, "PARTIAL"),
+            EvalStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalVerdict {
+    pub status: EvalStatus,
+    pub score: f64,
+    pub message: String,
+}
+
+// The exit codes of testlib checkers. 4 and 8 are its extra output format and
+// unexpected eof codes.
+const EVAL_EXIT_CODES: [(i32, EvalStatus); 7] = [
+    (0, EvalStatus::Accepted),
+    (1, EvalStatus::WrongAnswer),
+    (2, EvalStatus::PresentationError),
+    (3, EvalStatus::Fail),
+    (4, EvalStatus::PresentationError),
+    (7, EvalStatus::Partial),
+    (8, EvalStatus::WrongAnswer),
+];
+
+impl EvalVerdict {
+    // The first line of the checker's stdout may start with AC, WA, PE, FAIL
+    // or PARTIAL followed by a score between 0 and 1, and everything after
+    // that is the message. A checker that prints no keyword has accepted the
+    // answer if it exits with 0, and failed otherwise.
+    pub fn from_process_resource(eval: &ProcessResource) -> Self {
+        let output = String::from_utf8_lossy(&eval.stdout);
+        let (status, message) = match split_keyword(&output) {
+            Some((status, message)) => (status, message),
+            None => match eval.exit_code.unwrap_or(0) {
+                0 => (EvalStatus::Accepted, output.to_string()),
+                exit_code => {
+                    return EvalVerdict::new(
+                        EvalStatus::Fail,
+                        format!("checker exited with code {}", exit_code).as_str(),
+                    );
+                }
+            },
+        };
+        let message = message.trim();
+        match status {
+            EvalStatus::Partial => {
+                let (score, message) = match message.split_once(char::is_whitespace) {
+                    None => (message, ""),
+                    Some(result) => result,
+                };
+                EvalVerdict::partial(score, message.trim())
+            }
+            status => EvalVerdict::new(status, message),
+        }
+    }
+
+    // A testlib checker run with -appes describes its verdict in the report
+    // file, e.g. <result outcome = "points" points = "0.5">message</result>.
+    // Without a report the exit code decides and stderr, such as
+    // "points 0.5 message", carries the message.
+    pub fn from_testlib(eval: &ProcessResource, report: &str) -> Self {
+        if let Some((outcome, points, message)) = parse_testlib_report(report) {
+            let message = message.trim();
+            return match outcome.as_str() {
+                "accepted" => EvalVerdict::new(EvalStatus::Accepted, message),
+                "wrong-answer" | "unexpected-eof" => {
+                    EvalVerdict::new(EvalStatus::WrongAnswer, message)
+                }
+                "presentation-error" => EvalVerdict::new(EvalStatus::PresentationError, message),
+                "fail" => EvalVerdict::new(EvalStatus::Fail, message),
+                "points" | "relative-scoring" => {
+                    EvalVerdict::partial(points.unwrap_or_default().trim(), message)
+                }
+                _ => EvalVerdict::new(
+                    EvalStatus::Fail,
+                    format!("unknown testlib outcome {}", outcome).as_str(),
+                ),
+            };
+        }
+        let exit_code = eval.exit_code.unwrap_or(0);
+        let status = match EVAL_EXIT_CODES.iter().find(|(code, _)| *code == exit_code) {
+            None => {
+                return EvalVerdict::new(
+                    EvalStatus::Fail,
+                    format!("checker exited with code {}", exit_code).as_str(),
+                );
+            }
+            Some((_, status)) => *status,
+        };
+        let message = String::from_utf8_lossy(&eval.stderr);
+        let message = message.trim();
+        match status {
+            EvalStatus::Partial => {
+                let message = message.strip_prefix("points").unwrap_or(message).trim();
+                let (score, message) = match message.split_once(char::is_whitespace) {
+                    None => (message, ""),
+                    Some(result) => result,
+                };
+                EvalVerdict::partial(score, message.trim())
+            }
+            status => EvalVerdict::new(status, message),
+        }
+    }
+
+    fn new(status: EvalStatus, message: &str) -> Self {
+        EvalVerdict {
+            status,
+            score: match status {
+                EvalStatus::Accepted => 1.0,
+                _ => 0.0,
+            },
+            message: message.to_string(),
+        }
+    }
+
+    fn partial(score: &str, message: &str) -> Self {
+        match score.parse::<f64>() {
+            Ok(result) if (0.0..=1.0).contains(&result) => EvalVerdict {
+                status: EvalStatus::Partial,
+                score: result,
+                message: message.to_string(),
+            },
+            _ => EvalVerdict::new(
+                EvalStatus::Fail,
+                format!("invalid partial score {}", score).as_str(),
+            ),
+        }
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        self.status == EvalStatus::Accepted
+    }
+
+    // Whether the process exited on its own with a verdict, rather than
+    // crashing or being killed. A testlib checker reports through its exit
+    // codes, but any other checker that exits with a nonzero code has only
+    // judged the answer if it printed a keyword, as a crashing script exits
+    // with 1 or 2 too.
+    pub fn is_verdict_exit(eval: &ProcessResource, testlib: bool) -> bool {
+        if eval.signal.is_some() {
+            return false;
+        }
+        match eval.exit_code {
+            None => false,
+            Some(0) => true,
+            Some(exit_code) if testlib => {
+                EVAL_EXIT_CODES.iter().any(|(code, _)| *code == exit_code)
+            }
+            Some(_) => split_keyword(&String::from_utf8_lossy(&eval.stdout)).is_some(),
+        }
+    }
+}
+
+// Returns the status named by the keyword that starts the output, along with
+// the rest of the output, or None if there is no keyword.
+fn split_keyword(output: &str) -> Option<(EvalStatus, String)> {
+    let output = output.trim_start();
+    let (first_line, rest) = match output.split_once('\n') {
+        None => (output, ""),
+        Some(result) => result,
+    };
+    let (keyword, first_line_rest) = match first_line.trim().split_once(char::is_whitespace) {
+        None => (first_line.trim(), ""),
+        Some(result) => result,
+    };
+    let status = match keyword {
+        "AC" => EvalStatus::Accepted,
+        "WA" => EvalStatus::WrongAnswer,
+        "PE" => EvalStatus::PresentationError,
+        "PARTIAL" => EvalStatus::Partial,
+        "FAIL" => EvalStatus::Fail,
+        _ => return None,
+    };
+    Some((status, format!("{}\n{}", first_line_rest, rest)))
+}
+
+// Returns the outcome, the points attribute and the unescaped message of the
+// <result> element, or None if the report holds no such element.
+fn parse_testlib_report(report: &str) -> Option<(String, Option<String>, String)> {
+    let start = report.find("<result")? + "<result".len();
+    let end = start + report[start..].find('>')?;
+    let mut attributes = report[start..end].trim_end_matches('/');
+    let mut outcome = None;
+    let mut points = None;
+    loop {
+        attributes = attributes.trim_start();
+        if attributes.is_empty() {
+            break;
+        }
+        let (name, rest) = attributes.split_once('=')?;
+        let rest = rest.trim_start();
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let (value, rest) = rest[1..].split_once(quote)?;
+        match name.trim() {
+            "outcome" => outcome = Some(unescape_xml(value)),
+            "points" => points = Some(unescape_xml(value)),
+            _ => {}
+        }
+        attributes = rest;
+    }
+    let message = match report[start..end].ends_with('/') {
+        true => String::new(),
+        false => {
+            let body = &report[end + 1..];
+            unescape_xml(&body[..body.find("</result>").unwrap_or(body.len())])
+        }
+    };
+    Some((outcome?, points, message))
+}
+
+fn unescape_xml(s: &str) -> String {
+    let mut result = String::new();
+    let mut rest = s;
+    while let Some(index) = rest.find('&') {
+        result.push_str(&rest[..index]);
+        rest = &rest[index..];
+        let entity = match rest.find(';') {
+            None => None,
+            Some(end) => {
+                let name = &rest[1..end];
+                let c = match name {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    _ => match name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => name.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                    }
+                    .and_then(char::from_u32),
+                };
+                c.map(|c| (c, end))
+            }
+        };
+        match entity {
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+impl std::fmt::Display for EvalVerdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.status {
+            EvalStatus::Partial => write!(f, "{} {}", self.status, self.score)?,
+            _ => write!(f, "{}", self.status)?,
         }
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        Ok(())
     }
 }
 
 #[derive(Debug)]
 pub enum RunWithInteractorResult {
-    InternalError(String),
+    InternalError(JudgeError),
     RuntimeError(ProcessResource, ProcessResource),
     MemoryLimitExceeded(ProcessResource, ProcessResource),
-    TimeLimitExceeded(ProcessResource, ProcessResource),
+    TimeLimitExceeded(TimeLimitKind, ProcessResource, ProcessResource),
     OutputLimitExceeded(ProcessResource, ProcessResource),
+    RestrictedFunction(i64, ProcessResource, ProcessResource),
     InteractorRuntimeError(ProcessResource, ProcessResource),
     InteractorMemoryLimitExceeded(ProcessResource, ProcessResource),
     InteractorTimeLimitExceeded(ProcessResource, ProcessResource),
     InteractorOutputLimitExceeded(ProcessResource, ProcessResource),
-    Ok(ProcessResource, ProcessResource),
+    InteractorRestrictedFunction(i64, ProcessResource, ProcessResource),
+    Ok(ProcessResource, ProcessResource, EvalVerdict),
 }
 
 impl RunWithInteractorResult {
-    pub fn unwrap(self) -> (ProcessResource, ProcessResource) {
+    pub fn unwrap(self) -> (ProcessResource, ProcessResource, EvalVerdict) {
         match self {
-            RunWithInteractorResult::Ok(i, j) => (i, j),
+            RunWithInteractorResult::Ok(i, j, k) => (i, j, k),
             RunWithInteractorResult::InternalError(i) => panic!(
                 "RunWithInteractorResult::InternalError({}) is not allowed",
                 i
@@ -167,9 +514,9 @@ impl RunWithInteractorResult {
                 "RunWithInteractorResult::MemoryLimitExceeded({},{}) is not allowed",
                 i, j
             ),
-            RunWithInteractorResult::TimeLimitExceeded(i, j) => panic!(
-                "RunWithInteractorResult::TimeLimitExceeded({},{}) is not allowed",
-                i, j
+            RunWithInteractorResult::TimeLimitExceeded(k, i, j) => panic!(
+                "RunWithInteractorResult::TimeLimitExceeded({},{},{}) is not allowed",
+                k, i, j
             ),
             RunWithInteractorResult::InteractorRuntimeError(i, j) => panic!(
                 "RunWithInteractorResult::InteractorRuntimeError({},{}) is not allowed",
@@ -191,12 +538,101 @@ impl RunWithInteractorResult {
                 "RunWithInteractorResult::OutputLimitExceeded({},{}) is not allowed",
                 i, j
             ),
+            RunWithInteractorResult::RestrictedFunction(k, i, j) => panic!(
+                "RunWithInteractorResult::RestrictedFunction({},{},{}) is not allowed",
+                k, i, j
+            ),
+            RunWithInteractorResult::InteractorRestrictedFunction(k, i, j) => panic!(
+                "RunWithInteractorResult::InteractorRestrictedFunction({},{},{}) is not allowed",
+                k, i, j
+            ),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        match self {
+            RunWithInteractorResult::Ok(_, _, _) => true,
+            _ => false,
+        }
+    }
+}
+
+// The instance fields point at the first contestant process that failed, the
+// instances hold every one of them in the order they were given, and their
+// total.
+#[derive(Debug)]
+pub enum RunWithManagerResult {
+    InternalError(JudgeError),
+    RuntimeError(usize, InstancesResource, ProcessResource),
+    MemoryLimitExceeded(usize, InstancesResource, ProcessResource),
+    TimeLimitExceeded(TimeLimitKind, usize, InstancesResource, ProcessResource),
+    OutputLimitExceeded(usize, InstancesResource, ProcessResource),
+    RestrictedFunction(i64, usize, InstancesResource, ProcessResource),
+    ManagerRuntimeError(InstancesResource, ProcessResource),
+    ManagerMemoryLimitExceeded(InstancesResource, ProcessResource),
+    ManagerTimeLimitExceeded(InstancesResource, ProcessResource),
+    ManagerOutputLimitExceeded(InstancesResource, ProcessResource),
+    ManagerRestrictedFunction(i64, InstancesResource, ProcessResource),
+    Ok(InstancesResource, ProcessResource, EvalVerdict),
+}
+
+impl RunWithManagerResult {
+    pub fn unwrap(self) -> (InstancesResource, ProcessResource, EvalVerdict) {
+        match self {
+            RunWithManagerResult::Ok(i, j, k) => (i, j, k),
+            RunWithManagerResult::InternalError(i) => {
+                panic!("RunWithManagerResult::InternalError({}) is not allowed", i)
+            }
+            RunWithManagerResult::RuntimeError(i, _, j) => panic!(
+                "RunWithManagerResult::RuntimeError({},{}) is not allowed",
+                i, j
+            ),
+            RunWithManagerResult::MemoryLimitExceeded(i, _, j) => panic!(
+                "RunWithManagerResult::MemoryLimitExceeded({},{}) is not allowed",
+                i, j
+            ),
+            RunWithManagerResult::TimeLimitExceeded(k, i, _, j) => panic!(
+                "RunWithManagerResult::TimeLimitExceeded({},{},{}) is not allowed",
+                k, i, j
+            ),
+            RunWithManagerResult::OutputLimitExceeded(i, _, j) => panic!(
+                "RunWithManagerResult::OutputLimitExceeded({},{}) is not allowed",
+                i, j
+            ),
+            RunWithManagerResult::RestrictedFunction(k, i, _, j) => panic!(
+                "RunWithManagerResult::RestrictedFunction({},{},{}) is not allowed",
+                k, i, j
+            ),
+            RunWithManagerResult::ManagerRuntimeError(_, j) => panic!(
+                "RunWithManagerResult::ManagerRuntimeError({}) is not allowed",
+                j
+            ),
+            RunWithManagerResult::ManagerMemoryLimitExceeded(_, j) => panic!(
+                "RunWithManagerResult::ManagerMemoryLimitExceeded({}) is not allowed",
+                j
+            ),
+            RunWithManagerResult::ManagerTimeLimitExceeded(_, j) => panic!(
+                "RunWithManagerResult::ManagerTimeLimitExceeded({}) is not allowed",
+                j
+            ),
+            RunWithManagerResult::ManagerOutputLimitExceeded(_, j) => panic!(
+                "RunWithManagerResult::ManagerOutputLimitExceeded({}) is not allowed",
+                j
+            ),
+            RunWithManagerResult::ManagerRestrictedFunction(k, _, j) => panic!(
+                "RunWithManagerResult::ManagerRestrictedFunction({},{}) is not allowed",
+                k, j
+            ),
         }
     }
 
     pub fn is_ok(&self) -> bool {
+        matches!(self, RunWithManagerResult::Ok(_, _, _))
+    }
+
+    pub fn is_accepted(&self) -> bool {
         match self {
-            RunWithInteractorResult::Ok(_, _) => true,
+            RunWithManagerResult::Ok(_, _, eval_verdict) => eval_verdict.is_accepted(),
             _ => false,
         }
     }
@@ -207,17 +643,22 @@ pub enum OnlyRunResult {
     PermissionDenied,
     SettingError,
     CompileError(String),
-    InternalError(String),
+    CompileTimeLimitExceeded(String),
+    CompileMemoryLimitExceeded(String),
+    InternalError(JudgeError),
     RuntimeError(ProcessResource),
     MemoryLimitExceeded(ProcessResource),
-    TimeLimitExceeded(ProcessResource),
+    TimeLimitExceeded(TimeLimitKind, ProcessResource),
     OutputLimitExceeded(ProcessResource),
+    RestrictedFunction(i64, ProcessResource),
+    Skipped,
     Ok(ProcessResource),
 }
 
 impl OnlyRunResult {
     pub fn unwrap(self) -> ProcessResource {
         match self {
+            OnlyRunResult::Skipped => panic!("OnlyRunResult::Skipped is not allowed"),
             OnlyRunResult::Ok(i) => i,
             OnlyRunResult::PermissionDenied => {
                 panic!("OnlyRunResult::PermissionDenied is not allowed")
@@ -226,6 +667,18 @@ impl OnlyRunResult {
             OnlyRunResult::CompileError(i) => {
                 panic!("OnlyRunResult::CompileError({}) is not allowed", i)
             }
+            OnlyRunResult::CompileTimeLimitExceeded(i) => {
+                panic!(
+                    "OnlyRunResult::CompileTimeLimitExceeded({}) is not allowed",
+                    i
+                )
+            }
+            OnlyRunResult::CompileMemoryLimitExceeded(i) => {
+                panic!(
+                    "OnlyRunResult::CompileMemoryLimitExceeded({}) is not allowed",
+                    i
+                )
+            }
             OnlyRunResult::InternalError(i) => {
                 panic!("OnlyRunResult::InternalError({}) is not allowed", i)
             }
@@ -235,12 +688,21 @@ impl OnlyRunResult {
             OnlyRunResult::MemoryLimitExceeded(i) => {
                 panic!("OnlyRunResult::MemoryLimitExceeded({}) is not allowed", i)
             }
-            OnlyRunResult::TimeLimitExceeded(i) => {
-                panic!("OnlyRunResult::TimeLimitExceeded({}) is not allowed", i)
+            OnlyRunResult::TimeLimitExceeded(k, i) => {
+                panic!(
+                    "OnlyRunResult::TimeLimitExceeded({},{}) is not allowed",
+                    k, i
+                )
             }
             OnlyRunResult::OutputLimitExceeded(i) => {
                 panic!("OnlyRunResult::OutputLimitExceeded({}) is not allowed", i)
             }
+            OnlyRunResult::RestrictedFunction(k, i) => {
+                panic!(
+                    "OnlyRunResult::RestrictedFunction({},{}) is not allowed",
+                    k, i
+                )
+            }
         }
     }
 
@@ -255,15 +717,23 @@ impl OnlyRunResult {
 impl std::fmt::Display for OnlyRunResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            OnlyRunResult::Skipped => write!(f, "Skipped"),
             OnlyRunResult::PermissionDenied => write!(f, "PermissionDenied"),
             OnlyRunResult::SettingError => write!(f, "SettingError"),
             OnlyRunResult::CompileError(i) => write!(f, "CompileError({})", i),
+            OnlyRunResult::CompileTimeLimitExceeded(i) => {
+                write!(f, "CompileTimeLimitExceeded({})", i)
+            }
+            OnlyRunResult::CompileMemoryLimitExceeded(i) => {
+                write!(f, "CompileMemoryLimitExceeded({})", i)
+            }
             OnlyRunResult::InternalError(i) => write!(f, "InternalError({})", i),
             OnlyRunResult::RuntimeError(i) => write!(f, "RuntimeError({})", i),
             OnlyRunResult::MemoryLimitExceeded(i) => write!(f, "MemoryLimitExceeded({})", i),
-            OnlyRunResult::TimeLimitExceeded(i) => write!(f, "TimeLimitExceeded({})", i),
+            OnlyRunResult::TimeLimitExceeded(k, i) => write!(f, "TimeLimitExceeded({},{})", k, i),
             OnlyRunResult::Ok(i) => write!(f, "Ok({})", i),
             OnlyRunResult::OutputLimitExceeded(i) => write!(f, "OutputLimitExceeded({})", i),
+            OnlyRunResult::RestrictedFunction(k, i) => write!(f, "RestrictedFunction({},{})", k, i),
         }
     }
 }
@@ -274,6 +744,12 @@ impl From<CompileResult> for OnlyRunResult {
             CompileResult::SettingError => OnlyRunResult::SettingError,
             CompileResult::InternalError(i) => OnlyRunResult::InternalError(i),
             CompileResult::CompileError(i) => OnlyRunResult::CompileError(i),
+            CompileResult::CompileTimeLimitExceeded(i) => {
+                OnlyRunResult::CompileTimeLimitExceeded(i)
+            }
+            CompileResult::CompileMemoryLimitExceeded(i) => {
+                OnlyRunResult::CompileMemoryLimitExceeded(i)
+            }
             CompileResult::Ok(_) => {
                 panic!("From<CompileResult> for OnlyRunResult: CompileResult::Ok(_) is not allowed")
             }
@@ -285,6 +761,7 @@ impl From<InitExeResourceResult> for OnlyRunResult {
     fn from(i: InitExeResourceResult) -> Self {
         match i {
             InitExeResourceResult::PermissionDenied => OnlyRunResult::PermissionDenied,
+            InitExeResourceResult::SettingError => OnlyRunResult::SettingError,
             InitExeResourceResult::InternalError(i) => OnlyRunResult::InternalError(i),
             InitExeResourceResult::Ok(_) => panic!("From<InitExeResourceResult> for OnlyRunResult: InitExeResourceResult::Ok(_) is not allowed"),
         }
@@ -297,9 +774,257 @@ impl From<RunToEndResult> for OnlyRunResult {
             RunToEndResult::InternalError(i) => OnlyRunResult::InternalError(i),
             RunToEndResult::RuntimeError(i) => OnlyRunResult::RuntimeError(i),
             RunToEndResult::MemoryLimitExceeded(i) => OnlyRunResult::MemoryLimitExceeded(i),
-            RunToEndResult::TimeLimitExceeded(i) => OnlyRunResult::TimeLimitExceeded(i),
+            RunToEndResult::TimeLimitExceeded(k, i) => OnlyRunResult::TimeLimitExceeded(k, i),
             RunToEndResult::Ok(i) => OnlyRunResult::Ok(i),
             RunToEndResult::OutputLimitExceeded(i) => OnlyRunResult::OutputLimitExceeded(i),
+            RunToEndResult::RestrictedFunction(k, i) => OnlyRunResult::RestrictedFunction(k, i),
+        }
+    }
+}
+
+// The stages come in the order they ran, the one that failed, if any, being
+// the last.
+#[derive(Debug, Clone)]
+pub enum RunInStagesResult {
+    PermissionDenied,
+    SettingError,
+    InternalError(JudgeError),
+    CompileError(String),
+    CompileTimeLimitExceeded(String),
+    CompileMemoryLimitExceeded(String),
+    TransformerCompileError(String),
+    TransformerCompileTimeLimitExceeded(String),
+    TransformerCompileMemoryLimitExceeded(String),
+    RuntimeError(Vec<StageResource>),
+    MemoryLimitExceeded(Vec<StageResource>),
+    TimeLimitExceeded(TimeLimitKind, Vec<StageResource>),
+    OutputLimitExceeded(Vec<StageResource>),
+    RestrictedFunction(i64, Vec<StageResource>),
+    TransformerRuntimeError(Vec<StageResource>),
+    TransformerMemoryLimitExceeded(Vec<StageResource>),
+    TransformerTimeLimitExceeded(TimeLimitKind, Vec<StageResource>),
+    TransformerOutputLimitExceeded(Vec<StageResource>),
+    TransformerRestrictedFunction(i64, Vec<StageResource>),
+    Skipped,
+    Ok(Vec<StageResource>),
+}
+
+fn stages_to_string(stages: &[StageResource]) -> String {
+    stages
+        .iter()
+        .map(|stage| stage.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+impl RunInStagesResult {
+    pub fn unwrap(self) -> Vec<StageResource> {
+        match self {
+            RunInStagesResult::Ok(i) => i,
+            i => panic!("RunInStagesResult::{} is not allowed", i),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        matches!(self, RunInStagesResult::Ok(_))
+    }
+
+    // There is no checker, so a test passes when every stage ran to the end.
+    pub fn is_accepted(&self) -> bool {
+        self.is_ok()
+    }
+
+    // What the last stage left, after its transformer if it has one.
+    pub fn output(&self) -> Option<&Vec<u8>> {
+        match self {
+            RunInStagesResult::Ok(i) => i.last().map(|stage| match &stage.transformer {
+                None => &stage.tested.stdout,
+                Some(transformer) => &transformer.stdout,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RunInStagesResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunInStagesResult::Skipped => write!(f, "Skipped"),
+            RunInStagesResult::PermissionDenied => write!(f, "PermissionDenied"),
+            RunInStagesResult::SettingError => write!(f, "SettingError"),
+            RunInStagesResult::InternalError(i) => write!(f, "InternalError({})", i),
+            RunInStagesResult::CompileError(i) => write!(f, "CompileError({})", i),
+            RunInStagesResult::CompileTimeLimitExceeded(i) => {
+                write!(f, "CompileTimeLimitExceeded({})", i)
+            }
+            RunInStagesResult::CompileMemoryLimitExceeded(i) => {
+                write!(f, "CompileMemoryLimitExceeded({})", i)
+            }
+            RunInStagesResult::TransformerCompileError(i) => {
+                write!(f, "TransformerCompileError({})", i)
+            }
+            RunInStagesResult::TransformerCompileTimeLimitExceeded(i) => {
+                write!(f, "TransformerCompileTimeLimitExceeded({})", i)
+            }
+            RunInStagesResult::TransformerCompileMemoryLimitExceeded(i) => {
+                write!(f, "TransformerCompileMemoryLimitExceeded({})", i)
+            }
+            RunInStagesResult::RuntimeError(i) => {
+                write!(f, "RuntimeError([{}])", stages_to_string(i))
+            }
+            RunInStagesResult::MemoryLimitExceeded(i) => {
+                write!(f, "MemoryLimitExceeded([{}])", stages_to_string(i))
+            }
+            RunInStagesResult::TimeLimitExceeded(k, i) => {
+                write!(f, "TimeLimitExceeded({},[{}])", k, stages_to_string(i))
+            }
+            RunInStagesResult::OutputLimitExceeded(i) => {
+                write!(f, "OutputLimitExceeded([{}])", stages_to_string(i))
+            }
+            RunInStagesResult::RestrictedFunction(k, i) => {
+                write!(f, "RestrictedFunction({},[{}])", k, stages_to_string(i))
+            }
+            RunInStagesResult::TransformerRuntimeError(i) => {
+                write!(f, "TransformerRuntimeError([{}])", stages_to_string(i))
+            }
+            RunInStagesResult::TransformerMemoryLimitExceeded(i) => {
+                write!(
+                    f,
+                    "TransformerMemoryLimitExceeded([{}])",
+                    stages_to_string(i)
+                )
+            }
+            RunInStagesResult::TransformerTimeLimitExceeded(k, i) => {
+                write!(
+                    f,
+                    "TransformerTimeLimitExceeded({},[{}])",
+                    k,
+                    stages_to_string(i)
+                )
+            }
+            RunInStagesResult::TransformerOutputLimitExceeded(i) => {
+                write!(
+                    f,
+                    "TransformerOutputLimitExceeded([{}])",
+                    stages_to_string(i)
+                )
+            }
+            RunInStagesResult::TransformerRestrictedFunction(k, i) => {
+                write!(
+                    f,
+                    "TransformerRestrictedFunction({},[{}])",
+                    k,
+                    stages_to_string(i)
+                )
+            }
+            RunInStagesResult::Ok(i) => write!(f, "Ok([{}])", stages_to_string(i)),
+        }
+    }
+}
+
+impl From<CompileResult> for RunInStagesResult {
+    fn from(i: CompileResult) -> Self {
+        match i {
+            CompileResult::SettingError => RunInStagesResult::SettingError,
+            CompileResult::InternalError(i) => RunInStagesResult::InternalError(i),
+            CompileResult::CompileError(i) => RunInStagesResult::CompileError(i),
+            CompileResult::CompileTimeLimitExceeded(i) => {
+                RunInStagesResult::CompileTimeLimitExceeded(i)
+            }
+            CompileResult::CompileMemoryLimitExceeded(i) => {
+                RunInStagesResult::CompileMemoryLimitExceeded(i)
+            }
+            CompileResult::Ok(_) => panic!(
+                "From<CompileResult> for RunInStagesResult: CompileResult::Ok(_) is not allowed"
+            ),
+        }
+    }
+}
+
+impl From<InitExeResourceResult> for RunInStagesResult {
+    fn from(i: InitExeResourceResult) -> Self {
+        match i {
+            InitExeResourceResult::PermissionDenied => RunInStagesResult::PermissionDenied,
+            InitExeResourceResult::SettingError => RunInStagesResult::SettingError,
+            InitExeResourceResult::InternalError(i) => RunInStagesResult::InternalError(i),
+            InitExeResourceResult::Ok(_) => panic!("From<InitExeResourceResult> for RunInStagesResult: InitExeResourceResult::Ok(_) is not allowed"),
+        }
+    }
+}
+
+impl RunInStagesResult {
+    pub fn to_transformer(self) -> Self {
+        match self {
+            RunInStagesResult::CompileError(i) => RunInStagesResult::TransformerCompileError(i),
+            RunInStagesResult::CompileTimeLimitExceeded(i) => {
+                RunInStagesResult::TransformerCompileTimeLimitExceeded(i)
+            }
+            RunInStagesResult::CompileMemoryLimitExceeded(i) => {
+                RunInStagesResult::TransformerCompileMemoryLimitExceeded(i)
+            }
+            RunInStagesResult::RuntimeError(i) => RunInStagesResult::TransformerRuntimeError(i),
+            RunInStagesResult::MemoryLimitExceeded(i) => {
+                RunInStagesResult::TransformerMemoryLimitExceeded(i)
+            }
+            RunInStagesResult::TimeLimitExceeded(k, i) => {
+                RunInStagesResult::TransformerTimeLimitExceeded(k, i)
+            }
+            RunInStagesResult::OutputLimitExceeded(i) => {
+                RunInStagesResult::TransformerOutputLimitExceeded(i)
+            }
+            RunInStagesResult::RestrictedFunction(k, i) => {
+                RunInStagesResult::TransformerRestrictedFunction(k, i)
+            }
+            i => i,
+        }
+    }
+
+    // Adds a run of the tested program as a new stage, or of a transformer
+    // to the last stage. A run that did not go to the end ends the pipeline.
+    pub fn push_stage(
+        stages: &mut Vec<StageResource>,
+        result: RunToEndResult,
+        is_transformer: bool,
+    ) -> Result<(), Self> {
+        let (resource, failure): (ProcessResource, Option<Box<dyn FnOnce(_) -> Self>>) =
+            match result {
+                RunToEndResult::InternalError(i) => {
+                    return Err(RunInStagesResult::InternalError(i))
+                }
+                RunToEndResult::Ok(i) => (i, None),
+                RunToEndResult::RuntimeError(i) => {
+                    (i, Some(Box::new(RunInStagesResult::RuntimeError)))
+                }
+                RunToEndResult::MemoryLimitExceeded(i) => {
+                    (i, Some(Box::new(RunInStagesResult::MemoryLimitExceeded)))
+                }
+                RunToEndResult::TimeLimitExceeded(k, i) => (
+                    i,
+                    Some(Box::new(move |stages| {
+                        RunInStagesResult::TimeLimitExceeded(k, stages)
+                    })),
+                ),
+                RunToEndResult::OutputLimitExceeded(i) => {
+                    (i, Some(Box::new(RunInStagesResult::OutputLimitExceeded)))
+                }
+                RunToEndResult::RestrictedFunction(k, i) => (
+                    i,
+                    Some(Box::new(move |stages| {
+                        RunInStagesResult::RestrictedFunction(k, stages)
+                    })),
+                ),
+            };
+        match (is_transformer, stages.last_mut()) {
+            (true, Some(stage)) => stage.transformer = Some(resource),
+            _ => stages.push(StageResource {
+                tested: resource,
+                transformer: None,
+            }),
+        }
+        match (failure, is_transformer) {
+            (None, _) => Ok(()),
+            (Some(failure), false) => Err(failure(std::mem::take(stages))),
+            (Some(failure), true) => Err(failure(std::mem::take(stages)).to_transformer()),
         }
     }
 }
@@ -308,48 +1033,68 @@ impl From<RunToEndResult> for OnlyRunResult {
 pub enum RunAndEvalResult {
     SettingError,
     PermissionDenied,
-    InternalError(String),
+    InternalError(JudgeError),
     CompileError(String),
+    CompileTimeLimitExceeded(String),
+    CompileMemoryLimitExceeded(String),
     RuntimeError(ProcessResource, ProcessResource),
     MemoryLimitExceeded(ProcessResource, ProcessResource),
-    TimeLimitExceeded(ProcessResource, ProcessResource),
+    TimeLimitExceeded(TimeLimitKind, ProcessResource, ProcessResource),
     OutputLimitExceeded(ProcessResource, ProcessResource),
+    RestrictedFunction(i64, ProcessResource, ProcessResource),
     EvalCompileError(String),
+    EvalCompileTimeLimitExceeded(String),
+    EvalCompileMemoryLimitExceeded(String),
     EvalRuntimeError(ProcessResource, ProcessResource),
     EvalMemoryLimitExceeded(ProcessResource, ProcessResource),
-    EvalTimeLimitExceeded(ProcessResource, ProcessResource),
+    EvalTimeLimitExceeded(TimeLimitKind, ProcessResource, ProcessResource),
     EvalOutputLimitExceeded(ProcessResource, ProcessResource),
-    Ok(ProcessResource, ProcessResource),
+    EvalRestrictedFunction(i64, ProcessResource, ProcessResource),
+    Skipped,
+    Ok(ProcessResource, ProcessResource, EvalVerdict),
 }
 
 impl RunAndEvalResult {
     pub fn to_eval(&self) -> Self {
         match self {
+            RunAndEvalResult::Skipped => RunAndEvalResult::Skipped,
             RunAndEvalResult::SettingError => RunAndEvalResult::SettingError,
             RunAndEvalResult::InternalError(i) => RunAndEvalResult::InternalError(i.clone()),
             RunAndEvalResult::CompileError(i) => RunAndEvalResult::EvalCompileError(i.clone()),
+            RunAndEvalResult::CompileTimeLimitExceeded(i) => {
+                RunAndEvalResult::EvalCompileTimeLimitExceeded(i.clone())
+            }
+            RunAndEvalResult::CompileMemoryLimitExceeded(i) => {
+                RunAndEvalResult::EvalCompileMemoryLimitExceeded(i.clone())
+            }
             RunAndEvalResult::RuntimeError(i, j) => {
                 RunAndEvalResult::EvalRuntimeError(i.clone(), j.clone())
             }
             RunAndEvalResult::MemoryLimitExceeded(i, j) => {
                 RunAndEvalResult::EvalMemoryLimitExceeded(i.clone(), j.clone())
             }
-            RunAndEvalResult::TimeLimitExceeded(i, j) => {
-                RunAndEvalResult::EvalTimeLimitExceeded(i.clone(), j.clone())
+            RunAndEvalResult::TimeLimitExceeded(k, i, j) => {
+                RunAndEvalResult::EvalTimeLimitExceeded(*k, i.clone(), j.clone())
             }
             RunAndEvalResult::EvalCompileError(_) => {
                 panic!("RunAndEvalResult::EvalCompileError(_) is not allowed")
             }
+            RunAndEvalResult::EvalCompileTimeLimitExceeded(_) => {
+                panic!("RunAndEvalResult::EvalCompileTimeLimitExceeded(_) is not allowed")
+            }
+            RunAndEvalResult::EvalCompileMemoryLimitExceeded(_) => {
+                panic!("RunAndEvalResult::EvalCompileMemoryLimitExceeded(_) is not allowed")
+            }
             RunAndEvalResult::EvalRuntimeError(_, _) => {
                 panic!("RunAndEvalResult::EvalRuntimeError(_, _) is not allowed")
             }
             RunAndEvalResult::EvalMemoryLimitExceeded(_, _) => {
                 panic!("RunAndEvalResult::EvalMemoryLimitExceeded(_, _) is not allowed")
             }
-            RunAndEvalResult::EvalTimeLimitExceeded(_, _) => {
-                panic!("RunAndEvalResult::EvalTimeLimitExceeded(_, _) is not allowed")
+            RunAndEvalResult::EvalTimeLimitExceeded(_, _, _) => {
+                panic!("RunAndEvalResult::EvalTimeLimitExceeded(_, _, _) is not allowed")
             }
-            RunAndEvalResult::Ok(_, _) => panic!("RunAndEvalResult::Ok(_, _) is not allowed"),
+            RunAndEvalResult::Ok(_, _, _) => panic!("RunAndEvalResult::Ok(_, _, _) is not allowed"),
             RunAndEvalResult::PermissionDenied => RunAndEvalResult::PermissionDenied,
             RunAndEvalResult::OutputLimitExceeded(i, j) => {
                 RunAndEvalResult::EvalOutputLimitExceeded(i.clone(), j.clone())
@@ -357,12 +1102,19 @@ impl RunAndEvalResult {
             RunAndEvalResult::EvalOutputLimitExceeded(_, _) => {
                 panic!("RunAndEvalResult::EvalOutputLimitExceeded(_, _) is not allowed")
             }
+            RunAndEvalResult::RestrictedFunction(k, i, j) => {
+                RunAndEvalResult::EvalRestrictedFunction(*k, i.clone(), j.clone())
+            }
+            RunAndEvalResult::EvalRestrictedFunction(_, _, _) => {
+                panic!("RunAndEvalResult::EvalRestrictedFunction(_, _, _) is not allowed")
+            }
         }
     }
 
-    pub fn unwrap(self) -> (ProcessResource, ProcessResource) {
+    pub fn unwrap(self) -> (ProcessResource, ProcessResource, EvalVerdict) {
         match self {
-            RunAndEvalResult::Ok(i, j) => (i, j),
+            RunAndEvalResult::Skipped => panic!("RunAndEvalResult::Skipped is not allowed"),
+            RunAndEvalResult::Ok(i, j, k) => (i, j, k),
             RunAndEvalResult::SettingError => {
                 panic!("RunAndEvalResult::SettingError is not allowed")
             }
@@ -375,6 +1127,14 @@ impl RunAndEvalResult {
             RunAndEvalResult::CompileError(i) => {
                 panic!("RunAndEvalResult::CompileError({}) is not allowed", i)
             }
+            RunAndEvalResult::CompileTimeLimitExceeded(i) => panic!(
+                "RunAndEvalResult::CompileTimeLimitExceeded({}) is not allowed",
+                i
+            ),
+            RunAndEvalResult::CompileMemoryLimitExceeded(i) => panic!(
+                "RunAndEvalResult::CompileMemoryLimitExceeded({}) is not allowed",
+                i
+            ),
             RunAndEvalResult::RuntimeError(i, j) => {
                 panic!("RunAndEvalResult::RuntimeError({},{}) is not allowed", i, j)
             }
@@ -382,13 +1142,21 @@ impl RunAndEvalResult {
                 "RunAndEvalResult::MemoryLimitExceeded({},{}) is not allowed",
                 i, j
             ),
-            RunAndEvalResult::TimeLimitExceeded(i, j) => panic!(
-                "RunAndEvalResult::TimeLimitExceeded({},{}) is not allowed",
-                i, j
+            RunAndEvalResult::TimeLimitExceeded(k, i, j) => panic!(
+                "RunAndEvalResult::TimeLimitExceeded({},{},{}) is not allowed",
+                k, i, j
             ),
             RunAndEvalResult::EvalCompileError(i) => {
                 panic!("RunAndEvalResult::EvalCompileError({}) is not allowed", i)
             }
+            RunAndEvalResult::EvalCompileTimeLimitExceeded(i) => panic!(
+                "RunAndEvalResult::EvalCompileTimeLimitExceeded({}) is not allowed",
+                i
+            ),
+            RunAndEvalResult::EvalCompileMemoryLimitExceeded(i) => panic!(
+                "RunAndEvalResult::EvalCompileMemoryLimitExceeded({}) is not allowed",
+                i
+            ),
             RunAndEvalResult::EvalRuntimeError(i, j) => panic!(
                 "RunAndEvalResult::EvalRuntimeError({},{}) is not allowed",
                 i, j
@@ -397,9 +1165,9 @@ impl RunAndEvalResult {
                 "RunAndEvalResult::EvalMemoryLimitExceeded({},{}) is not allowed",
                 i, j
             ),
-            RunAndEvalResult::EvalTimeLimitExceeded(i, j) => panic!(
-                "RunAndEvalResult::EvalTimeLimitExceeded({},{}) is not allowed",
-                i, j
+            RunAndEvalResult::EvalTimeLimitExceeded(k, i, j) => panic!(
+                "RunAndEvalResult::EvalTimeLimitExceeded({},{},{}) is not allowed",
+                k, i, j
             ),
             RunAndEvalResult::EvalOutputLimitExceeded(i, j) => panic!(
                 "RunAndEvalResult::EvalOutputLimitExceeded({},{}) is not allowed",
@@ -409,12 +1177,28 @@ impl RunAndEvalResult {
                 "RunAndEvalResult::OutputLimitExceeded({},{}) is not allowed",
                 i, j
             ),
+            RunAndEvalResult::RestrictedFunction(k, i, j) => panic!(
+                "RunAndEvalResult::RestrictedFunction({},{},{}) is not allowed",
+                k, i, j
+            ),
+            RunAndEvalResult::EvalRestrictedFunction(k, i, j) => panic!(
+                "RunAndEvalResult::EvalRestrictedFunction({},{},{}) is not allowed",
+                k, i, j
+            ),
         }
     }
 
     pub fn is_ok(&self) -> bool {
         match self {
-            RunAndEvalResult::Ok(_, _) => true,
+            RunAndEvalResult::Ok(_, _, _) => true,
+            _ => false,
+        }
+    }
+
+    // Ok only says the checker judged the output, this is whether it passed.
+    pub fn is_accepted(&self) -> bool {
+        match self {
+            RunAndEvalResult::Ok(_, _, eval_verdict) => eval_verdict.is_accepted(),
             _ => false,
         }
     }
@@ -423,25 +1207,38 @@ impl RunAndEvalResult {
 impl std::fmt::Display for RunAndEvalResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            RunAndEvalResult::Skipped => write!(f, "Skipped"),
             RunAndEvalResult::SettingError => write!(f, "SettingError"),
             RunAndEvalResult::InternalError(i) => write!(f, "InternalError({})", i),
             RunAndEvalResult::CompileError(i) => write!(f, "CompileError({})", i),
+            RunAndEvalResult::CompileTimeLimitExceeded(i) => {
+                write!(f, "CompileTimeLimitExceeded({})", i)
+            }
+            RunAndEvalResult::CompileMemoryLimitExceeded(i) => {
+                write!(f, "CompileMemoryLimitExceeded({})", i)
+            }
             RunAndEvalResult::RuntimeError(i, j) => write!(f, "RuntimeError({},{})", i, j),
             RunAndEvalResult::MemoryLimitExceeded(i, j) => {
                 write!(f, "MemoryLimitExceeded({},{})", i, j)
             }
-            RunAndEvalResult::TimeLimitExceeded(i, j) => {
-                write!(f, "TimeLimitExceeded({},{})", i, j)
+            RunAndEvalResult::TimeLimitExceeded(k, i, j) => {
+                write!(f, "TimeLimitExceeded({},{},{})", k, i, j)
             }
             RunAndEvalResult::EvalCompileError(i) => write!(f, "EvalCompileError({})", i),
+            RunAndEvalResult::EvalCompileTimeLimitExceeded(i) => {
+                write!(f, "EvalCompileTimeLimitExceeded({})", i)
+            }
+            RunAndEvalResult::EvalCompileMemoryLimitExceeded(i) => {
+                write!(f, "EvalCompileMemoryLimitExceeded({})", i)
+            }
             RunAndEvalResult::EvalRuntimeError(i, j) => write!(f, "EvalRuntimeError({},{})", i, j),
             RunAndEvalResult::EvalMemoryLimitExceeded(i, j) => {
                 write!(f, "EvalMemoryLimitExceeded({},{})", i, j)
             }
-            RunAndEvalResult::EvalTimeLimitExceeded(i, j) => {
-                write!(f, "EvalTimeLimitExceeded({},{})", i, j)
+            RunAndEvalResult::EvalTimeLimitExceeded(k, i, j) => {
+                write!(f, "EvalTimeLimitExceeded({},{},{})", k, i, j)
             }
-            RunAndEvalResult::Ok(i, j) => write!(f, "Ok({}, {})", i, j),
+            RunAndEvalResult::Ok(i, j, k) => write!(f, "Ok({}, {}, {})", i, j, k),
             RunAndEvalResult::PermissionDenied => write!(f, "PermissionDenied"),
             RunAndEvalResult::OutputLimitExceeded(i, j) => {
                 write!(f, "OutputLimitExceeded({},{})", i, j)
@@ -449,6 +1246,12 @@ impl std::fmt::Display for RunAndEvalResult {
             RunAndEvalResult::EvalOutputLimitExceeded(i, j) => {
                 write!(f, "EvalOutputLimitExceeded({},{})", i, j)
             }
+            RunAndEvalResult::RestrictedFunction(k, i, j) => {
+                write!(f, "RestrictedFunction({},{},{})", k, i, j)
+            }
+            RunAndEvalResult::EvalRestrictedFunction(k, i, j) => {
+                write!(f, "EvalRestrictedFunction({},{},{})", k, i, j)
+            }
         }
     }
 }
@@ -459,6 +1262,12 @@ impl From<CompileResult> for RunAndEvalResult {
             CompileResult::SettingError => RunAndEvalResult::SettingError,
             CompileResult::InternalError(i) => RunAndEvalResult::InternalError(i),
             CompileResult::CompileError(i) => RunAndEvalResult::CompileError(i),
+            CompileResult::CompileTimeLimitExceeded(i) => {
+                RunAndEvalResult::CompileTimeLimitExceeded(i)
+            }
+            CompileResult::CompileMemoryLimitExceeded(i) => {
+                RunAndEvalResult::CompileMemoryLimitExceeded(i)
+            }
             CompileResult::Ok(_) => panic!(
                 "From<CompileResult> for RunAndEvalResult: CompileResult::Ok(_) is not allowed"
             ),
@@ -470,6 +1279,7 @@ impl From<InitExeResourceResult> for RunAndEvalResult {
     fn from(i: InitExeResourceResult) -> Self {
         match i {
             InitExeResourceResult::PermissionDenied => RunAndEvalResult::PermissionDenied,
+            InitExeResourceResult::SettingError => RunAndEvalResult::SettingError,
             InitExeResourceResult::InternalError(i) => RunAndEvalResult::InternalError(i),
             InitExeResourceResult::Ok(_) => panic!("From<InitExeResourceResult> for RunAndEvalResult: InitExeResourceResult::Ok(_) is not allowed"),
         }
@@ -480,19 +1290,24 @@ impl From<InitExeResourceResult> for RunAndEvalResult {
 pub enum AnsAndEvalResult {
     PermissionDenied,
     SettingError,
-    InternalError(String),
+    InternalError(JudgeError),
     EvalCompileError(String),
+    EvalCompileTimeLimitExceeded(String),
+    EvalCompileMemoryLimitExceeded(String),
     EvalRuntimeError(ProcessResource),
     EvalMemoryLimitExceeded(ProcessResource),
-    EvalTimeLimitExceeded(ProcessResource),
+    EvalTimeLimitExceeded(TimeLimitKind, ProcessResource),
     EvalOutputLimitExceeded(ProcessResource),
-    Ok(ProcessResource),
+    EvalRestrictedFunction(i64, ProcessResource),
+    Skipped,
+    Ok(ProcessResource, EvalVerdict),
 }
 
 impl AnsAndEvalResult {
-    pub fn unwrap(self) -> ProcessResource {
+    pub fn unwrap(self) -> (ProcessResource, EvalVerdict) {
         match self {
-            AnsAndEvalResult::Ok(i) => i,
+            AnsAndEvalResult::Skipped => panic!("AnsAndEvalResult::Skipped is not allowed"),
+            AnsAndEvalResult::Ok(i, j) => (i, j),
             AnsAndEvalResult::PermissionDenied => {
                 panic!("AnsAndEvalResult::PermissionDenied is not allowed")
             }
@@ -505,6 +1320,14 @@ impl AnsAndEvalResult {
             AnsAndEvalResult::EvalCompileError(i) => {
                 panic!("AnsAndEvalResult::EvalCompileError({}) is not allowed", i)
             }
+            AnsAndEvalResult::EvalCompileTimeLimitExceeded(i) => panic!(
+                "AnsAndEvalResult::EvalCompileTimeLimitExceeded({}) is not allowed",
+                i
+            ),
+            AnsAndEvalResult::EvalCompileMemoryLimitExceeded(i) => panic!(
+                "AnsAndEvalResult::EvalCompileMemoryLimitExceeded({}) is not allowed",
+                i
+            ),
             AnsAndEvalResult::EvalRuntimeError(i) => {
                 panic!("AnsAndEvalResult::EvalRuntimeError({}) is not allowed", i)
             }
@@ -512,20 +1335,31 @@ impl AnsAndEvalResult {
                 "AnsAndEvalResult::EvalMemoryLimitExceeded({}) is not allowed",
                 i
             ),
-            AnsAndEvalResult::EvalTimeLimitExceeded(i) => panic!(
-                "AnsAndEvalResult::EvalTimeLimitExceeded({}) is not allowed",
-                i
+            AnsAndEvalResult::EvalTimeLimitExceeded(k, i) => panic!(
+                "AnsAndEvalResult::EvalTimeLimitExceeded({},{}) is not allowed",
+                k, i
             ),
             AnsAndEvalResult::EvalOutputLimitExceeded(i) => panic!(
                 "AnsAndEvalResult::EvalOutputLimitExceeded({}) is not allowed",
                 i
             ),
+            AnsAndEvalResult::EvalRestrictedFunction(k, i) => panic!(
+                "AnsAndEvalResult::EvalRestrictedFunction({},{}) is not allowed",
+                k, i
+            ),
         }
     }
 
     pub fn is_ok(&self) -> bool {
         match self {
-            AnsAndEvalResult::Ok(_) => true,
+            AnsAndEvalResult::Ok(_, _) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        match self {
+            AnsAndEvalResult::Ok(_, eval_verdict) => eval_verdict.is_accepted(),
             _ => false,
         }
     }
@@ -534,19 +1368,31 @@ impl AnsAndEvalResult {
 impl std::fmt::Display for AnsAndEvalResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            AnsAndEvalResult::Skipped => write!(f, "Skipped"),
             AnsAndEvalResult::PermissionDenied => write!(f, "PermissionDenied"),
             AnsAndEvalResult::SettingError => write!(f, "SettingError"),
             AnsAndEvalResult::InternalError(i) => write!(f, "InternalError({})", i),
             AnsAndEvalResult::EvalCompileError(i) => write!(f, "EvalCompileError({})", i),
+            AnsAndEvalResult::EvalCompileTimeLimitExceeded(i) => {
+                write!(f, "EvalCompileTimeLimitExceeded({})", i)
+            }
+            AnsAndEvalResult::EvalCompileMemoryLimitExceeded(i) => {
+                write!(f, "EvalCompileMemoryLimitExceeded({})", i)
+            }
             AnsAndEvalResult::EvalRuntimeError(i) => write!(f, "EvalRuntimeError({})", i),
             AnsAndEvalResult::EvalMemoryLimitExceeded(i) => {
                 write!(f, "EvalMemoryLimitExceeded({})", i)
             }
-            AnsAndEvalResult::EvalTimeLimitExceeded(i) => write!(f, "EvalTimeLimitExceeded({})", i),
-            AnsAndEvalResult::Ok(i) => write!(f, "Ok({})", i),
+            AnsAndEvalResult::EvalTimeLimitExceeded(k, i) => {
+                write!(f, "EvalTimeLimitExceeded({},{})", k, i)
+            }
+            AnsAndEvalResult::Ok(i, j) => write!(f, "Ok({}, {})", i, j),
             AnsAndEvalResult::EvalOutputLimitExceeded(i) => {
                 write!(f, "EvalOutputLimitExceeded({})", i)
             }
+            AnsAndEvalResult::EvalRestrictedFunction(k, i) => {
+                write!(f, "EvalRestrictedFunction({},{})", k, i)
+            }
         }
     }
 }
@@ -557,6 +1403,12 @@ impl From<CompileResult> for AnsAndEvalResult {
             CompileResult::SettingError => AnsAndEvalResult::SettingError,
             CompileResult::InternalError(i) => AnsAndEvalResult::InternalError(i),
             CompileResult::CompileError(i) => AnsAndEvalResult::EvalCompileError(i),
+            CompileResult::CompileTimeLimitExceeded(i) => {
+                AnsAndEvalResult::EvalCompileTimeLimitExceeded(i)
+            }
+            CompileResult::CompileMemoryLimitExceeded(i) => {
+                AnsAndEvalResult::EvalCompileMemoryLimitExceeded(i)
+            }
             CompileResult::Ok(_) => panic!(
                 "From<CompileResult> for AnsAndEvalResult: CompileResult::Ok(_) is not allowed"
             ),
@@ -568,6 +1420,7 @@ impl From<InitExeResourceResult> for AnsAndEvalResult {
     fn from(i: InitExeResourceResult) -> Self {
         match i {
             InitExeResourceResult::PermissionDenied => AnsAndEvalResult::PermissionDenied,
+            InitExeResourceResult::SettingError => AnsAndEvalResult::SettingError,
             InitExeResourceResult::InternalError(i) => AnsAndEvalResult::InternalError(i),
             InitExeResourceResult::Ok(_) => panic!("From<InitExeResourceResult> for AnsAndEvalResult: InitExeResourceResult::Ok(_) is not allowed"),
         }
@@ -580,9 +1433,17 @@ impl From<RunToEndResult> for AnsAndEvalResult {
             RunToEndResult::InternalError(i) => AnsAndEvalResult::InternalError(i),
             RunToEndResult::RuntimeError(i) => AnsAndEvalResult::EvalRuntimeError(i),
             RunToEndResult::MemoryLimitExceeded(i) => AnsAndEvalResult::EvalMemoryLimitExceeded(i),
-            RunToEndResult::TimeLimitExceeded(i) => AnsAndEvalResult::EvalTimeLimitExceeded(i),
-            RunToEndResult::Ok(i) => AnsAndEvalResult::Ok(i),
+            RunToEndResult::TimeLimitExceeded(k, i) => {
+                AnsAndEvalResult::EvalTimeLimitExceeded(k, i)
+            }
+            RunToEndResult::Ok(i) => {
+                let verdict = EvalVerdict::from_process_resource(&i);
+                AnsAndEvalResult::Ok(i, verdict)
+            }
             RunToEndResult::OutputLimitExceeded(i) => AnsAndEvalResult::EvalOutputLimitExceeded(i),
+            RunToEndResult::RestrictedFunction(k, i) => {
+                AnsAndEvalResult::EvalRestrictedFunction(k, i)
+            }
         }
     }
 }
@@ -591,23 +1452,31 @@ impl From<RunToEndResult> for AnsAndEvalResult {
 pub enum RunAndInteractResult {
     PermissionDenied,
     SettingError,
-    InternalError(String),
+    InternalError(JudgeError),
     CompileError(String),
+    CompileTimeLimitExceeded(String),
+    CompileMemoryLimitExceeded(String),
     RuntimeError(ProcessResource, ProcessResource),
     MemoryLimitExceeded(ProcessResource, ProcessResource),
-    TimeLimitExceeded(ProcessResource, ProcessResource),
+    TimeLimitExceeded(TimeLimitKind, ProcessResource, ProcessResource),
     OutputLimitExceeded(ProcessResource, ProcessResource),
+    RestrictedFunction(i64, ProcessResource, ProcessResource),
     InteractorCompileError(String),
+    InteractorCompileTimeLimitExceeded(String),
+    InteractorCompileMemoryLimitExceeded(String),
     InteractorRuntimeError(ProcessResource, ProcessResource),
     InteractorMemoryLimitExceeded(ProcessResource, ProcessResource),
     InteractorTimeLimitExceeded(ProcessResource, ProcessResource),
     InteractorOutputLimitExceeded(ProcessResource, ProcessResource),
-    Ok(ProcessResource, ProcessResource),
+    InteractorRestrictedFunction(i64, ProcessResource, ProcessResource),
+    Skipped,
+    Ok(ProcessResource, ProcessResource, EvalVerdict),
 }
 
 impl RunAndInteractResult {
     pub fn to_interactor(&self) -> Self {
         match self {
+            RunAndInteractResult::Skipped => RunAndInteractResult::Skipped,
             RunAndInteractResult::SettingError => RunAndInteractResult::SettingError,
             RunAndInteractResult::InternalError(i) => {
                 RunAndInteractResult::InternalError(i.clone())
@@ -615,18 +1484,32 @@ impl RunAndInteractResult {
             RunAndInteractResult::CompileError(i) => {
                 RunAndInteractResult::InteractorCompileError(i.clone())
             }
+            RunAndInteractResult::CompileTimeLimitExceeded(i) => {
+                RunAndInteractResult::InteractorCompileTimeLimitExceeded(i.clone())
+            }
+            RunAndInteractResult::CompileMemoryLimitExceeded(i) => {
+                RunAndInteractResult::InteractorCompileMemoryLimitExceeded(i.clone())
+            }
             RunAndInteractResult::RuntimeError(i, j) => {
                 RunAndInteractResult::InteractorRuntimeError(i.clone(), j.clone())
             }
             RunAndInteractResult::MemoryLimitExceeded(i, j) => {
                 RunAndInteractResult::InteractorMemoryLimitExceeded(i.clone(), j.clone())
             }
-            RunAndInteractResult::TimeLimitExceeded(i, j) => {
+            RunAndInteractResult::TimeLimitExceeded(_, i, j) => {
                 RunAndInteractResult::InteractorTimeLimitExceeded(i.clone(), j.clone())
             }
             RunAndInteractResult::InteractorCompileError(_) => {
                 panic!("RunAndInteractResult::InteractorCompileError(_) is not allowed")
             }
+            RunAndInteractResult::InteractorCompileTimeLimitExceeded(_) => {
+                panic!("RunAndInteractResult::InteractorCompileTimeLimitExceeded(_) is not allowed")
+            }
+            RunAndInteractResult::InteractorCompileMemoryLimitExceeded(_) => {
+                panic!(
+                    "RunAndInteractResult::InteractorCompileMemoryLimitExceeded(_) is not allowed"
+                )
+            }
             RunAndInteractResult::InteractorRuntimeError(_, _) => {
                 panic!("RunAndInteractResult::InteractorRuntimeError(_, _) is not allowed")
             }
@@ -636,8 +1519,8 @@ impl RunAndInteractResult {
             RunAndInteractResult::InteractorTimeLimitExceeded(_, _) => {
                 panic!("RunAndInteractResult::InteractorTimeLimitExceeded(_, _) is not allowed")
             }
-            RunAndInteractResult::Ok(_, _) => {
-                panic!("RunAndInteractResult::Ok(_, _) is not allowed")
+            RunAndInteractResult::Ok(_, _, _) => {
+                panic!("RunAndInteractResult::Ok(_, _, _) is not allowed")
             }
             RunAndInteractResult::PermissionDenied => RunAndInteractResult::PermissionDenied,
             RunAndInteractResult::OutputLimitExceeded(i, j) => {
@@ -646,12 +1529,19 @@ impl RunAndInteractResult {
             RunAndInteractResult::InteractorOutputLimitExceeded(_, _) => {
                 panic!("RunAndInteractResult::InteractorOutputLimitExceeded(_, _) is not allowed")
             }
+            RunAndInteractResult::RestrictedFunction(k, i, j) => {
+                RunAndInteractResult::InteractorRestrictedFunction(*k, i.clone(), j.clone())
+            }
+            RunAndInteractResult::InteractorRestrictedFunction(_, _, _) => {
+                panic!("RunAndInteractResult::InteractorRestrictedFunction(_, _, _) is not allowed")
+            }
         }
     }
 
-    pub fn unwrap(self) -> (ProcessResource, ProcessResource) {
+    pub fn unwrap(self) -> (ProcessResource, ProcessResource, EvalVerdict) {
         match self {
-            RunAndInteractResult::Ok(i, j) => (i, j),
+            RunAndInteractResult::Skipped => panic!("RunAndInteractResult::Skipped is not allowed"),
+            RunAndInteractResult::Ok(i, j, k) => (i, j, k),
             RunAndInteractResult::SettingError => {
                 panic!("RunAndInteractResult::SettingError is not allowed")
             }
@@ -664,6 +1554,14 @@ impl RunAndInteractResult {
             RunAndInteractResult::CompileError(i) => {
                 panic!("RunAndInteractResult::CompileError({}) is not allowed", i)
             }
+            RunAndInteractResult::CompileTimeLimitExceeded(i) => panic!(
+                "RunAndInteractResult::CompileTimeLimitExceeded({}) is not allowed",
+                i
+            ),
+            RunAndInteractResult::CompileMemoryLimitExceeded(i) => panic!(
+                "RunAndInteractResult::CompileMemoryLimitExceeded({}) is not allowed",
+                i
+            ),
             RunAndInteractResult::RuntimeError(i, j) => panic!(
                 "RunAndInteractResult::RuntimeError({},{}) is not allowed",
                 i, j
@@ -672,14 +1570,22 @@ impl RunAndInteractResult {
                 "RunAndInteractResult::MemoryLimitExceeded({},{}) is not allowed",
                 i, j
             ),
-            RunAndInteractResult::TimeLimitExceeded(i, j) => panic!(
-                "RunAndInteractResult::TimeLimitExceeded({},{}) is not allowed",
-                i, j
+            RunAndInteractResult::TimeLimitExceeded(k, i, j) => panic!(
+                "RunAndInteractResult::TimeLimitExceeded({},{},{}) is not allowed",
+                k, i, j
             ),
             RunAndInteractResult::InteractorCompileError(i) => panic!(
                 "RunAndInteractResult::InteractorCompileError({}) is not allowed",
                 i
             ),
+            RunAndInteractResult::InteractorCompileTimeLimitExceeded(i) => panic!(
+                "RunAndInteractResult::InteractorCompileTimeLimitExceeded({}) is not allowed",
+                i
+            ),
+            RunAndInteractResult::InteractorCompileMemoryLimitExceeded(i) => panic!(
+                "RunAndInteractResult::InteractorCompileMemoryLimitExceeded({}) is not allowed",
+                i
+            ),
             RunAndInteractResult::InteractorRuntimeError(i, j) => panic!(
                 "RunAndInteractResult::InteractorRuntimeError({},{}) is not allowed",
                 i, j
@@ -700,12 +1606,29 @@ impl RunAndInteractResult {
                 "RunAndInteractResult::OutputLimitExceeded({},{}) is not allowed",
                 i, j
             ),
+            RunAndInteractResult::RestrictedFunction(k, i, j) => panic!(
+                "RunAndInteractResult::RestrictedFunction({},{},{}) is not allowed",
+                k, i, j
+            ),
+            RunAndInteractResult::InteractorRestrictedFunction(k, i, j) => panic!(
+                "RunAndInteractResult::InteractorRestrictedFunction({},{},{}) is not allowed",
+                k, i, j
+            ),
         }
     }
 
     pub fn is_ok(&self) -> bool {
         match self {
-            RunAndInteractResult::Ok(_, _) => true,
+            RunAndInteractResult::Ok(_, _, _) => true,
+            _ => false,
+        }
+    }
+
+    // Ok only says the interactor judged the exchange, this is whether it
+    // passed.
+    pub fn is_accepted(&self) -> bool {
+        match self {
+            RunAndInteractResult::Ok(_, _, eval_verdict) => eval_verdict.is_accepted(),
             _ => false,
         }
     }
@@ -714,19 +1637,32 @@ impl RunAndInteractResult {
 impl std::fmt::Display for RunAndInteractResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            RunAndInteractResult::Skipped => write!(f, "Skipped"),
             RunAndInteractResult::SettingError => write!(f, "SettingError"),
             RunAndInteractResult::InternalError(i) => write!(f, "InternalError({})", i),
             RunAndInteractResult::CompileError(i) => write!(f, "CompileError({})", i),
+            RunAndInteractResult::CompileTimeLimitExceeded(i) => {
+                write!(f, "CompileTimeLimitExceeded({})", i)
+            }
+            RunAndInteractResult::CompileMemoryLimitExceeded(i) => {
+                write!(f, "CompileMemoryLimitExceeded({})", i)
+            }
             RunAndInteractResult::RuntimeError(i, j) => write!(f, "RuntimeError({},{})", i, j),
             RunAndInteractResult::MemoryLimitExceeded(i, j) => {
                 write!(f, "MemoryLimitExceeded({},{})", i, j)
             }
-            RunAndInteractResult::TimeLimitExceeded(i, j) => {
-                write!(f, "TimeLimitExceeded({},{})", i, j)
+            RunAndInteractResult::TimeLimitExceeded(k, i, j) => {
+                write!(f, "TimeLimitExceeded({},{},{})", k, i, j)
             }
             RunAndInteractResult::InteractorCompileError(i) => {
                 write!(f, "InteractorCompileError({})", i)
             }
+            RunAndInteractResult::InteractorCompileTimeLimitExceeded(i) => {
+                write!(f, "InteractorCompileTimeLimitExceeded({})", i)
+            }
+            RunAndInteractResult::InteractorCompileMemoryLimitExceeded(i) => {
+                write!(f, "InteractorCompileMemoryLimitExceeded({})", i)
+            }
             RunAndInteractResult::InteractorRuntimeError(i, j) => {
                 write!(f, "InteractorRuntimeError({},{})", i, j)
             }
@@ -736,7 +1672,7 @@ impl std::fmt::Display for RunAndInteractResult {
             RunAndInteractResult::InteractorTimeLimitExceeded(i, j) => {
                 write!(f, "InteractorTimeLimitExceeded({},{})", i, j)
             }
-            RunAndInteractResult::Ok(i, j) => write!(f, "Ok({}, {})", i, j),
+            RunAndInteractResult::Ok(i, j, k) => write!(f, "Ok({}, {}, {})", i, j, k),
             RunAndInteractResult::PermissionDenied => write!(f, "PermissionDenied"),
             RunAndInteractResult::InteractorOutputLimitExceeded(i, j) => {
                 write!(f, "InteractorOutputLimitExceeded({},{})", i, j)
@@ -744,6 +1680,12 @@ impl std::fmt::Display for RunAndInteractResult {
             RunAndInteractResult::OutputLimitExceeded(i, j) => {
                 write!(f, "OutputLimitExceeded({},{})", i, j)
             }
+            RunAndInteractResult::RestrictedFunction(k, i, j) => {
+                write!(f, "RestrictedFunction({},{},{})", k, i, j)
+            }
+            RunAndInteractResult::InteractorRestrictedFunction(k, i, j) => {
+                write!(f, "InteractorRestrictedFunction({},{},{})", k, i, j)
+            }
         }
     }
 }
@@ -754,6 +1696,12 @@ impl From<CompileResult> for RunAndInteractResult {
             CompileResult::SettingError => RunAndInteractResult::SettingError,
             CompileResult::InternalError(i) => RunAndInteractResult::InternalError(i),
             CompileResult::CompileError(i) => RunAndInteractResult::CompileError(i),
+            CompileResult::CompileTimeLimitExceeded(i) => {
+                RunAndInteractResult::CompileTimeLimitExceeded(i)
+            }
+            CompileResult::CompileMemoryLimitExceeded(i) => {
+                RunAndInteractResult::CompileMemoryLimitExceeded(i)
+            }
             CompileResult::Ok(_) => panic!(
                 "From<CompileResult> for RunAndInteractResult: CompileResult::Ok(_) is not allowed"
             ),
@@ -765,6 +1713,7 @@ impl From<InitExeResourceResult> for RunAndInteractResult {
     fn from(i: InitExeResourceResult) -> Self {
         match i {
             InitExeResourceResult::PermissionDenied => RunAndInteractResult::PermissionDenied,
+            InitExeResourceResult::SettingError => RunAndInteractResult::SettingError,
             InitExeResourceResult::InternalError(i) => RunAndInteractResult::InternalError(i),
             InitExeResourceResult::Ok(_) => panic!("From<InitExeResourceResult> for RunAndInteractResult: InitExeResourceResult::Ok(_) is not allowed"),
         }
@@ -779,8 +1728,8 @@ impl From<RunWithInteractorResult> for RunAndInteractResult {
             RunWithInteractorResult::MemoryLimitExceeded(i, j) => {
                 RunAndInteractResult::MemoryLimitExceeded(i, j)
             }
-            RunWithInteractorResult::TimeLimitExceeded(i, j) => {
-                RunAndInteractResult::TimeLimitExceeded(i, j)
+            RunWithInteractorResult::TimeLimitExceeded(k, i, j) => {
+                RunAndInteractResult::TimeLimitExceeded(k, i, j)
             }
             RunWithInteractorResult::InteractorRuntimeError(i, j) => {
                 RunAndInteractResult::InteractorRuntimeError(i, j)
@@ -791,13 +1740,741 @@ impl From<RunWithInteractorResult> for RunAndInteractResult {
             RunWithInteractorResult::InteractorTimeLimitExceeded(i, j) => {
                 RunAndInteractResult::InteractorTimeLimitExceeded(i, j)
             }
-            RunWithInteractorResult::Ok(i, j) => RunAndInteractResult::Ok(i, j),
+            RunWithInteractorResult::Ok(i, j, k) => RunAndInteractResult::Ok(i, j, k),
             RunWithInteractorResult::InteractorOutputLimitExceeded(i, j) => {
                 RunAndInteractResult::InteractorOutputLimitExceeded(i, j)
             }
             RunWithInteractorResult::OutputLimitExceeded(i, j) => {
                 RunAndInteractResult::OutputLimitExceeded(i, j)
             }
+            RunWithInteractorResult::RestrictedFunction(k, i, j) => {
+                RunAndInteractResult::RestrictedFunction(k, i, j)
+            }
+            RunWithInteractorResult::InteractorRestrictedFunction(k, i, j) => {
+                RunAndInteractResult::InteractorRestrictedFunction(k, i, j)
+            }
+        }
+    }
+}
+
+// As with RunWithManagerResult, the instance fields point at the first
+// instance that failed.
+#[derive(Debug, Clone)]
+pub enum RunAndManageResult {
+    PermissionDenied,
+    SettingError,
+    InternalError(JudgeError),
+    CompileError(String),
+    CompileTimeLimitExceeded(String),
+    CompileMemoryLimitExceeded(String),
+    RuntimeError(usize, InstancesResource, ProcessResource),
+    MemoryLimitExceeded(usize, InstancesResource, ProcessResource),
+    TimeLimitExceeded(TimeLimitKind, usize, InstancesResource, ProcessResource),
+    OutputLimitExceeded(usize, InstancesResource, ProcessResource),
+    RestrictedFunction(i64, usize, InstancesResource, ProcessResource),
+    ManagerCompileError(String),
+    ManagerCompileTimeLimitExceeded(String),
+    ManagerCompileMemoryLimitExceeded(String),
+    ManagerRuntimeError(InstancesResource, ProcessResource),
+    ManagerMemoryLimitExceeded(InstancesResource, ProcessResource),
+    ManagerTimeLimitExceeded(InstancesResource, ProcessResource),
+    ManagerOutputLimitExceeded(InstancesResource, ProcessResource),
+    ManagerRestrictedFunction(i64, InstancesResource, ProcessResource),
+    Skipped,
+    Ok(InstancesResource, ProcessResource, EvalVerdict),
+}
+
+impl RunAndManageResult {
+    // Only the results of compiling and setting up a program come before the
+    // run, so only those can be turned into the manager's.
+    pub fn to_manager(&self) -> Self {
+        match self {
+            RunAndManageResult::Skipped => RunAndManageResult::Skipped,
+            RunAndManageResult::PermissionDenied => RunAndManageResult::PermissionDenied,
+            RunAndManageResult::SettingError => RunAndManageResult::SettingError,
+            RunAndManageResult::InternalError(i) => RunAndManageResult::InternalError(i.clone()),
+            RunAndManageResult::CompileError(i) => {
+                RunAndManageResult::ManagerCompileError(i.clone())
+            }
+            RunAndManageResult::CompileTimeLimitExceeded(i) => {
+                RunAndManageResult::ManagerCompileTimeLimitExceeded(i.clone())
+            }
+            RunAndManageResult::CompileMemoryLimitExceeded(i) => {
+                RunAndManageResult::ManagerCompileMemoryLimitExceeded(i.clone())
+            }
+            i => panic!("RunAndManageResult::to_manager({}) is not allowed", i),
+        }
+    }
+
+    pub fn unwrap(self) -> (InstancesResource, ProcessResource, EvalVerdict) {
+        match self {
+            RunAndManageResult::Ok(i, j, k) => (i, j, k),
+            RunAndManageResult::Skipped => panic!("RunAndManageResult::Skipped is not allowed"),
+            RunAndManageResult::PermissionDenied => {
+                panic!("RunAndManageResult::PermissionDenied is not allowed")
+            }
+            RunAndManageResult::SettingError => {
+                panic!("RunAndManageResult::SettingError is not allowed")
+            }
+            RunAndManageResult::InternalError(i) => {
+                panic!("RunAndManageResult::InternalError({}) is not allowed", i)
+            }
+            RunAndManageResult::CompileError(i) => {
+                panic!("RunAndManageResult::CompileError({}) is not allowed", i)
+            }
+            RunAndManageResult::CompileTimeLimitExceeded(i) => panic!(
+                "RunAndManageResult::CompileTimeLimitExceeded({}) is not allowed",
+                i
+            ),
+            RunAndManageResult::CompileMemoryLimitExceeded(i) => panic!(
+                "RunAndManageResult::CompileMemoryLimitExceeded({}) is not allowed",
+                i
+            ),
+            RunAndManageResult::RuntimeError(i, _, j) => panic!(
+                "RunAndManageResult::RuntimeError({},{}) is not allowed",
+                i, j
+            ),
+            RunAndManageResult::MemoryLimitExceeded(i, _, j) => panic!(
+                "RunAndManageResult::MemoryLimitExceeded({},{}) is not allowed",
+                i, j
+            ),
+            RunAndManageResult::TimeLimitExceeded(k, i, _, j) => panic!(
+                "RunAndManageResult::TimeLimitExceeded({},{},{}) is not allowed",
+                k, i, j
+            ),
+            RunAndManageResult::OutputLimitExceeded(i, _, j) => panic!(
+                "RunAndManageResult::OutputLimitExceeded({},{}) is not allowed",
+                i, j
+            ),
+            RunAndManageResult::RestrictedFunction(k, i, _, j) => panic!(
+                "RunAndManageResult::RestrictedFunction({},{},{}) is not allowed",
+                k, i, j
+            ),
+            RunAndManageResult::ManagerCompileError(i) => panic!(
+                "RunAndManageResult::ManagerCompileError({}) is not allowed",
+                i
+            ),
+            RunAndManageResult::ManagerCompileTimeLimitExceeded(i) => panic!(
+                "RunAndManageResult::ManagerCompileTimeLimitExceeded({}) is not allowed",
+                i
+            ),
+            RunAndManageResult::ManagerCompileMemoryLimitExceeded(i) => panic!(
+                "RunAndManageResult::ManagerCompileMemoryLimitExceeded({}) is not allowed",
+                i
+            ),
+            RunAndManageResult::ManagerRuntimeError(_, j) => panic!(
+                "RunAndManageResult::ManagerRuntimeError({}) is not allowed",
+                j
+            ),
+            RunAndManageResult::ManagerMemoryLimitExceeded(_, j) => panic!(
+                "RunAndManageResult::ManagerMemoryLimitExceeded({}) is not allowed",
+                j
+            ),
+            RunAndManageResult::ManagerTimeLimitExceeded(_, j) => panic!(
+                "RunAndManageResult::ManagerTimeLimitExceeded({}) is not allowed",
+                j
+            ),
+            RunAndManageResult::ManagerOutputLimitExceeded(_, j) => panic!(
+                "RunAndManageResult::ManagerOutputLimitExceeded({}) is not allowed",
+                j
+            ),
+            RunAndManageResult::ManagerRestrictedFunction(k, _, j) => panic!(
+                "RunAndManageResult::ManagerRestrictedFunction({},{}) is not allowed",
+                k, j
+            ),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        matches!(self, RunAndManageResult::Ok(_, _, _))
+    }
+
+    pub fn is_accepted(&self) -> bool {
+        match self {
+            RunAndManageResult::Ok(_, _, eval_verdict) => eval_verdict.is_accepted(),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for RunAndManageResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunAndManageResult::Skipped => write!(f, "Skipped"),
+            RunAndManageResult::PermissionDenied => write!(f, "PermissionDenied"),
+            RunAndManageResult::SettingError => write!(f, "SettingError"),
+            RunAndManageResult::InternalError(i) => write!(f, "InternalError({})", i),
+            RunAndManageResult::CompileError(i) => write!(f, "CompileError({})", i),
+            RunAndManageResult::CompileTimeLimitExceeded(i) => {
+                write!(f, "CompileTimeLimitExceeded({})", i)
+            }
+            RunAndManageResult::CompileMemoryLimitExceeded(i) => {
+                write!(f, "CompileMemoryLimitExceeded({})", i)
+            }
+            RunAndManageResult::RuntimeError(i, _, j) => write!(f, "RuntimeError({},{})", i, j),
+            RunAndManageResult::MemoryLimitExceeded(i, _, j) => {
+                write!(f, "MemoryLimitExceeded({},{})", i, j)
+            }
+            RunAndManageResult::TimeLimitExceeded(k, i, _, j) => {
+                write!(f, "TimeLimitExceeded({},{},{})", k, i, j)
+            }
+            RunAndManageResult::OutputLimitExceeded(i, _, j) => {
+                write!(f, "OutputLimitExceeded({},{})", i, j)
+            }
+            RunAndManageResult::RestrictedFunction(k, i, _, j) => {
+                write!(f, "RestrictedFunction({},{},{})", k, i, j)
+            }
+            RunAndManageResult::ManagerCompileError(i) => write!(f, "ManagerCompileError({})", i),
+            RunAndManageResult::ManagerCompileTimeLimitExceeded(i) => {
+                write!(f, "ManagerCompileTimeLimitExceeded({})", i)
+            }
+            RunAndManageResult::ManagerCompileMemoryLimitExceeded(i) => {
+                write!(f, "ManagerCompileMemoryLimitExceeded({})", i)
+            }
+            RunAndManageResult::ManagerRuntimeError(_, j) => {
+                write!(f, "ManagerRuntimeError({})", j)
+            }
+            RunAndManageResult::ManagerMemoryLimitExceeded(_, j) => {
+                write!(f, "ManagerMemoryLimitExceeded({})", j)
+            }
+            RunAndManageResult::ManagerTimeLimitExceeded(_, j) => {
+                write!(f, "ManagerTimeLimitExceeded({})", j)
+            }
+            RunAndManageResult::ManagerOutputLimitExceeded(_, j) => {
+                write!(f, "ManagerOutputLimitExceeded({})", j)
+            }
+            RunAndManageResult::ManagerRestrictedFunction(k, _, j) => {
+                write!(f, "ManagerRestrictedFunction({},{})", k, j)
+            }
+            RunAndManageResult::Ok(_, j, k) => write!(f, "Ok({}, {})", j, k),
         }
     }
 }
+
+impl From<CompileResult> for RunAndManageResult {
+    fn from(i: CompileResult) -> Self {
+        match i {
+            CompileResult::SettingError => RunAndManageResult::SettingError,
+            CompileResult::InternalError(i) => RunAndManageResult::InternalError(i),
+            CompileResult::CompileError(i) => RunAndManageResult::CompileError(i),
+            CompileResult::CompileTimeLimitExceeded(i) => {
+                RunAndManageResult::CompileTimeLimitExceeded(i)
+            }
+            CompileResult::CompileMemoryLimitExceeded(i) => {
+                RunAndManageResult::CompileMemoryLimitExceeded(i)
+            }
+            CompileResult::Ok(_) => panic!(
+                "From<CompileResult> for RunAndManageResult: CompileResult::Ok(_) is not allowed"
+            ),
+        }
+    }
+}
+
+impl From<InitExeResourceResult> for RunAndManageResult {
+    fn from(i: InitExeResourceResult) -> Self {
+        match i {
+            InitExeResourceResult::PermissionDenied => RunAndManageResult::PermissionDenied,
+            InitExeResourceResult::SettingError => RunAndManageResult::SettingError,
+            InitExeResourceResult::InternalError(i) => RunAndManageResult::InternalError(i),
+            InitExeResourceResult::Ok(_) => panic!("From<InitExeResourceResult> for RunAndManageResult: InitExeResourceResult::Ok(_) is not allowed"),
+        }
+    }
+}
+
+impl From<RunWithManagerResult> for RunAndManageResult {
+    fn from(i: RunWithManagerResult) -> Self {
+        match i {
+            RunWithManagerResult::InternalError(i) => RunAndManageResult::InternalError(i),
+            RunWithManagerResult::RuntimeError(i, j, k) => {
+                RunAndManageResult::RuntimeError(i, j, k)
+            }
+            RunWithManagerResult::MemoryLimitExceeded(i, j, k) => {
+                RunAndManageResult::MemoryLimitExceeded(i, j, k)
+            }
+            RunWithManagerResult::TimeLimitExceeded(l, i, j, k) => {
+                RunAndManageResult::TimeLimitExceeded(l, i, j, k)
+            }
+            RunWithManagerResult::OutputLimitExceeded(i, j, k) => {
+                RunAndManageResult::OutputLimitExceeded(i, j, k)
+            }
+            RunWithManagerResult::RestrictedFunction(l, i, j, k) => {
+                RunAndManageResult::RestrictedFunction(l, i, j, k)
+            }
+            RunWithManagerResult::ManagerRuntimeError(i, j) => {
+                RunAndManageResult::ManagerRuntimeError(i, j)
+            }
+            RunWithManagerResult::ManagerMemoryLimitExceeded(i, j) => {
+                RunAndManageResult::ManagerMemoryLimitExceeded(i, j)
+            }
+            RunWithManagerResult::ManagerTimeLimitExceeded(i, j) => {
+                RunAndManageResult::ManagerTimeLimitExceeded(i, j)
+            }
+            RunWithManagerResult::ManagerOutputLimitExceeded(i, j) => {
+                RunAndManageResult::ManagerOutputLimitExceeded(i, j)
+            }
+            RunWithManagerResult::ManagerRestrictedFunction(k, i, j) => {
+                RunAndManageResult::ManagerRestrictedFunction(k, i, j)
+            }
+            RunWithManagerResult::Ok(i, j, k) => RunAndManageResult::Ok(i, j, k),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StressProgram {
+    Generator,
+    Reference,
+    Candidate,
+    Checker,
+}
+
+impl std::fmt::Display for StressProgram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StressProgram::Generator => write!(f, "Generator"),
+            StressProgram::Reference => write!(f, "Reference"),
+            StressProgram::Candidate => write!(f, "Candidate"),
+            StressProgram::Checker => write!(f, "Checker"),
+        }
+    }
+}
+
+// A seed the candidate failed on. The candidate's run is Ok when it ran to
+// the end, the checker's verdict then being set.
+#[serde_with::serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StressFailure {
+    pub seed: u64,
+    #[cfg_attr(feature = "base64", serde_as(as = "serde_with::base64::Base64"))]
+    pub input: Vec<u8>,
+    #[cfg_attr(feature = "base64", serde_as(as = "serde_with::base64::Base64"))]
+    pub reference_output: Vec<u8>,
+    pub candidate: OnlyRunResult,
+    pub eval_verdict: Option<EvalVerdict>,
+}
+
+impl StressFailure {
+    pub fn candidate_output(&self) -> &[u8] {
+        match &self.candidate {
+            OnlyRunResult::Ok(i)
+            | OnlyRunResult::RuntimeError(i)
+            | OnlyRunResult::MemoryLimitExceeded(i)
+            | OnlyRunResult::TimeLimitExceeded(_, i)
+            | OnlyRunResult::OutputLimitExceeded(i)
+            | OnlyRunResult::RestrictedFunction(_, i) => &i.stdout,
+            _ => &[],
+        }
+    }
+
+    // The checker's message, or how the candidate failed to run to the end.
+    pub fn message(&self) -> String {
+        match &self.eval_verdict {
+            Some(eval_verdict) => eval_verdict.message.clone(),
+            None => self.candidate.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for StressFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.eval_verdict {
+            None => write!(f, "seed {}: {}", self.seed, self.candidate),
+            Some(eval_verdict) => write!(f, "seed {}: {}", self.seed, eval_verdict),
+        }
+    }
+}
+
+// The seed is None when the program failed before any seed ran, as when it
+// does not compile.
+#[derive(Debug, Clone)]
+pub enum StressResult {
+    ProgramError(StressProgram, Option<u64>, OnlyRunResult),
+    // How many seeds were tried.
+    Passed(usize),
+    // How many seeds were tried, and the failure with the shortest input.
+    Failed(usize, StressFailure),
+}
+
+impl StressResult {
+    pub fn unwrap(self) -> StressFailure {
+        match self {
+            StressResult::ProgramError(k, _, i) => {
+                panic!("StressResult::ProgramError({}, {}) is not allowed", k, i)
+            }
+            StressResult::Passed(i) => panic!("StressResult::Passed({}) is not allowed", i),
+            StressResult::Failed(_, i) => i,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        matches!(self, StressResult::Passed(_))
+    }
+}
+
+impl std::fmt::Display for StressResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StressResult::ProgramError(k, None, i) => write!(f, "ProgramError({},{})", k, i),
+            StressResult::ProgramError(k, Some(j), i) => {
+                write!(f, "ProgramError({},seed {},{})", k, j, i)
+            }
+            StressResult::Passed(i) => write!(f, "Passed({})", i),
+            StressResult::Failed(i, j) => write!(f, "Failed({},{})", i, j),
+        }
+    }
+}
+
+// Every verdict is written as one flat object, e.g.
+// {"verdict": "TimeLimitExceeded", "time_limit_kind": "CpuTime", "tested": {...}},
+// so that the backend can dispatch on "verdict" without knowing the variant shapes.
+#[derive(Serialize)]
+struct VerdictRef<'a> {
+    verdict: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_limit_kind: Option<&'a TimeLimitKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    syscall: Option<&'a i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<&'a String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a JudgeError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exe_code: Option<&'a ExeCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tested: Option<&'a ProcessResource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    eval: Option<&'a ProcessResource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    eval_verdict: Option<&'a EvalVerdict>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interactor: Option<&'a ProcessResource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<&'a usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instances: Option<&'a InstancesResource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manager: Option<&'a ProcessResource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stages: Option<&'a Vec<StageResource>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    program: Option<&'a StressProgram>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<&'a Option<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run: Option<&'a OnlyRunResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tried: Option<&'a usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<&'a StressFailure>,
+}
+
+impl<'a> VerdictRef<'a> {
+    fn new(verdict: &'a str) -> Self {
+        VerdictRef {
+            verdict,
+            time_limit_kind: None,
+            syscall: None,
+            output: None,
+            error: None,
+            exe_code: None,
+            tested: None,
+            eval: None,
+            eval_verdict: None,
+            interactor: None,
+            instance: None,
+            instances: None,
+            manager: None,
+            stages: None,
+            program: None,
+            seed: None,
+            run: None,
+            tried: None,
+            failure: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Verdict {
+    verdict: String,
+    #[serde(default)]
+    time_limit_kind: Option<TimeLimitKind>,
+    #[serde(default)]
+    syscall: Option<i64>,
+    #[serde(default)]
+    output: Option<String>,
+    #[serde(default)]
+    error: Option<JudgeError>,
+    #[serde(default)]
+    exe_code: Option<Box<ExeCode>>,
+    #[serde(default)]
+    tested: Option<ProcessResource>,
+    #[serde(default)]
+    eval: Option<ProcessResource>,
+    #[serde(default)]
+    eval_verdict: Option<EvalVerdict>,
+    #[serde(default)]
+    interactor: Option<ProcessResource>,
+    #[serde(default)]
+    instance: Option<usize>,
+    #[serde(default)]
+    instances: Option<InstancesResource>,
+    #[serde(default)]
+    manager: Option<ProcessResource>,
+    #[serde(default)]
+    stages: Option<Vec<StageResource>>,
+    #[serde(default)]
+    program: Option<StressProgram>,
+    // A seed is written as null when there is none, which still counts as
+    // the field being there.
+    #[serde(default, deserialize_with = "present")]
+    seed: Option<Option<u64>>,
+    #[serde(default)]
+    run: Option<OnlyRunResult>,
+    #[serde(default)]
+    tried: Option<usize>,
+    #[serde(default)]
+    failure: Option<StressFailure>,
+}
+
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+fn take_field<T>(field: &mut Option<T>, verdict: &str, name: &str) -> Result<T, String> {
+    field
+        .take()
+        .ok_or_else(|| format!("{} requires the {} field", verdict, name))
+}
+
+// Writes Serialize and Deserialize for a result enum from one list of its
+// variants, each naming the Verdict field that holds each of its values in
+// order, so that the two directions cannot drift apart.
+macro_rules! verdict_serde {
+    ($result:ident { $($variant:ident $(($($field:ident),+))?,)+ }) => {
+        impl Serialize for $result {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                match self {
+                    $($result::$variant $(($($field),+))? => VerdictRef {
+                        $($($field: Some($field),)+)?
+                        ..VerdictRef::new(stringify!($variant))
+                    },)+
+                }
+                .serialize(serializer)
+            }
+        }
+
+        impl $result {
+            fn from_verdict(verdict: &mut Verdict) -> Result<Self, String> {
+                Ok(match verdict.verdict.as_str() {
+                    $(stringify!($variant) => $result::$variant $(($(take_field(
+                        &mut verdict.$field,
+                        &verdict.verdict,
+                        stringify!($field),
+                    )?),+))?,)+
+                    _ => return Err(format!("unknown verdict {}", verdict.verdict)),
+                })
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $result {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                let mut verdict = Verdict::deserialize(deserializer)?;
+                Self::from_verdict(&mut verdict).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+verdict_serde!(CompileResult {
+    SettingError,
+    InternalError(error),
+    CompileError(output),
+    CompileTimeLimitExceeded(output),
+    CompileMemoryLimitExceeded(output),
+    Ok(exe_code),
+});
+
+verdict_serde!(RunToEndResult {
+    InternalError(error),
+    RuntimeError(tested),
+    MemoryLimitExceeded(tested),
+    TimeLimitExceeded(time_limit_kind, tested),
+    OutputLimitExceeded(tested),
+    RestrictedFunction(syscall, tested),
+    Ok(tested),
+});
+
+verdict_serde!(OnlyRunResult {
+    Skipped,
+    PermissionDenied,
+    SettingError,
+    CompileError(output),
+    CompileTimeLimitExceeded(output),
+    CompileMemoryLimitExceeded(output),
+    InternalError(error),
+    RuntimeError(tested),
+    MemoryLimitExceeded(tested),
+    TimeLimitExceeded(time_limit_kind, tested),
+    OutputLimitExceeded(tested),
+    RestrictedFunction(syscall, tested),
+    Ok(tested),
+});
+
+verdict_serde!(RunInStagesResult {
+    Skipped,
+    PermissionDenied,
+    SettingError,
+    InternalError(error),
+    CompileError(output),
+    CompileTimeLimitExceeded(output),
+    CompileMemoryLimitExceeded(output),
+    TransformerCompileError(output),
+    TransformerCompileTimeLimitExceeded(output),
+    TransformerCompileMemoryLimitExceeded(output),
+    RuntimeError(stages),
+    MemoryLimitExceeded(stages),
+    TimeLimitExceeded(time_limit_kind, stages),
+    OutputLimitExceeded(stages),
+    RestrictedFunction(syscall, stages),
+    TransformerRuntimeError(stages),
+    TransformerMemoryLimitExceeded(stages),
+    TransformerTimeLimitExceeded(time_limit_kind, stages),
+    TransformerOutputLimitExceeded(stages),
+    TransformerRestrictedFunction(syscall, stages),
+    Ok(stages),
+});
+
+verdict_serde!(RunWithInteractorResult {
+    InternalError(error),
+    RuntimeError(tested, interactor),
+    MemoryLimitExceeded(tested, interactor),
+    TimeLimitExceeded(time_limit_kind, tested, interactor),
+    OutputLimitExceeded(tested, interactor),
+    RestrictedFunction(syscall, tested, interactor),
+    InteractorRuntimeError(tested, interactor),
+    InteractorMemoryLimitExceeded(tested, interactor),
+    InteractorTimeLimitExceeded(tested, interactor),
+    InteractorOutputLimitExceeded(tested, interactor),
+    InteractorRestrictedFunction(syscall, tested, interactor),
+    Ok(tested, interactor, eval_verdict),
+});
+
+verdict_serde!(RunWithManagerResult {
+    InternalError(error),
+    RuntimeError(instance, instances, manager),
+    MemoryLimitExceeded(instance, instances, manager),
+    TimeLimitExceeded(time_limit_kind, instance, instances, manager),
+    OutputLimitExceeded(instance, instances, manager),
+    RestrictedFunction(syscall, instance, instances, manager),
+    ManagerRuntimeError(instances, manager),
+    ManagerMemoryLimitExceeded(instances, manager),
+    ManagerTimeLimitExceeded(instances, manager),
+    ManagerOutputLimitExceeded(instances, manager),
+    ManagerRestrictedFunction(syscall, instances, manager),
+    Ok(instances, manager, eval_verdict),
+});
+
+verdict_serde!(RunAndInteractResult {
+    Skipped,
+    PermissionDenied,
+    SettingError,
+    InternalError(error),
+    CompileError(output),
+    CompileTimeLimitExceeded(output),
+    CompileMemoryLimitExceeded(output),
+    RuntimeError(tested, interactor),
+    MemoryLimitExceeded(tested, interactor),
+    TimeLimitExceeded(time_limit_kind, tested, interactor),
+    OutputLimitExceeded(tested, interactor),
+    RestrictedFunction(syscall, tested, interactor),
+    InteractorCompileError(output),
+    InteractorCompileTimeLimitExceeded(output),
+    InteractorCompileMemoryLimitExceeded(output),
+    InteractorRuntimeError(tested, interactor),
+    InteractorMemoryLimitExceeded(tested, interactor),
+    InteractorTimeLimitExceeded(tested, interactor),
+    InteractorOutputLimitExceeded(tested, interactor),
+    InteractorRestrictedFunction(syscall, tested, interactor),
+    Ok(tested, interactor, eval_verdict),
+});
+
+verdict_serde!(RunAndManageResult {
+    Skipped,
+    PermissionDenied,
+    SettingError,
+    InternalError(error),
+    CompileError(output),
+    CompileTimeLimitExceeded(output),
+    CompileMemoryLimitExceeded(output),
+    RuntimeError(instance, instances, manager),
+    MemoryLimitExceeded(instance, instances, manager),
+    TimeLimitExceeded(time_limit_kind, instance, instances, manager),
+    OutputLimitExceeded(instance, instances, manager),
+    RestrictedFunction(syscall, instance, instances, manager),
+    ManagerCompileError(output),
+    ManagerCompileTimeLimitExceeded(output),
+    ManagerCompileMemoryLimitExceeded(output),
+    ManagerRuntimeError(instances, manager),
+    ManagerMemoryLimitExceeded(instances, manager),
+    ManagerTimeLimitExceeded(instances, manager),
+    ManagerOutputLimitExceeded(instances, manager),
+    ManagerRestrictedFunction(syscall, instances, manager),
+    Ok(instances, manager, eval_verdict),
+});
+
+verdict_serde!(RunAndEvalResult {
+    Skipped,
+    SettingError,
+    PermissionDenied,
+    InternalError(error),
+    CompileError(output),
+    CompileTimeLimitExceeded(output),
+    CompileMemoryLimitExceeded(output),
+    RuntimeError(tested, eval),
+    MemoryLimitExceeded(tested, eval),
+    TimeLimitExceeded(time_limit_kind, tested, eval),
+    OutputLimitExceeded(tested, eval),
+    RestrictedFunction(syscall, tested, eval),
+    EvalCompileError(output),
+    EvalCompileTimeLimitExceeded(output),
+    EvalCompileMemoryLimitExceeded(output),
+    EvalRuntimeError(tested, eval),
+    EvalMemoryLimitExceeded(tested, eval),
+    EvalTimeLimitExceeded(time_limit_kind, tested, eval),
+    EvalOutputLimitExceeded(tested, eval),
+    EvalRestrictedFunction(syscall, tested, eval),
+    Ok(tested, eval, eval_verdict),
+});
+
+verdict_serde!(AnsAndEvalResult {
+    Skipped,
+    PermissionDenied,
+    SettingError,
+    InternalError(error),
+    EvalCompileError(output),
+    EvalCompileTimeLimitExceeded(output),
+    EvalCompileMemoryLimitExceeded(output),
+    EvalRuntimeError(eval),
+    EvalMemoryLimitExceeded(eval),
+    EvalTimeLimitExceeded(time_limit_kind, eval),
+    EvalOutputLimitExceeded(eval),
+    EvalRestrictedFunction(syscall, eval),
+    Ok(eval, eval_verdict),
+});
+
+verdict_serde!(StressResult {
+    ProgramError(program, seed, run),
+    Passed(tried),
+    Failed(tried, failure),
+});
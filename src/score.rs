@@ -0,0 +1,240 @@
+use crate::{error::JudgeError, result::RunAndEvalResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Aggregation {
+    // The subtask is worth as much as its weakest test, the usual IOI rule.
+    #[default]
+    Min,
+    // Every test contributes its share of the points on its own.
+    Sum,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Subtask {
+    pub name: String,
+    pub points: f64,
+    pub tests: Vec<usize>,
+    #[serde(default)]
+    pub test_weights: Vec<f64>,
+    #[serde(default)]
+    pub aggregation: Aggregation,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProblemScoring {
+    pub subtasks: Vec<Subtask>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestScore {
+    pub test: usize,
+    pub ratio: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtaskScore {
+    pub name: String,
+    pub points: f64,
+    pub score: f64,
+    pub tests: Vec<TestScore>,
+    pub blocked_by: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreReport {
+    pub score: f64,
+    pub points: f64,
+    pub subtasks: Vec<SubtaskScore>,
+}
+
+impl ProblemScoring {
+    pub fn self_check(&self, test_count: usize) -> Result<(), JudgeError> {
+        let mut names = HashMap::new();
+        for (index, subtask) in self.subtasks.iter().enumerate() {
+            if names.insert(subtask.name.as_str(), index).is_some() {
+                return Err(JudgeError::Setting(format!(
+                    "subtask {} is defined twice",
+                    subtask.name
+                )));
+            }
+            if subtask.points < 0.0 {
+                return Err(JudgeError::Setting(format!(
+                    "subtask {}'s points is negative",
+                    subtask.name
+                )));
+            }
+            if subtask.tests.is_empty() {
+                return Err(JudgeError::Setting(format!(
+                    "subtask {} has no tests",
+                    subtask.name
+                )));
+            }
+            if let Some(test) = subtask.tests.iter().find(|test| **test >= test_count) {
+                return Err(JudgeError::Setting(format!(
+                    "subtask {}'s test {} does not exist",
+                    subtask.name, test
+                )));
+            }
+            if !subtask.test_weights.is_empty() && subtask.test_weights.len() != subtask.tests.len()
+            {
+                return Err(JudgeError::Setting(format!(
+                    "subtask {}'s test_weights does not match its tests",
+                    subtask.name
+                )));
+            }
+            if subtask.test_weights.iter().any(|weight| *weight < 0.0) {
+                return Err(JudgeError::Setting(format!(
+                    "subtask {}'s test_weights is negative",
+                    subtask.name
+                )));
+            }
+        }
+        for subtask in &self.subtasks {
+            for dependency in &subtask.dependencies {
+                if !names.contains_key(dependency.as_str()) {
+                    return Err(JudgeError::Setting(format!(
+                        "subtask {} depends on unknown subtask {}",
+                        subtask.name, dependency
+                    )));
+                }
+            }
+        }
+        // Dependencies must form a DAG, otherwise no subtask in a cycle could
+        // ever be scored.
+        let mut states = vec![0u8; self.subtasks.len()];
+        for index in 0..self.subtasks.len() {
+            self.check_cycle(index, &names, &mut states)?;
+        }
+        Ok(())
+    }
+
+    fn check_cycle(
+        &self,
+        index: usize,
+        names: &HashMap<&str, usize>,
+        states: &mut Vec<u8>,
+    ) -> Result<(), JudgeError> {
+        match states[index] {
+            1 => {
+                return Err(JudgeError::Setting(format!(
+                    "subtask {} depends on itself",
+                    self.subtasks[index].name
+                )));
+            }
+            2 => return Ok(()),
+            _ => {}
+        }
+        states[index] = 1;
+        for dependency in &self.subtasks[index].dependencies {
+            self.check_cycle(names[dependency.as_str()], names, states)?;
+        }
+        states[index] = 2;
+        Ok(())
+    }
+
+    // A subtask only scores when every subtask it depends on, directly or
+    // through another dependency, got full points.
+    pub fn score(&self, results: &[RunAndEvalResult]) -> Result<ScoreReport, JudgeError> {
+        self.self_check(results.len())?;
+        let mut subtasks: Vec<SubtaskScore> = self
+            .subtasks
+            .iter()
+            .map(|subtask| {
+                let tests: Vec<TestScore> = subtask
+                    .tests
+                    .iter()
+                    .map(|test| TestScore {
+                        test: *test,
+                        ratio: test_ratio(&results[*test]),
+                    })
+                    .collect();
+                let ratio = match subtask.aggregation {
+                    Aggregation::Min => tests.iter().map(|test| test.ratio).fold(1.0, f64::min),
+                    Aggregation::Sum => {
+                        let weights: Vec<f64> = match subtask.test_weights.is_empty() {
+                            true => vec![1.0; tests.len()],
+                            false => subtask.test_weights.clone(),
+                        };
+                        let total: f64 = weights.iter().sum();
+                        match total > 0.0 {
+                            true => {
+                                tests
+                                    .iter()
+                                    .zip(weights.iter())
+                                    .map(|(test, weight)| test.ratio * weight)
+                                    .sum::<f64>()
+                                    / total
+                            }
+                            false => 0.0,
+                        }
+                    }
+                };
+                SubtaskScore {
+                    name: subtask.name.clone(),
+                    points: subtask.points,
+                    score: subtask.points * ratio,
+                    tests,
+                    blocked_by: vec![],
+                }
+            })
+            .collect();
+        let names: HashMap<&str, usize> = self
+            .subtasks
+            .iter()
+            .enumerate()
+            .map(|(index, subtask)| (subtask.name.as_str(), index))
+            .collect();
+        let is_full: Vec<bool> = subtasks
+            .iter()
+            .map(|subtask| subtask.score >= subtask.points)
+            .collect();
+        for index in 0..subtasks.len() {
+            let mut blocked_by = vec![];
+            let mut pending: Vec<&str> = self.subtasks[index]
+                .dependencies
+                .iter()
+                .map(|dependency| dependency.as_str())
+                .collect();
+            let mut visited = vec![false; subtasks.len()];
+            while let Some(dependency) = pending.pop() {
+                let dependency_index = names[dependency];
+                if visited[dependency_index] {
+                    continue;
+                }
+                visited[dependency_index] = true;
+                if !is_full[dependency_index] {
+                    blocked_by.push(dependency.to_string());
+                }
+                pending.extend(
+                    self.subtasks[dependency_index]
+                        .dependencies
+                        .iter()
+                        .map(|dependency| dependency.as_str()),
+                );
+            }
+            if !blocked_by.is_empty() {
+                blocked_by.sort();
+                subtasks[index].score = 0.0;
+                subtasks[index].blocked_by = blocked_by;
+            }
+        }
+        Ok(ScoreReport {
+            score: subtasks.iter().map(|subtask| subtask.score).sum(),
+            points: subtasks.iter().map(|subtask| subtask.points).sum(),
+            subtasks,
+        })
+    }
+}
+
+// Only a test that ran to the end is judged by the checker, and its verdict
+// carries the ratio of the points earned.
+fn test_ratio(result: &RunAndEvalResult) -> f64 {
+    match result {
+        RunAndEvalResult::Ok(_, _, eval_verdict) => eval_verdict.score,
+        _ => 0.0,
+    }
+}
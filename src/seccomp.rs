@@ -0,0 +1,304 @@
+use crate::error::JudgeError;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_003E);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_00B7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: Option<u32> = None;
+
+const DEFAULT_PROFILE: [i64; 28] = [
+    libc::SYS_ptrace,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_setns,
+    libc::SYS_unshare,
+    libc::SYS_kexec_load,
+    libc::SYS_kexec_file_load,
+    libc::SYS_reboot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_acct,
+    libc::SYS_quotactl,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_sethostname,
+    libc::SYS_setdomainname,
+];
+
+const NETWORK_PROFILE: [i64; 6] = [
+    libc::SYS_socket,
+    libc::SYS_connect,
+    libc::SYS_bind,
+    libc::SYS_listen,
+    libc::SYS_accept,
+    libc::SYS_accept4,
+];
+
+pub fn get_profile_syscalls(profile: &str) -> Result<Vec<i64>, JudgeError> {
+    match profile {
+        "" => Ok(vec![]),
+        "default" => Ok(DEFAULT_PROFILE.to_vec()),
+        "strict" => Ok([DEFAULT_PROFILE.as_slice(), NETWORK_PROFILE.as_slice()].concat()),
+        _ => Err(JudgeError::Setting(format!(
+            "{} is not a valid seccomp profile",
+            profile
+        ))),
+    }
+}
+
+pub struct SeccompFilter {
+    filter: Vec<libc::sock_filter>,
+    parent_socket: OwnedFd,
+    child_socket: OwnedFd,
+}
+
+impl SeccompFilter {
+    pub fn new(syscalls: &Vec<i64>) -> Result<Self, JudgeError> {
+        let audit_arch = match AUDIT_ARCH {
+            None => {
+                return Err(JudgeError::Unsupported(
+                    "seccomp is not supported on this architecture".to_string(),
+                ));
+            }
+            Some(result) => result,
+        };
+        // Blocked syscalls are reported to a listener instead of just killing the
+        // process, because the exit status alone can not tell which syscall it was.
+        let mut filter = unsafe {
+            vec![
+                libc::BPF_STMT((libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16, 4),
+                libc::BPF_JUMP(
+                    (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16,
+                    audit_arch,
+                    1,
+                    0,
+                ),
+                libc::BPF_STMT(
+                    (libc::BPF_RET | libc::BPF_K) as u16,
+                    libc::SECCOMP_RET_KILL_PROCESS,
+                ),
+                libc::BPF_STMT((libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16, 0),
+            ]
+        };
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            filter.push(libc::BPF_JUMP(
+                (libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K) as u16,
+                0x4000_0000,
+                0,
+                1,
+            ));
+            filter.push(libc::BPF_STMT(
+                (libc::BPF_RET | libc::BPF_K) as u16,
+                libc::SECCOMP_RET_KILL_PROCESS,
+            ));
+        }
+        for syscall in syscalls {
+            unsafe {
+                filter.push(libc::BPF_JUMP(
+                    (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16,
+                    *syscall as u32,
+                    0,
+                    1,
+                ));
+                filter.push(libc::BPF_STMT(
+                    (libc::BPF_RET | libc::BPF_K) as u16,
+                    libc::SECCOMP_RET_USER_NOTIF,
+                ));
+            }
+        }
+        filter.push(unsafe {
+            libc::BPF_STMT(
+                (libc::BPF_RET | libc::BPF_K) as u16,
+                libc::SECCOMP_RET_ALLOW,
+            )
+        });
+
+        let mut sockets = [0 as RawFd; 2];
+        let ret = unsafe {
+            libc::socketpair(
+                libc::AF_UNIX,
+                libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+                0,
+                sockets.as_mut_ptr(),
+            )
+        };
+        if ret != 0 {
+            return Err(JudgeError::io(
+                "socketpair()",
+                std::io::Error::last_os_error(),
+            ));
+        }
+        Ok(Self {
+            filter,
+            parent_socket: unsafe { OwnedFd::from_raw_fd(sockets[0]) },
+            child_socket: unsafe { OwnedFd::from_raw_fd(sockets[1]) },
+        })
+    }
+
+    pub fn apply(&self, command: &mut tokio::process::Command) {
+        let filter = self.filter.clone();
+        let child_socket = self.child_socket.as_raw_fd();
+        unsafe {
+            command.pre_exec(move || install_filter(&filter, child_socket));
+        }
+    }
+
+    pub fn monitor(self, pid: i32) -> Result<SeccompMonitor, JudgeError> {
+        drop(self.child_socket);
+        let listener = receive_fd(self.parent_socket.as_raw_fd())?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let stop = stop.clone();
+            tokio::task::spawn_blocking(move || watch_listener(listener, pid, stop))
+        };
+        Ok(SeccompMonitor { stop, handle })
+    }
+}
+
+pub struct SeccompMonitor {
+    stop: Arc<AtomicBool>,
+    handle: tokio::task::JoinHandle<Option<i64>>,
+}
+
+impl SeccompMonitor {
+    pub async fn stop(self) -> Option<i64> {
+        self.stop.store(true, Ordering::SeqCst);
+        self.handle.await.unwrap_or_default()
+    }
+}
+
+fn install_filter(filter: &[libc::sock_filter], socket: RawFd) -> std::io::Result<()> {
+    let prog = libc::sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_ptr() as *mut libc::sock_filter,
+    };
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let listener = libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            libc::SECCOMP_FILTER_FLAG_NEW_LISTENER,
+            &prog as *const libc::sock_fprog,
+        );
+        if listener < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let result = send_fd(socket, listener as RawFd);
+        libc::close(listener as RawFd);
+        result
+    }
+}
+
+fn send_fd(socket: RawFd, fd: RawFd) -> std::io::Result<()> {
+    let mut byte = 0u8;
+    let mut iov = libc::iovec {
+        iov_base: &mut byte as *mut u8 as *mut libc::c_void,
+        iov_len: 1,
+    };
+    let mut control = [0u64; 4];
+    unsafe {
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = libc::CMSG_SPACE(std::mem::size_of::<RawFd>() as u32) as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<RawFd>() as u32) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+        if libc::sendmsg(socket, &msg, 0) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn receive_fd(socket: RawFd) -> Result<OwnedFd, JudgeError> {
+    let mut byte = 0u8;
+    let mut iov = libc::iovec {
+        iov_base: &mut byte as *mut u8 as *mut libc::c_void,
+        iov_len: 1,
+    };
+    let mut control = [0u64; 4];
+    unsafe {
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = std::mem::size_of_val(&control) as _;
+        if libc::recvmsg(
+            socket,
+            &mut msg,
+            libc::MSG_DONTWAIT | libc::MSG_CMSG_CLOEXEC,
+        ) < 0
+        {
+            return Err(JudgeError::io("recvmsg()", std::io::Error::last_os_error()));
+        }
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        if cmsg.is_null()
+            || (*cmsg).cmsg_level != libc::SOL_SOCKET
+            || (*cmsg).cmsg_type != libc::SCM_RIGHTS
+        {
+            return Err(JudgeError::Internal(
+                "seccomp listener was not received".to_string(),
+            ));
+        }
+        let fd = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const RawFd);
+        Ok(OwnedFd::from_raw_fd(fd))
+    }
+}
+
+fn watch_listener(listener: OwnedFd, pid: i32, stop: Arc<AtomicBool>) -> Option<i64> {
+    loop {
+        let mut poll_fd = libc::pollfd {
+            fd: listener.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ret = unsafe { libc::poll(&mut poll_fd, 1, 10) };
+        if ret > 0 && poll_fd.revents & libc::POLLIN != 0 {
+            let mut notif: libc::seccomp_notif = unsafe { std::mem::zeroed() };
+            let ret = unsafe {
+                libc::ioctl(
+                    listener.as_raw_fd(),
+                    libc::SECCOMP_IOCTL_NOTIF_RECV,
+                    &mut notif,
+                )
+            };
+            if ret == 0 {
+                // The blocked syscall is left pending, so the process never
+                // observes a result and is killed together with its caller.
+                unsafe {
+                    libc::kill(notif.pid as i32, libc::SIGKILL);
+                    libc::kill(pid, libc::SIGKILL);
+                }
+                return Some(notif.data.nr as i64);
+            }
+        } else if ret > 0 && poll_fd.revents & libc::POLLHUP != 0 {
+            return None;
+        }
+        if stop.load(Ordering::SeqCst) {
+            return None;
+        }
+    }
+}
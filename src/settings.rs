@@ -1,4 +1,6 @@
 #![allow(non_snake_case)]
+use crate::error::JudgeError;
+use crate::quantity::{MemorySize, TimeSpan};
 use config::Config;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
@@ -6,6 +8,9 @@ use std::collections::HashMap;
 use std::io::Write;
 use std::process::{Command, Stdio};
 
+pub(crate) const DEFAULT_PATH: &str =
+    "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
 #[serde_as]
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct CompileAndExeSetting {
@@ -19,6 +24,28 @@ pub struct CompileAndExeSetting {
     pub exe_files: Vec<String>,
     #[serde(default = "CompileAndExeSetting::language_info_command_default")]
     pub language_info_command: String,
+    #[serde(default = "CompileAndExeSetting::inherit_env_default")]
+    pub inherit_env: bool,
+    #[serde(default = "CompileAndExeSetting::env_default")]
+    pub env: HashMap<String, String>,
+    #[serde(default = "CompileAndExeSetting::compile_env_default")]
+    pub compile_env: HashMap<String, String>,
+    #[serde(default = "CompileAndExeSetting::problem_files_default")]
+    pub problem_files: HashMap<String, String>,
+    #[serde(default = "CompileAndExeSetting::jail_mounts_default")]
+    pub jail_mounts: Vec<String>,
+    #[serde(default = "CompileAndExeSetting::seccomp_profile_default")]
+    pub seccomp_profile: String,
+    #[serde(default = "CompileAndExeSetting::compile_time_limit_default")]
+    pub compile_time_limit: TimeSpan,
+    #[serde(default = "CompileAndExeSetting::compile_memory_limit_default")]
+    pub compile_memory_limit: MemorySize,
+    #[serde(default = "CompileAndExeSetting::compile_process_limit_default")]
+    pub compile_process_limit: usize,
+    #[serde(default = "CompileAndExeSetting::compile_output_limit_default")]
+    pub compile_output_limit: MemorySize,
+    #[serde(default = "CompileAndExeSetting::archive_limit_default")]
+    pub archive_limit: MemorySize,
 }
 
 impl CompileAndExeSetting {
@@ -29,8 +56,80 @@ impl CompileAndExeSetting {
             exe_command: Self::exe_command_default(),
             exe_files: Self::exe_files_default(),
             language_info_command: Self::language_info_command_default(),
+            inherit_env: Self::inherit_env_default(),
+            env: Self::env_default(),
+            compile_env: Self::compile_env_default(),
+            problem_files: Self::problem_files_default(),
+            jail_mounts: Self::jail_mounts_default(),
+            seccomp_profile: Self::seccomp_profile_default(),
+            compile_time_limit: Self::compile_time_limit_default(),
+            compile_memory_limit: Self::compile_memory_limit_default(),
+            compile_process_limit: Self::compile_process_limit_default(),
+            compile_output_limit: Self::compile_output_limit_default(),
+            archive_limit: Self::archive_limit_default(),
+        }
+    }
+    pub fn command_placeholders(&self, memory_limit: MemorySize) -> HashMap<String, String> {
+        let mut result = HashMap::new();
+        result.insert("source".to_string(), self.raw_code.clone());
+        result.insert(
+            "exe".to_string(),
+            self.exe_files.first().cloned().unwrap_or_default(),
+        );
+        result.insert(
+            "memory_limit_bytes".to_string(),
+            memory_limit.as_bytes().to_string(),
+        );
+        result.insert(
+            "memory_limit_kb".to_string(),
+            memory_limit.as_kilobytes().to_string(),
+        );
+        result.insert(
+            "memory_limit_mb".to_string(),
+            memory_limit.as_megabytes().to_string(),
+        );
+        result
+    }
+
+    pub fn get_language_info(&self) -> Result<String, JudgeError> {
+        // The command sees the same environment as the compile command, so it
+        // describes the compiler that compiling would pick up.
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(self.language_info_command.as_str())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if !self.inherit_env {
+            command.env_clear();
+            command.env("PATH", DEFAULT_PATH);
+        }
+        command.envs(&self.compile_env);
+        let child = match command.spawn() {
+            Ok(result) => result,
+            Err(result) => {
+                return Err(JudgeError::spawn("language_info_command", result));
+            }
+        };
+
+        let result = match child.wait_with_output() {
+            Err(result) => {
+                return Err(JudgeError::io("language_info_command", result));
+            }
+            Ok(result) => result,
+        };
+        if !result.status.success() {
+            return Err(JudgeError::Unsupported(format!(
+                "language_info_command failed: {}",
+                String::from_utf8_lossy(&result.stderr)
+            )));
+        }
+        if result.stdout.is_empty() {
+            return Ok(String::from_utf8(result.stderr).unwrap());
         }
+        Ok(String::from_utf8(result.stdout).unwrap())
     }
+
     fn raw_code_default() -> String {
         String::new()
     }
@@ -46,6 +145,39 @@ impl CompileAndExeSetting {
     fn language_info_command_default() -> String {
         String::new()
     }
+    fn inherit_env_default() -> bool {
+        false
+    }
+    fn env_default() -> HashMap<String, String> {
+        HashMap::new()
+    }
+    fn compile_env_default() -> HashMap<String, String> {
+        HashMap::new()
+    }
+    fn problem_files_default() -> HashMap<String, String> {
+        HashMap::new()
+    }
+    fn jail_mounts_default() -> Vec<String> {
+        vec![]
+    }
+    fn seccomp_profile_default() -> String {
+        String::new()
+    }
+    fn compile_time_limit_default() -> TimeSpan {
+        TimeSpan::from_seconds(10)
+    }
+    fn compile_memory_limit_default() -> MemorySize {
+        MemorySize::from_gigabytes(1)
+    }
+    fn compile_process_limit_default() -> usize {
+        256
+    }
+    fn compile_output_limit_default() -> MemorySize {
+        MemorySize::from_megabytes(1)
+    }
+    fn archive_limit_default() -> MemorySize {
+        MemorySize::from_megabytes(64)
+    }
 }
 
 #[serde_as]
@@ -65,43 +197,20 @@ impl CompileAndExeSettings {
         }
     }
 
-    pub fn get_language_info(&self, language: &str) -> Result<String, String> {
+    pub fn get_language_info(&self, language: &str) -> Result<String, JudgeError> {
         let setting = match self.languages.get(language) {
             None => {
-                return Err(format!("{} is not supported", language));
+                return Err(JudgeError::Setting(format!(
+                    "{} is not supported",
+                    language
+                )));
             }
             Some(result) => result,
         };
-        let child = match Command::new("sh")
-            .arg("-c")
-            .arg(setting.language_info_command.as_str())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-        {
-            Ok(result) => result,
-            Err(result) => {
-                return Err(format!(
-                    "language_info_command failed: {}",
-                    result.to_string()
-                ));
-            }
-        };
-
-        let result = child.wait_with_output().unwrap();
-        if !result.status.success() {
-            return Err(format!(
-                "language_info_command failed: {}",
-                String::from_utf8(result.stderr).unwrap()
-            ));
-        }
-        if result.stdout.is_empty() {
-            return Ok(String::from_utf8(result.stderr).unwrap());
-        }
-        Ok(String::from_utf8(result.stdout).unwrap())
+        setting.get_language_info()
     }
 
-    pub fn get_languages_info(&self) -> Result<HashMap<String, String>, String> {
+    pub fn get_languages_info(&self) -> Result<HashMap<String, String>, JudgeError> {
         let mut result = HashMap::new();
         for (language, _) in self.languages.iter() {
             match self.get_language_info(language) {
@@ -116,31 +225,67 @@ impl CompileAndExeSettings {
         Ok(result)
     }
 
-    pub fn self_check(&self) -> Result<(), String> {
+    pub fn self_check(&self) -> Result<(), JudgeError> {
         for (language, setting) in self.languages.iter() {
             if language.is_empty() {
-                return Err("language is empty".to_string());
+                return Err(JudgeError::Setting("language is empty".to_string()));
             }
             if setting.exe_command.is_empty() {
-                return Err(format!("{}'s exe_command is empty", language));
+                return Err(JudgeError::Setting(format!(
+                    "{}'s exe_command is empty",
+                    language
+                )));
+            }
+            let placeholders = setting.command_placeholders(setting.compile_memory_limit);
+            if !setting.compile_command.is_empty() {
+                if let Err(result) =
+                    turn_command_into_command_and_args(&setting.compile_command, &placeholders)
+                {
+                    return Err(JudgeError::Setting(format!(
+                        "{}'s compile_command is invalid: {}",
+                        language, result
+                    )));
+                }
+            }
+            if let Err(result) =
+                turn_command_into_command_and_args(&setting.exe_command, &placeholders)
+            {
+                return Err(JudgeError::Setting(format!(
+                    "{}'s exe_command is invalid: {}",
+                    language, result
+                )));
             }
             if setting.exe_files.is_empty() {
-                return Err(format!("{}'s exe_files is empty", language));
+                return Err(JudgeError::Setting(format!(
+                    "{}'s exe_files is empty",
+                    language
+                )));
+            }
+            for path in setting.problem_files.keys() {
+                if let Err(result) = check_relative_path(path) {
+                    return Err(JudgeError::Setting(format!(
+                        "{}'s problem_files is invalid: {}",
+                        language, result
+                    )));
+                }
             }
             if setting.language_info_command.is_empty() {
-                return Err(format!("{}'s language_info_command is empty", language));
+                return Err(JudgeError::Setting(format!(
+                    "{}'s language_info_command is empty",
+                    language
+                )));
             }
             if let Err(result) = self.get_language_info(language) {
-                return Err(format!(
+                return Err(JudgeError::Setting(format!(
                     "{}'s language_info_command is invalid: {}",
                     language, result
-                ));
+                )));
             }
         }
         Ok(())
     }
 
-    pub fn load_from_string(s: String, format: config::FileFormat) -> Result<Self, String> {
+    pub fn load_from_string(s: String, format: config::FileFormat) -> Result<Self, JudgeError> {
         match Config::builder()
             .add_source(config::File::from_str(s.as_str(), format))
             .build()
@@ -150,13 +295,13 @@ impl CompileAndExeSettings {
                     Ok(_) => Ok(result),
                     Err(result) => Err(result),
                 },
-                Err(result) => Err(result.to_string()),
+                Err(result) => Err(result.into()),
             },
-            Err(result) => Err(result.to_string()),
+            Err(result) => Err(result.into()),
         }
     }
 
-    pub fn load_from_file(file_path: &str, format: config::FileFormat) -> Result<Self, String> {
+    pub fn load_from_file(file_path: &str, format: config::FileFormat) -> Result<Self, JudgeError> {
         match Config::builder()
             .add_source(config::File::with_name(file_path).format(format))
             .build()
@@ -166,9 +311,9 @@ impl CompileAndExeSettings {
                     Ok(_) => Ok(result),
                     Err(result) => Err(result),
                 },
-                Err(result) => Err(result.to_string()),
+                Err(result) => Err(result.into()),
             },
-            Err(result) => Err(result.to_string()),
+            Err(result) => Err(result.into()),
         }
     }
 
@@ -195,6 +340,144 @@ impl CompileAndExeSettings {
     }
 }
 
+// Every file placed into a compile dir is named by a path relative to it. Only
+// plain components are allowed, so nothing can be written outside of the dir.
+pub fn check_relative_path(path: &str) -> Result<(), JudgeError> {
+    if path.is_empty() {
+        return Err(JudgeError::Setting("path is empty".to_string()));
+    }
+    for component in std::path::Path::new(path).components() {
+        match component {
+            std::path::Component::Normal(_) => {}
+            _ => {
+                return Err(JudgeError::Setting(format!(
+                    "{} is not a plain relative path",
+                    path
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Splits a command the way a POSIX shell would, without running one. Quotes
+// and backslashes are honoured, and `{name}` or `${name}` is replaced by the
+// matching placeholder everywhere except inside single quotes. Any other name
+// is left as it is, so that shell variables like `${HOME}` reach the shell.
+pub fn turn_command_into_command_and_args(
+    command: &str,
+    placeholders: &HashMap<String, String>,
+) -> Result<(String, Vec<String>), JudgeError> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        None => {
+                            return Err(JudgeError::Setting(
+                                "unterminated single quote".to_string(),
+                            ))
+                        }
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        None => {
+                            return Err(JudgeError::Setting(
+                                "unterminated double quote".to_string(),
+                            ))
+                        }
+                        Some('"') => break,
+                        Some('\\') => match chars.peek() {
+                            Some('\n') => {
+                                chars.next();
+                            }
+                            Some(&c) if c == '"' || c == '\\' || c == '$' || c == '`' => {
+                                chars.next();
+                                word.push(c);
+                            }
+                            _ => word.push('\\'),
+                        },
+                        Some(c) => expand_placeholder(c, &mut chars, placeholders, &mut word),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                None => return Err(JudgeError::Setting("trailing backslash".to_string())),
+                Some('\n') => {}
+                Some(c) => {
+                    in_word = true;
+                    word.push(c);
+                }
+            },
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                expand_placeholder(c, &mut chars, placeholders, &mut word);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    if words.is_empty() {
+        return Err(JudgeError::Setting("command is empty".to_string()));
+    }
+    let command = words.remove(0);
+    Ok((command, words))
+}
+
+fn expand_placeholder(
+    c: char,
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    placeholders: &HashMap<String, String>,
+    word: &mut String,
+) {
+    let mut lookahead = chars.clone();
+    if c == '$' && lookahead.next() != Some('{') {
+        word.push(c);
+        return;
+    }
+    if c != '$' && c != '{' {
+        word.push(c);
+        return;
+    }
+    let mut name = String::new();
+    loop {
+        match lookahead.next() {
+            Some('}') if !name.is_empty() => break,
+            Some(c) if c.is_ascii_alphanumeric() || c == '_' => name.push(c),
+            _ => {
+                // Not a placeholder, e.g. `{}` in `find -exec`.
+                word.push(c);
+                return;
+            }
+        }
+    }
+    match placeholders.get(&name) {
+        None => word.push(c),
+        Some(result) => {
+            *chars = lookahead;
+            word.push_str(result);
+        }
+    }
+}
+
 fn toml_to_ini(toml_string: &str) -> String {
     let value: toml::Value = toml::from_str(toml_string).unwrap();
     let mut ini_string = String::new();
@@ -212,8 +495,8 @@ fn toml_to_ini(toml_string: &str) -> String {
     ini_string
 }
 
-pub fn create_a_tmp_user_return_uid(user_name: &str) -> Result<u32, ()> {
-    let _ = Command::new("adduser")
+pub fn create_a_tmp_user_return_uid(user_name: &str) -> Result<u32, JudgeError> {
+    match Command::new("adduser")
         .arg("--disabled-password")
         .arg("--gecos")
         .arg("\"\"")
@@ -222,12 +505,19 @@ pub fn create_a_tmp_user_return_uid(user_name: &str) -> Result<u32, ()> {
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .spawn()
-        .unwrap()
-        .wait();
-    match users::get_user_by_name(user_name) {
-        None => {
-            return Err(());
+    {
+        Err(result) => {
+            return Err(JudgeError::spawn("adduser", result));
         }
+        Ok(mut result) => {
+            let _ = result.wait();
+        }
+    }
+    match users::get_user_by_name(user_name) {
+        None => Err(JudgeError::Internal(format!(
+            "user {} could not be created",
+            user_name
+        ))),
         Some(result) => Ok(result.uid()),
     }
 }
@@ -1,33 +1,245 @@
 use crate::{
+    cache::CompileCache,
     cgroup::Cgroup,
-    program::RawCode,
+    program::{join_all, ExeCode, ExeResources, RawCode, RunLimits},
     quantity::{MemorySize, ProcessResource, TimeSpan},
     result::{
         self, AnsAndEvalResult, CompileResult, InitExeResourceResult, OnlyRunResult,
-        RunAndEvalResult, RunAndInteractResult,
+        RunAndEvalResult, RunAndInteractResult, RunAndManageResult, RunInStagesResult,
+        RunToEndResult, StressFailure, StressProgram, StressResult,
     },
 };
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+// Checkers and interactors are shared by every submission to a problem, so
+// passing a cache here lets them be compiled once instead of once per run.
+async fn compile(code: &RawCode, uid: u32, cache: Option<&CompileCache>) -> CompileResult {
+    let setting = &code.compile_and_exe_setting;
+    match cache {
+        None => {
+            code.compile(
+                uid,
+                setting.compile_time_limit,
+                setting.compile_memory_limit,
+                setting.compile_process_limit,
+                setting.compile_output_limit,
+            )
+            .await
+        }
+        Some(cache) => {
+            cache
+                .compile(
+                    code,
+                    uid,
+                    setting.compile_time_limit,
+                    setting.compile_memory_limit,
+                    setting.compile_process_limit,
+                    setting.compile_output_limit,
+                )
+                .await
+        }
+    }
+}
+
+// The `multiple` runners compile once and then hand the tests out to a number
+// of workers, each owning its own cgroups and exe resources. A worker takes
+// the next test nobody has taken yet, so one slow test does not hold up the
+// tests behind it.
+fn worker_count(concurrency: usize, test_count: usize) -> usize {
+    concurrency.clamp(1, test_count.max(1))
+}
+
+// Workers are only pinned when every process that can run at the same time
+// gets a core of its own, otherwise pinning would pile them onto each other.
+fn worker_cpus(worker_count: usize, cpus_per_worker: usize) -> Vec<Vec<Option<usize>>> {
+    let mut cpus = vec![];
+    if worker_count > 1 {
+        if let Ok(cpu_set) = nix::sched::sched_getaffinity(nix::unistd::Pid::from_raw(0)) {
+            for cpu in 0..nix::sched::CpuSet::count() {
+                if cpu_set.is_set(cpu).unwrap_or(false) {
+                    cpus.push(cpu);
+                }
+            }
+        }
+    }
+    let is_pinned = cpus.len() >= worker_count * cpus_per_worker;
+    (0..worker_count)
+        .map(|worker| {
+            (0..cpus_per_worker)
+                .map(|i| match is_pinned {
+                    true => Some(cpus[worker * cpus_per_worker + i]),
+                    false => None,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopPolicy {
+    RunAll,
+    StopOnFirstFailure,
+    // Holds the group of every test. A failing test skips the rest of its
+    // group and leaves the other groups running.
+    PerGroup(Vec<usize>),
+}
+
+// Hands the tests out to the workers in input order and keeps track of
+// failures for the stop policy. Tests that are already running when a failure
+// is reported finish normally, only the ones not taken yet are skipped.
+struct TestQueue<'a> {
+    test_count: usize,
+    stop_policy: &'a StopPolicy,
+    next_test: Cell<usize>,
+    is_stopped: Cell<bool>,
+    failed_groups: RefCell<HashSet<usize>>,
+}
+
+impl<'a> TestQueue<'a> {
+    fn new(test_count: usize, stop_policy: &'a StopPolicy) -> Self {
+        TestQueue {
+            test_count,
+            stop_policy,
+            next_test: Cell::new(0),
+            is_stopped: Cell::new(false),
+            failed_groups: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn take(&self) -> Option<usize> {
+        let index = self.next_test.get();
+        if index >= self.test_count {
+            return None;
+        }
+        self.next_test.set(index + 1);
+        Some(index)
+    }
+
+    fn is_skipped(&self, index: usize) -> bool {
+        match self.stop_policy {
+            StopPolicy::RunAll => false,
+            StopPolicy::StopOnFirstFailure => self.is_stopped.get(),
+            StopPolicy::PerGroup(groups) => match groups.get(index) {
+                None => false,
+                Some(group) => self.failed_groups.borrow().contains(group),
+            },
+        }
+    }
+
+    fn report(&self, index: usize, is_ok: bool) {
+        if is_ok {
+            return;
+        }
+        self.is_stopped.set(true);
+        if let StopPolicy::PerGroup(groups) = self.stop_policy {
+            if let Some(group) = groups.get(index) {
+                self.failed_groups.borrow_mut().insert(*group);
+            }
+        }
+    }
+}
+
+fn in_order<R>(worker_results: Vec<(Vec<usize>, Vec<R>)>, test_count: usize) -> Vec<R> {
+    let mut all_results: Vec<Option<R>> = (0..test_count).map(|_| None).collect();
+    for (indexes, results) in worker_results {
+        for (index, result) in indexes.into_iter().zip(results) {
+            all_results[index] = Some(result);
+        }
+    }
+    all_results
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect()
+}
+
+// The limits of one program of a test. For an interactor, time_limit is the
+// time it may keep running after the tested program has ended and
+// cpu_time_limit is not used. A process_limit of 0 leaves the number of
+// processes unlimited, for hosts without the pids controller.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgramLimits {
+    pub time_limit: TimeSpan,
+    pub cpu_time_limit: TimeSpan,
+    pub memory_limit: MemorySize,
+    pub process_limit: usize,
+    pub uid: u32,
+}
+
+impl ProgramLimits {
+    pub fn new(
+        time_limit: TimeSpan,
+        memory_limit: MemorySize,
+        process_limit: usize,
+        uid: u32,
+    ) -> Self {
+        ProgramLimits {
+            time_limit,
+            cpu_time_limit: time_limit,
+            memory_limit,
+            process_limit,
+            uid,
+        }
+    }
+
+    fn run_limits(&self, options: &RunOptions<'_>) -> RunLimits {
+        RunLimits {
+            time_limit: self.time_limit,
+            cpu_time_limit: self.cpu_time_limit,
+            output_limit: options.output_limit,
+            network_isolation: options.network_isolation,
+        }
+    }
+}
+
+// The options shared by every program of a test. testlib tells whether the
+// checker or the interactor talks testlib, the tested program never does.
+// concurrency and stop_policy are only read by the `multiple` runners.
+#[derive(Clone)]
+pub struct RunOptions<'a> {
+    pub output_limit: MemorySize,
+    pub network_isolation: bool,
+    pub jail_mounts: Option<&'a Vec<String>>,
+    pub testlib: bool,
+    pub concurrency: usize,
+    pub stop_policy: StopPolicy,
+    pub cache: Option<&'a CompileCache>,
+}
+
+impl RunOptions<'_> {
+    pub fn new(output_limit: MemorySize) -> Self {
+        RunOptions {
+            output_limit,
+            network_isolation: false,
+            jail_mounts: None,
+            testlib: false,
+            concurrency: 1,
+            stop_policy: StopPolicy::RunAll,
+            cache: None,
+        }
+    }
+}
+
 pub struct OnlyRun;
 
 impl OnlyRun {
     pub async fn single(
         code: &RawCode,
-        time_limit: TimeSpan,
-        memory_limit: MemorySize,
-        code_uid: u32,
+        limits: &ProgramLimits,
         input: &Vec<u8>,
-        output_limit: MemorySize,
+        options: &RunOptions<'_>,
     ) -> OnlyRunResult {
-        let mut cgroup = match Cgroup::new_tmp(memory_limit) {
+        let mut cgroup = match Cgroup::new_tmp(limits.memory_limit, limits.process_limit) {
             Ok(result) => result,
             Err(result) => return OnlyRunResult::InternalError(result),
         };
-        match code.compile().await {
+        match compile(code, limits.uid, options.cache).await {
             CompileResult::Ok(exe_code) => {
-                let exe_resources = exe_code.initial_exe_resources(code_uid).await;
+                let exe_resources = exe_code
+                    .initial_exe_resources(limits.uid, options.jail_mounts)
+                    .await;
                 match exe_resources {
                     InitExeResourceResult::Ok(mut exe_resources) => exe_resources
-                        .run_to_end(input, &mut cgroup, time_limit, output_limit)
+                        .run_to_end(input, &mut cgroup, limits.run_limits(options))
                         .await
                         .into(),
                     result => result.into(),
@@ -39,32 +251,56 @@ impl OnlyRun {
 
     pub async fn multiple(
         code: &RawCode,
-        time_limit: TimeSpan,
-        memory_limit: MemorySize,
-        code_uid: u32,
+        limits: &ProgramLimits,
         inputs: &Vec<Vec<u8>>,
-        output_limit: MemorySize,
+        options: &RunOptions<'_>,
     ) -> Vec<OnlyRunResult> {
-        let mut cgroup = match Cgroup::new_tmp(memory_limit) {
-            Ok(result) => result,
-            Err(result) => return vec![OnlyRunResult::InternalError(result); inputs.len()],
-        };
-        let exe_code = match code.compile().await {
+        let exe_code = match compile(code, limits.uid, options.cache).await {
             CompileResult::Ok(result) => result,
             result => return vec![result.into(); inputs.len()],
         };
-        let mut exe_resources = match exe_code.initial_exe_resources(code_uid).await {
-            InitExeResourceResult::Ok(result) => result,
-            result => return vec![result.into(); inputs.len()],
-        };
-        let mut all_results = vec![];
-        for input in inputs {
-            let result = exe_resources
-                .run_to_end(input, &mut cgroup, time_limit, output_limit)
-                .await;
-            all_results.push(result.into());
+        let mut workers = vec![];
+        for cpus in worker_cpus(worker_count(options.concurrency, inputs.len()), 1) {
+            let cgroup = match Cgroup::new_tmp(limits.memory_limit, limits.process_limit) {
+                Ok(result) => result,
+                Err(result) => return vec![OnlyRunResult::InternalError(result); inputs.len()],
+            };
+            let mut exe_resources = match exe_code
+                .initial_exe_resources(limits.uid, options.jail_mounts)
+                .await
+            {
+                InitExeResourceResult::Ok(result) => result,
+                result => return vec![result.into(); inputs.len()],
+            };
+            exe_resources.cpu_affinity = cpus[0];
+            workers.push((cgroup, exe_resources));
         }
-        all_results
+        let test_queue = TestQueue::new(inputs.len(), &options.stop_policy);
+        let workers = workers
+            .into_iter()
+            .map(|(mut cgroup, mut exe_resources)| {
+                let test_queue = &test_queue;
+                async move {
+                    let mut indexes = vec![];
+                    let mut all_results = vec![];
+                    while let Some(index) = test_queue.take() {
+                        indexes.push(index);
+                        if test_queue.is_skipped(index) {
+                            all_results.push(OnlyRunResult::Skipped);
+                            continue;
+                        }
+                        let result = exe_resources
+                            .run_to_end(&inputs[index], &mut cgroup, limits.run_limits(options))
+                            .await;
+                        let result = OnlyRunResult::from(result);
+                        test_queue.report(index, result.is_ok());
+                        all_results.push(result);
+                    }
+                    (indexes, all_results)
+                }
+            })
+            .collect();
+        in_order(join_all(workers).await, inputs.len())
     }
 }
 
@@ -73,50 +309,47 @@ pub struct RunAndEval;
 impl RunAndEval {
     pub async fn single(
         tested_code: &RawCode,
-        tested_code_time_limit: TimeSpan,
-        tested_code_memory_limit: MemorySize,
-        tested_code_uid: u32,
+        tested_limits: &ProgramLimits,
         eval_code: &RawCode,
-        eval_code_time_limit: TimeSpan,
-        eval_code_memory_limit: MemorySize,
-        eval_code_uid: u32,
+        eval_limits: &ProgramLimits,
         input: &Vec<u8>,
         output: &Vec<u8>,
-        output_limit: MemorySize,
+        options: &RunOptions<'_>,
     ) -> RunAndEvalResult {
-        let mut tested_cgroup = match Cgroup::new_tmp(tested_code_memory_limit) {
-            Ok(result) => result,
-            Err(result) => return RunAndEvalResult::InternalError(result),
-        };
-        let mut eval_cgroup = match Cgroup::new_tmp(eval_code_memory_limit) {
-            Ok(result) => result,
-            Err(result) => return RunAndEvalResult::InternalError(result),
-        };
-        let exe_tested_code = match tested_code.compile().await {
+        let mut tested_cgroup =
+            match Cgroup::new_tmp(tested_limits.memory_limit, tested_limits.process_limit) {
+                Ok(result) => result,
+                Err(result) => return RunAndEvalResult::InternalError(result),
+            };
+        let mut eval_cgroup =
+            match Cgroup::new_tmp(eval_limits.memory_limit, eval_limits.process_limit) {
+                Ok(result) => result,
+                Err(result) => return RunAndEvalResult::InternalError(result),
+            };
+        let exe_tested_code = match compile(tested_code, tested_limits.uid, options.cache).await {
             CompileResult::Ok(result) => result,
             result => return result.into(),
         };
-        let exe_eval_code = match eval_code.compile().await {
+        let exe_eval_code = match compile(eval_code, eval_limits.uid, options.cache).await {
             CompileResult::Ok(result) => result,
             result => return RunAndEvalResult::from(result).to_eval(),
         };
-        let mut tested_code_exe_resources =
-            match exe_tested_code.initial_exe_resources(tested_code_uid).await {
-                InitExeResourceResult::Ok(result) => result,
-                result => return result.into(),
-            };
-        let mut eval_code_exe_resources =
-            match exe_eval_code.initial_exe_resources(eval_code_uid).await {
-                InitExeResourceResult::Ok(result) => result,
-                result => return RunAndEvalResult::from(result).to_eval(),
-            };
+        let mut tested_code_exe_resources = match exe_tested_code
+            .initial_exe_resources(tested_limits.uid, options.jail_mounts)
+            .await
+        {
+            InitExeResourceResult::Ok(result) => result,
+            result => return result.into(),
+        };
+        let mut eval_code_exe_resources = match exe_eval_code
+            .initial_exe_resources(eval_limits.uid, options.jail_mounts)
+            .await
+        {
+            InitExeResourceResult::Ok(result) => result,
+            result => return RunAndEvalResult::from(result).to_eval(),
+        };
         let tested_code_process_resource = match tested_code_exe_resources
-            .run_to_end(
-                input,
-                &mut tested_cgroup,
-                tested_code_time_limit,
-                output_limit,
-            )
+            .run_to_end(input, &mut tested_cgroup, tested_limits.run_limits(options))
             .await
         {
             result::RunToEndResult::Ok(result) => result,
@@ -126,8 +359,12 @@ impl RunAndEval {
             result::RunToEndResult::MemoryLimitExceeded(result) => {
                 return RunAndEvalResult::MemoryLimitExceeded(result, ProcessResource::default())
             }
-            result::RunToEndResult::TimeLimitExceeded(result) => {
-                return RunAndEvalResult::TimeLimitExceeded(result, ProcessResource::default())
+            result::RunToEndResult::TimeLimitExceeded(kind, result) => {
+                return RunAndEvalResult::TimeLimitExceeded(
+                    kind,
+                    result,
+                    ProcessResource::default(),
+                )
             }
             result::RunToEndResult::InternalError(result) => {
                 return RunAndEvalResult::InternalError(result)
@@ -135,23 +372,23 @@ impl RunAndEval {
             result::RunToEndResult::OutputLimitExceeded(result) => {
                 return RunAndEvalResult::OutputLimitExceeded(result, ProcessResource::default())
             }
+            result::RunToEndResult::RestrictedFunction(syscall, result) => {
+                return RunAndEvalResult::RestrictedFunction(
+                    syscall,
+                    result,
+                    ProcessResource::default(),
+                )
+            }
         };
 
-        let mut eval_input = vec![];
-        eval_input.append(&mut Vec::from((input.len() as u64).to_le_bytes()));
-        eval_input.append(&mut input.clone());
-        eval_input.append(&mut Vec::from(
-            (tested_code_process_resource.stdout.len() as u64).to_le_bytes(),
-        ));
-        eval_input.append(&mut tested_code_process_resource.stdout.clone());
-        eval_input.append(&mut Vec::from((output.len() as u64).to_le_bytes()));
-        eval_input.append(&mut output.clone());
         let eval_code_process_resource = match eval_code_exe_resources
-            .run_to_end(
-                &eval_input,
+            .run_eval(
+                Some(input),
+                &tested_code_process_resource.stdout,
+                output,
+                options.testlib,
                 &mut eval_cgroup,
-                eval_code_time_limit,
-                output_limit,
+                eval_limits.run_limits(options),
             )
             .await
         {
@@ -165,8 +402,9 @@ impl RunAndEval {
                     result,
                 )
             }
-            result::RunToEndResult::TimeLimitExceeded(result) => {
+            result::RunToEndResult::TimeLimitExceeded(kind, result) => {
                 return RunAndEvalResult::EvalTimeLimitExceeded(
+                    kind,
                     tested_code_process_resource,
                     result,
                 )
@@ -180,153 +418,232 @@ impl RunAndEval {
                     result,
                 )
             }
+            result::RunToEndResult::RestrictedFunction(syscall, result) => {
+                return RunAndEvalResult::EvalRestrictedFunction(
+                    syscall,
+                    tested_code_process_resource,
+                    result,
+                )
+            }
         };
-        RunAndEvalResult::Ok(tested_code_process_resource, eval_code_process_resource)
+        let eval_verdict = match eval_code_exe_resources
+            .eval_verdict(
+                &eval_code_process_resource,
+                options.testlib,
+                options.output_limit,
+            )
+            .await
+        {
+            Err(result) => return RunAndEvalResult::InternalError(result),
+            Ok(result) => result,
+        };
+        RunAndEvalResult::Ok(
+            tested_code_process_resource,
+            eval_code_process_resource,
+            eval_verdict,
+        )
     }
 
     pub async fn multiple(
         tested_code: &RawCode,
-        tested_code_time_limit: TimeSpan,
-        tested_code_memory_limit: MemorySize,
-        tested_code_uid: u32,
+        tested_limits: &ProgramLimits,
         eval_code: &RawCode,
-        eval_code_time_limit: TimeSpan,
-        eval_code_memory_limit: MemorySize,
-        eval_code_uid: u32,
+        eval_limits: &ProgramLimits,
         inputs: &Vec<Vec<u8>>,
         outputs: &Vec<Vec<u8>>,
-        output_limit: MemorySize,
+        options: &RunOptions<'_>,
     ) -> Vec<RunAndEvalResult> {
-        let mut tested_cgroup = match Cgroup::new_tmp(tested_code_memory_limit) {
-            Ok(result) => result,
-            Err(result) => return vec![RunAndEvalResult::InternalError(result); inputs.len()],
-        };
-        let mut eval_cgroup = match Cgroup::new_tmp(eval_code_memory_limit) {
-            Ok(result) => result,
-            Err(result) => return vec![RunAndEvalResult::InternalError(result); inputs.len()],
-        };
-        let exe_tested_code = match tested_code.compile().await {
+        let exe_tested_code = match compile(tested_code, tested_limits.uid, options.cache).await {
             CompileResult::Ok(result) => result,
             result => return vec![result.into(); inputs.len()],
         };
-        let exe_eval_code = match eval_code.compile().await {
+        let exe_eval_code = match compile(eval_code, eval_limits.uid, options.cache).await {
             CompileResult::Ok(result) => result,
             result => return vec![RunAndEvalResult::from(result).to_eval(); inputs.len()],
         };
-        let mut tested_code_exe_resources =
-            match exe_tested_code.initial_exe_resources(tested_code_uid).await {
-                InitExeResourceResult::Ok(result) => result,
-                result => return vec![result.into(); inputs.len()],
-            };
-        let mut eval_code_exe_resources =
-            match exe_eval_code.initial_exe_resources(eval_code_uid).await {
-                InitExeResourceResult::Ok(result) => result,
-                result => return vec![RunAndEvalResult::from(result).to_eval(); inputs.len()],
-            };
-        let mut all_results = vec![];
-        for (input, output) in inputs.iter().zip(outputs.iter()) {
-            let tested_code_process_resource = match tested_code_exe_resources
-                .run_to_end(
-                    input,
-                    &mut tested_cgroup,
-                    tested_code_time_limit,
-                    output_limit,
-                )
+        let mut workers = vec![];
+        // The tested code and the checker take turns, so they share a core.
+        for cpus in worker_cpus(worker_count(options.concurrency, inputs.len()), 1) {
+            let tested_cgroup =
+                match Cgroup::new_tmp(tested_limits.memory_limit, tested_limits.process_limit) {
+                    Ok(result) => result,
+                    Err(result) => {
+                        return vec![RunAndEvalResult::InternalError(result); inputs.len()]
+                    }
+                };
+            let eval_cgroup =
+                match Cgroup::new_tmp(eval_limits.memory_limit, eval_limits.process_limit) {
+                    Ok(result) => result,
+                    Err(result) => {
+                        return vec![RunAndEvalResult::InternalError(result); inputs.len()]
+                    }
+                };
+            let mut tested_code_exe_resources = match exe_tested_code
+                .initial_exe_resources(tested_limits.uid, options.jail_mounts)
                 .await
             {
-                result::RunToEndResult::Ok(result) => result,
-                result::RunToEndResult::RuntimeError(result) => {
-                    all_results.push(RunAndEvalResult::RuntimeError(
-                        result,
-                        ProcessResource::default(),
-                    ));
-                    continue;
-                }
-                result::RunToEndResult::MemoryLimitExceeded(result) => {
-                    all_results.push(RunAndEvalResult::MemoryLimitExceeded(
-                        result,
-                        ProcessResource::default(),
-                    ));
-                    continue;
-                }
-                result::RunToEndResult::TimeLimitExceeded(result) => {
-                    all_results.push(RunAndEvalResult::TimeLimitExceeded(
-                        result,
-                        ProcessResource::default(),
-                    ));
-                    continue;
-                }
-                result::RunToEndResult::InternalError(result) => {
-                    all_results.push(RunAndEvalResult::InternalError(result));
-                    continue;
-                }
-                result::RunToEndResult::OutputLimitExceeded(result) => {
-                    all_results.push(RunAndEvalResult::OutputLimitExceeded(
-                        result,
-                        ProcessResource::default(),
-                    ));
-                    continue;
-                }
+                InitExeResourceResult::Ok(result) => result,
+                result => return vec![result.into(); inputs.len()],
             };
-
-            let mut eval_input = vec![];
-            eval_input.append(&mut Vec::from((input.len() as u64).to_le_bytes()));
-            eval_input.append(&mut input.clone());
-            eval_input.append(&mut Vec::from(
-                (tested_code_process_resource.stdout.len() as u64).to_le_bytes(),
-            ));
-            eval_input.append(&mut tested_code_process_resource.stdout.clone());
-            eval_input.append(&mut Vec::from((output.len() as u64).to_le_bytes()));
-            eval_input.append(&mut output.clone());
-            let eval_code_process_resource = match eval_code_exe_resources
-                .run_to_end(
-                    &eval_input,
-                    &mut eval_cgroup,
-                    eval_code_time_limit,
-                    output_limit,
-                )
+            let mut eval_code_exe_resources = match exe_eval_code
+                .initial_exe_resources(eval_limits.uid, options.jail_mounts)
                 .await
             {
-                result::RunToEndResult::Ok(result) => result,
-                result::RunToEndResult::RuntimeError(result) => {
-                    all_results.push(RunAndEvalResult::EvalRuntimeError(
-                        tested_code_process_resource,
-                        result,
-                    ));
-                    continue;
-                }
-                result::RunToEndResult::MemoryLimitExceeded(result) => {
-                    all_results.push(RunAndEvalResult::EvalMemoryLimitExceeded(
-                        tested_code_process_resource,
-                        result,
-                    ));
-                    continue;
-                }
-                result::RunToEndResult::TimeLimitExceeded(result) => {
-                    all_results.push(RunAndEvalResult::EvalTimeLimitExceeded(
-                        tested_code_process_resource,
-                        result,
-                    ));
-                    continue;
-                }
-                result::RunToEndResult::InternalError(result) => {
-                    all_results.push(RunAndEvalResult::InternalError(result));
-                    continue;
-                }
-                result::RunToEndResult::OutputLimitExceeded(result) => {
-                    all_results.push(RunAndEvalResult::EvalOutputLimitExceeded(
-                        tested_code_process_resource,
-                        result,
-                    ));
-                    continue;
-                }
+                InitExeResourceResult::Ok(result) => result,
+                result => return vec![RunAndEvalResult::from(result).to_eval(); inputs.len()],
             };
-            all_results.push(RunAndEvalResult::Ok(
-                tested_code_process_resource,
-                eval_code_process_resource,
+            tested_code_exe_resources.cpu_affinity = cpus[0];
+            eval_code_exe_resources.cpu_affinity = cpus[0];
+            workers.push((
+                tested_cgroup,
+                eval_cgroup,
+                tested_code_exe_resources,
+                eval_code_exe_resources,
             ));
         }
-        all_results
+        let test_queue = TestQueue::new(inputs.len(), &options.stop_policy);
+        let workers = workers
+            .into_iter()
+            .map(
+                |(
+                    mut tested_cgroup,
+                    mut eval_cgroup,
+                    mut tested_code_exe_resources,
+                    mut eval_code_exe_resources,
+                )| {
+                    let test_queue = &test_queue;
+                    async move {
+                        let mut indexes = vec![];
+                        let mut all_results = vec![];
+                        while let Some(index) = test_queue.take() {
+                            indexes.push(index);
+                            if test_queue.is_skipped(index) {
+                                all_results.push(RunAndEvalResult::Skipped);
+                                continue;
+                            }
+                            let result = async {
+                                let (input, output) = (&inputs[index], &outputs[index]);
+                                let tested_code_process_resource = match tested_code_exe_resources
+                                    .run_to_end(
+                                        input,
+                                        &mut tested_cgroup,
+                                        tested_limits.run_limits(options),
+                                    )
+                                    .await
+                                {
+                                    result::RunToEndResult::Ok(result) => result,
+                                    result::RunToEndResult::RuntimeError(result) => {
+                                        return RunAndEvalResult::RuntimeError(
+                                            result,
+                                            ProcessResource::default(),
+                                        );
+                                    }
+                                    result::RunToEndResult::MemoryLimitExceeded(result) => {
+                                        return RunAndEvalResult::MemoryLimitExceeded(
+                                            result,
+                                            ProcessResource::default(),
+                                        );
+                                    }
+                                    result::RunToEndResult::TimeLimitExceeded(kind, result) => {
+                                        return RunAndEvalResult::TimeLimitExceeded(
+                                            kind,
+                                            result,
+                                            ProcessResource::default(),
+                                        );
+                                    }
+                                    result::RunToEndResult::InternalError(result) => {
+                                        return RunAndEvalResult::InternalError(result);
+                                    }
+                                    result::RunToEndResult::OutputLimitExceeded(result) => {
+                                        return RunAndEvalResult::OutputLimitExceeded(
+                                            result,
+                                            ProcessResource::default(),
+                                        );
+                                    }
+                                    result::RunToEndResult::RestrictedFunction(syscall, result) => {
+                                        return RunAndEvalResult::RestrictedFunction(
+                                            syscall,
+                                            result,
+                                            ProcessResource::default(),
+                                        );
+                                    }
+                                };
+
+                                let eval_code_process_resource = match eval_code_exe_resources
+                                    .run_eval(
+                                        Some(input),
+                                        &tested_code_process_resource.stdout,
+                                        output,
+                                        options.testlib,
+                                        &mut eval_cgroup,
+                                        eval_limits.run_limits(options),
+                                    )
+                                    .await
+                                {
+                                    result::RunToEndResult::Ok(result) => result,
+                                    result::RunToEndResult::RuntimeError(result) => {
+                                        return RunAndEvalResult::EvalRuntimeError(
+                                            tested_code_process_resource,
+                                            result,
+                                        );
+                                    }
+                                    result::RunToEndResult::MemoryLimitExceeded(result) => {
+                                        return RunAndEvalResult::EvalMemoryLimitExceeded(
+                                            tested_code_process_resource,
+                                            result,
+                                        );
+                                    }
+                                    result::RunToEndResult::TimeLimitExceeded(kind, result) => {
+                                        return RunAndEvalResult::EvalTimeLimitExceeded(
+                                            kind,
+                                            tested_code_process_resource,
+                                            result,
+                                        );
+                                    }
+                                    result::RunToEndResult::InternalError(result) => {
+                                        return RunAndEvalResult::InternalError(result);
+                                    }
+                                    result::RunToEndResult::OutputLimitExceeded(result) => {
+                                        return RunAndEvalResult::EvalOutputLimitExceeded(
+                                            tested_code_process_resource,
+                                            result,
+                                        );
+                                    }
+                                    result::RunToEndResult::RestrictedFunction(syscall, result) => {
+                                        return RunAndEvalResult::EvalRestrictedFunction(
+                                            syscall,
+                                            tested_code_process_resource,
+                                            result,
+                                        );
+                                    }
+                                };
+                                let eval_verdict = match eval_code_exe_resources
+                                    .eval_verdict(
+                                        &eval_code_process_resource,
+                                        options.testlib,
+                                        options.output_limit,
+                                    )
+                                    .await
+                                {
+                                    Err(result) => return RunAndEvalResult::InternalError(result),
+                                    Ok(result) => result,
+                                };
+                                RunAndEvalResult::Ok(
+                                    tested_code_process_resource,
+                                    eval_code_process_resource,
+                                    eval_verdict,
+                                )
+                            }
+                            .await;
+                            test_queue.report(index, result.is_accepted());
+                            all_results.push(result);
+                        }
+                        (indexes, all_results)
+                    }
+                },
+            )
+            .collect();
+        in_order(join_all(workers).await, inputs.len())
     }
 }
 
@@ -335,94 +652,125 @@ pub struct AnsAndEval;
 impl AnsAndEval {
     pub async fn single(
         eval_code: &RawCode,
-        eval_code_time_limit: TimeSpan,
-        eval_code_memory_limit: MemorySize,
-        eval_code_uid: u32,
+        eval_limits: &ProgramLimits,
         tested_ans: &Vec<u8>,
         std_ans: &Vec<u8>,
-        output_limit: MemorySize,
+        options: &RunOptions<'_>,
     ) -> AnsAndEvalResult {
-        let mut eval_cgroup = match Cgroup::new_tmp(eval_code_memory_limit) {
-            Ok(result) => result,
-            Err(result) => return AnsAndEvalResult::InternalError(result),
-        };
-        let exe_eval_code = match eval_code.compile().await {
+        let mut eval_cgroup =
+            match Cgroup::new_tmp(eval_limits.memory_limit, eval_limits.process_limit) {
+                Ok(result) => result,
+                Err(result) => return AnsAndEvalResult::InternalError(result),
+            };
+        let exe_eval_code = match compile(eval_code, eval_limits.uid, options.cache).await {
             CompileResult::Ok(result) => result,
             result => return result.into(),
         };
-        let mut eval_input = vec![];
-        eval_input.append(&mut Vec::from((tested_ans.len() as u64).to_le_bytes()));
-        eval_input.append(&mut tested_ans.clone());
-        eval_input.append(&mut Vec::from((std_ans.len() as u64).to_le_bytes()));
-        eval_input.append(&mut std_ans.clone());
-        let mut eval_code_exe_resource =
-            match exe_eval_code.initial_exe_resources(eval_code_uid).await {
-                InitExeResourceResult::Ok(result) => result,
-                result => return result.into(),
-            };
+        let mut eval_code_exe_resource = match exe_eval_code
+            .initial_exe_resources(eval_limits.uid, options.jail_mounts)
+            .await
+        {
+            InitExeResourceResult::Ok(result) => result,
+            result => return result.into(),
+        };
         match eval_code_exe_resource
-            .run_to_end(
-                &eval_input,
+            .run_eval(
+                None,
+                tested_ans,
+                std_ans,
+                options.testlib,
                 &mut eval_cgroup,
-                eval_code_time_limit,
-                output_limit,
+                eval_limits.run_limits(options),
             )
             .await
         {
-            result::RunToEndResult::Ok(result) => AnsAndEvalResult::Ok(result),
+            result::RunToEndResult::Ok(result) => match eval_code_exe_resource
+                .eval_verdict(&result, options.testlib, options.output_limit)
+                .await
+            {
+                Err(result) => AnsAndEvalResult::InternalError(result),
+                Ok(eval_verdict) => AnsAndEvalResult::Ok(result, eval_verdict),
+            },
             result => result.into(),
         }
     }
 
     pub async fn multiple(
         eval_code: &RawCode,
-        eval_code_time_limit: TimeSpan,
-        eval_code_memory_limit: MemorySize,
-        eval_code_uid: u32,
+        eval_limits: &ProgramLimits,
         tested_anses: &Vec<Vec<u8>>,
         std_anses: &Vec<Vec<u8>>,
-        output_limit: MemorySize,
+        options: &RunOptions<'_>,
     ) -> Vec<AnsAndEvalResult> {
-        let mut eval_cgroup = match Cgroup::new_tmp(eval_code_memory_limit) {
-            Ok(result) => result,
-            Err(result) => {
-                return vec![AnsAndEvalResult::InternalError(result); tested_anses.len()]
-            }
-        };
-        let exe_eval_code = match eval_code.compile().await {
+        let exe_eval_code = match compile(eval_code, eval_limits.uid, options.cache).await {
             CompileResult::Ok(result) => result,
             result => return vec![result.into(); tested_anses.len()],
         };
-        let mut eval_code_exe_resources =
-            match exe_eval_code.initial_exe_resources(eval_code_uid).await {
-                InitExeResourceResult::Ok(result) => result,
-                result => return vec![result.into(); tested_anses.len()],
-            };
-        let mut all_results = vec![];
-        for (tested_ans, std_ans) in tested_anses.iter().zip(std_anses.iter()) {
-            let mut eval_input = vec![];
-            eval_input.append(&mut Vec::from((tested_ans.len() as u64).to_le_bytes()));
-            eval_input.append(&mut tested_ans.clone());
-            eval_input.append(&mut Vec::from((std_ans.len() as u64).to_le_bytes()));
-            eval_input.append(&mut std_ans.clone());
-            let eval_code_process_resource = match eval_code_exe_resources
-                .run_to_end(
-                    &eval_input,
-                    &mut eval_cgroup,
-                    eval_code_time_limit,
-                    output_limit,
-                )
+        let mut workers = vec![];
+        for cpus in worker_cpus(worker_count(options.concurrency, tested_anses.len()), 1) {
+            let eval_cgroup =
+                match Cgroup::new_tmp(eval_limits.memory_limit, eval_limits.process_limit) {
+                    Ok(result) => result,
+                    Err(result) => {
+                        return vec![AnsAndEvalResult::InternalError(result); tested_anses.len()]
+                    }
+                };
+            let mut eval_code_exe_resources = match exe_eval_code
+                .initial_exe_resources(eval_limits.uid, options.jail_mounts)
                 .await
             {
-                result::RunToEndResult::Ok(result) => result,
-                result => {
-                    all_results.push(AnsAndEvalResult::from(result));
-                    continue;
-                }
+                InitExeResourceResult::Ok(result) => result,
+                result => return vec![result.into(); tested_anses.len()],
             };
-            all_results.push(AnsAndEvalResult::Ok(eval_code_process_resource));
+            eval_code_exe_resources.cpu_affinity = cpus[0];
+            workers.push((eval_cgroup, eval_code_exe_resources));
         }
-        all_results
+        let test_queue = TestQueue::new(tested_anses.len(), &options.stop_policy);
+        let workers = workers
+            .into_iter()
+            .map(|(mut eval_cgroup, mut eval_code_exe_resources)| {
+                let test_queue = &test_queue;
+                async move {
+                    let mut indexes = vec![];
+                    let mut all_results = vec![];
+                    while let Some(index) = test_queue.take() {
+                        indexes.push(index);
+                        if test_queue.is_skipped(index) {
+                            all_results.push(AnsAndEvalResult::Skipped);
+                            continue;
+                        }
+                        let result = async {
+                            let (tested_ans, std_ans) = (&tested_anses[index], &std_anses[index]);
+                            match eval_code_exe_resources
+                                .run_eval(
+                                    None,
+                                    tested_ans,
+                                    std_ans,
+                                    options.testlib,
+                                    &mut eval_cgroup,
+                                    eval_limits.run_limits(options),
+                                )
+                                .await
+                            {
+                                result::RunToEndResult::Ok(result) => match eval_code_exe_resources
+                                    .eval_verdict(&result, options.testlib, options.output_limit)
+                                    .await
+                                {
+                                    Err(result) => AnsAndEvalResult::InternalError(result),
+                                    Ok(eval_verdict) => AnsAndEvalResult::Ok(result, eval_verdict),
+                                },
+                                result => result.into(),
+                            }
+                        }
+                        .await;
+                        test_queue.report(index, result.is_accepted());
+                        all_results.push(result);
+                    }
+                    (indexes, all_results)
+                }
+            })
+            .collect();
+        in_order(join_all(workers).await, tested_anses.len())
     }
 }
 
@@ -431,39 +779,43 @@ pub struct RunAndInteract;
 impl RunAndInteract {
     pub async fn single(
         tested_code: &RawCode,
-        tested_code_time_limit: TimeSpan,
-        tested_code_memory_limit: MemorySize,
-        tested_code_uid: u32,
+        tested_limits: &ProgramLimits,
         interactor_code: &RawCode,
-        interactor_code_extra_time_limit: TimeSpan,
-        interactor_code_memory_limit: MemorySize,
-        interactor_code_uid: u32,
+        interactor_limits: &ProgramLimits,
         interactor_code_input: &Vec<u8>,
-        output_limit: MemorySize,
+        transcript_limit: Option<MemorySize>,
+        options: &RunOptions<'_>,
     ) -> RunAndInteractResult {
-        let mut tested_cgroup = match Cgroup::new_tmp(tested_code_memory_limit) {
-            Ok(result) => result,
-            Err(result) => return RunAndInteractResult::InternalError(result),
-        };
-        let mut interactor_cgroup = match Cgroup::new_tmp(interactor_code_memory_limit) {
+        let mut tested_cgroup =
+            match Cgroup::new_tmp(tested_limits.memory_limit, tested_limits.process_limit) {
+                Ok(result) => result,
+                Err(result) => return RunAndInteractResult::InternalError(result),
+            };
+        let mut interactor_cgroup = match Cgroup::new_tmp(
+            interactor_limits.memory_limit,
+            interactor_limits.process_limit,
+        ) {
             Ok(result) => result,
             Err(result) => return RunAndInteractResult::InternalError(result),
         };
-        let exe_tested_code = match tested_code.compile().await {
+        let exe_tested_code = match compile(tested_code, tested_limits.uid, options.cache).await {
             CompileResult::Ok(result) => result,
             result => return result.into(),
         };
-        let exe_interactor_code = match interactor_code.compile().await {
-            CompileResult::Ok(result) => result,
-            result => return RunAndInteractResult::from(result).to_interactor(),
-        };
-        let mut tested_code_exe_resources =
-            match exe_tested_code.initial_exe_resources(tested_code_uid).await {
-                InitExeResourceResult::Ok(result) => result,
-                result => return result.into(),
+        let exe_interactor_code =
+            match compile(interactor_code, interactor_limits.uid, options.cache).await {
+                CompileResult::Ok(result) => result,
+                result => return RunAndInteractResult::from(result).to_interactor(),
             };
+        let mut tested_code_exe_resources = match exe_tested_code
+            .initial_exe_resources(tested_limits.uid, options.jail_mounts)
+            .await
+        {
+            InitExeResourceResult::Ok(result) => result,
+            result => return result.into(),
+        };
         let mut interactor_code_exe_resources = match exe_interactor_code
-            .initial_exe_resources(interactor_code_uid)
+            .initial_exe_resources(interactor_limits.uid, options.jail_mounts)
             .await
         {
             InitExeResourceResult::Ok(result) => result,
@@ -472,12 +824,13 @@ impl RunAndInteract {
         tested_code_exe_resources
             .run_with_interactor(
                 &mut tested_cgroup,
-                tested_code_time_limit,
+                tested_limits.run_limits(options),
                 &mut interactor_code_exe_resources,
                 &mut interactor_cgroup,
-                interactor_code_extra_time_limit,
+                interactor_limits.time_limit,
                 interactor_code_input,
-                output_limit,
+                options.testlib,
+                transcript_limit,
             )
             .await
             .into()
@@ -485,79 +838,757 @@ impl RunAndInteract {
 
     pub async fn multiple(
         tested_code: &RawCode,
-        tested_code_time_limit: TimeSpan,
-        tested_code_memory_limit: MemorySize,
-        tested_code_uid: u32,
+        tested_limits: &ProgramLimits,
         interactor_code: &RawCode,
-        interactor_code_extra_time_limit: TimeSpan,
-        interactor_code_memory_limit: MemorySize,
-        interactor_code_uid: u32,
+        interactor_limits: &ProgramLimits,
         interactor_code_inputs: &Vec<Vec<u8>>,
-        output_limit: MemorySize,
+        transcript_limit: Option<MemorySize>,
+        options: &RunOptions<'_>,
     ) -> Vec<RunAndInteractResult> {
-        let mut tested_cgroup = match Cgroup::new_tmp(tested_code_memory_limit) {
-            Ok(result) => result,
-            Err(result) => {
-                return vec![
-                    RunAndInteractResult::InternalError(result);
-                    interactor_code_inputs.len()
-                ]
-            }
+        let exe_tested_code = match compile(tested_code, tested_limits.uid, options.cache).await {
+            CompileResult::Ok(result) => result,
+            result => return vec![result.into(); interactor_code_inputs.len()],
+        };
+        let exe_interactor_code =
+            match compile(interactor_code, interactor_limits.uid, options.cache).await {
+                CompileResult::Ok(result) => result,
+                result => {
+                    return vec![
+                        RunAndInteractResult::from(result).to_interactor();
+                        interactor_code_inputs.len()
+                    ]
+                }
+            };
+        let mut workers = vec![];
+        // The tested code and the interactor run at the same time, so every
+        // worker needs a core for each of them.
+        for cpus in worker_cpus(
+            worker_count(options.concurrency, interactor_code_inputs.len()),
+            2,
+        ) {
+            let tested_cgroup =
+                match Cgroup::new_tmp(tested_limits.memory_limit, tested_limits.process_limit) {
+                    Ok(result) => result,
+                    Err(result) => {
+                        return vec![
+                            RunAndInteractResult::InternalError(result);
+                            interactor_code_inputs.len()
+                        ]
+                    }
+                };
+            let interactor_cgroup = match Cgroup::new_tmp(
+                interactor_limits.memory_limit,
+                interactor_limits.process_limit,
+            ) {
+                Ok(result) => result,
+                Err(result) => {
+                    return vec![
+                        RunAndInteractResult::InternalError(result);
+                        interactor_code_inputs.len()
+                    ]
+                }
+            };
+            let mut tested_code_exe_resources = match exe_tested_code
+                .initial_exe_resources(tested_limits.uid, options.jail_mounts)
+                .await
+            {
+                InitExeResourceResult::Ok(result) => result,
+                result => return vec![result.into(); interactor_code_inputs.len()],
+            };
+            let mut interactor_code_exe_resources = match exe_interactor_code
+                .initial_exe_resources(interactor_limits.uid, options.jail_mounts)
+                .await
+            {
+                InitExeResourceResult::Ok(result) => result,
+                result => {
+                    return vec![
+                        RunAndInteractResult::from(result).to_interactor();
+                        interactor_code_inputs.len()
+                    ]
+                }
+            };
+            tested_code_exe_resources.cpu_affinity = cpus[0];
+            interactor_code_exe_resources.cpu_affinity = cpus[1];
+            workers.push((
+                tested_cgroup,
+                interactor_cgroup,
+                tested_code_exe_resources,
+                interactor_code_exe_resources,
+            ));
+        }
+        let test_queue = TestQueue::new(interactor_code_inputs.len(), &options.stop_policy);
+        let workers = workers
+            .into_iter()
+            .map(
+                |(
+                    mut tested_cgroup,
+                    mut interactor_cgroup,
+                    mut tested_code_exe_resources,
+                    mut interactor_code_exe_resources,
+                )| {
+                    let test_queue = &test_queue;
+                    async move {
+                        let mut indexes = vec![];
+                        let mut all_results = vec![];
+                        while let Some(index) = test_queue.take() {
+                            indexes.push(index);
+                            if test_queue.is_skipped(index) {
+                                all_results.push(RunAndInteractResult::Skipped);
+                                continue;
+                            }
+                            let result = tested_code_exe_resources
+                                .run_with_interactor(
+                                    &mut tested_cgroup,
+                                    tested_limits.run_limits(options),
+                                    &mut interactor_code_exe_resources,
+                                    &mut interactor_cgroup,
+                                    interactor_limits.time_limit,
+                                    &interactor_code_inputs[index],
+                                    options.testlib,
+                                    transcript_limit,
+                                )
+                                .await;
+                            let result = RunAndInteractResult::from(result);
+                            test_queue.report(index, result.is_accepted());
+                            all_results.push(result);
+                        }
+                        (indexes, all_results)
+                    }
+                },
+            )
+            .collect();
+        in_order(join_all(workers).await, interactor_code_inputs.len())
+    }
+}
+
+// The manager talks to instance_count instances of the tested program at
+// once. For the manager, time_limit is the time it may keep running after the
+// last instance has ended and cpu_time_limit is not used.
+pub struct RunWithManager;
+
+impl RunWithManager {
+    pub async fn single(
+        tested_code: &RawCode,
+        tested_limits: &ProgramLimits,
+        instance_count: usize,
+        manager_code: &RawCode,
+        manager_limits: &ProgramLimits,
+        manager_input: &[u8],
+        options: &RunOptions<'_>,
+    ) -> RunAndManageResult {
+        let exe_tested_code = match compile(tested_code, tested_limits.uid, options.cache).await {
+            CompileResult::Ok(result) => result,
+            result => return result.into(),
         };
-        let mut interactor_cgroup = match Cgroup::new_tmp(interactor_code_memory_limit) {
+        let exe_manager_code = match compile(manager_code, manager_limits.uid, options.cache).await
+        {
+            CompileResult::Ok(result) => result,
+            result => return RunAndManageResult::from(result).to_manager(),
+        };
+        let (mut manager_cgroup, mut manager_exe_resources, mut instances) = match manager_worker(
+            &exe_tested_code,
+            tested_limits,
+            instance_count,
+            &exe_manager_code,
+            manager_limits,
+            &vec![None; instance_count + 1],
+            options,
+        )
+        .await
+        {
+            Err(result) => return result,
             Ok(result) => result,
-            Err(result) => {
-                return vec![
-                    RunAndInteractResult::InternalError(result);
-                    interactor_code_inputs.len()
-                ]
-            }
         };
-        let exe_tested_code = match tested_code.compile().await {
+        manager_exe_resources
+            .run_with_manager(
+                &mut manager_cgroup,
+                manager_limits.time_limit,
+                manager_input,
+                &mut instances,
+                manager_limits.run_limits(options),
+            )
+            .await
+            .into()
+    }
+
+    pub async fn multiple(
+        tested_code: &RawCode,
+        tested_limits: &ProgramLimits,
+        instance_count: usize,
+        manager_code: &RawCode,
+        manager_limits: &ProgramLimits,
+        manager_inputs: &[Vec<u8>],
+        options: &RunOptions<'_>,
+    ) -> Vec<RunAndManageResult> {
+        let exe_tested_code = match compile(tested_code, tested_limits.uid, options.cache).await {
             CompileResult::Ok(result) => result,
-            result => return vec![result.into(); interactor_code_inputs.len()],
+            result => return vec![result.into(); manager_inputs.len()],
         };
-        let exe_interactor_code = match interactor_code.compile().await {
+        let exe_manager_code = match compile(manager_code, manager_limits.uid, options.cache).await
+        {
             CompileResult::Ok(result) => result,
             result => {
-                return vec![
-                    RunAndInteractResult::from(result).to_interactor();
-                    interactor_code_inputs.len()
-                ]
+                return vec![RunAndManageResult::from(result).to_manager(); manager_inputs.len()]
             }
         };
-        let mut tested_code_exe_resources =
-            match exe_tested_code.initial_exe_resources(tested_code_uid).await {
-                InitExeResourceResult::Ok(result) => result,
-                result => return vec![result.into(); interactor_code_inputs.len()],
+        let mut workers = vec![];
+        // The manager and all of the instances run at the same time, so every
+        // worker needs a core for each of them.
+        for cpus in worker_cpus(
+            worker_count(options.concurrency, manager_inputs.len()),
+            instance_count + 1,
+        ) {
+            match manager_worker(
+                &exe_tested_code,
+                tested_limits,
+                instance_count,
+                &exe_manager_code,
+                manager_limits,
+                &cpus,
+                options,
+            )
+            .await
+            {
+                Err(result) => return vec![result; manager_inputs.len()],
+                Ok(result) => workers.push(result),
+            }
+        }
+        let test_queue = TestQueue::new(manager_inputs.len(), &options.stop_policy);
+        let workers = workers
+            .into_iter()
+            .map(
+                |(mut manager_cgroup, mut manager_exe_resources, mut instances)| {
+                    let test_queue = &test_queue;
+                    async move {
+                        let mut indexes = vec![];
+                        let mut all_results = vec![];
+                        while let Some(index) = test_queue.take() {
+                            indexes.push(index);
+                            if test_queue.is_skipped(index) {
+                                all_results.push(RunAndManageResult::Skipped);
+                                continue;
+                            }
+                            let result = manager_exe_resources
+                                .run_with_manager(
+                                    &mut manager_cgroup,
+                                    manager_limits.time_limit,
+                                    &manager_inputs[index],
+                                    &mut instances,
+                                    manager_limits.run_limits(options),
+                                )
+                                .await;
+                            let result = RunAndManageResult::from(result);
+                            test_queue.report(index, result.is_accepted());
+                            all_results.push(result);
+                        }
+                        (indexes, all_results)
+                    }
+                },
+            )
+            .collect();
+        in_order(join_all(workers).await, manager_inputs.len())
+    }
+}
+
+// Sets up the manager and the instances of one worker. The manager takes the
+// first of the cpus and the instances the rest.
+async fn manager_worker(
+    exe_tested_code: &ExeCode,
+    tested_limits: &ProgramLimits,
+    instance_count: usize,
+    exe_manager_code: &ExeCode,
+    manager_limits: &ProgramLimits,
+    cpus: &[Option<usize>],
+    options: &RunOptions<'_>,
+) -> Result<(Cgroup, ExeResources, Vec<(ExeResources, Cgroup, RunLimits)>), RunAndManageResult> {
+    let manager_cgroup =
+        match Cgroup::new_tmp(manager_limits.memory_limit, manager_limits.process_limit) {
+            Ok(result) => result,
+            Err(result) => return Err(RunAndManageResult::InternalError(result)),
+        };
+    let mut manager_exe_resources = match exe_manager_code
+        .initial_exe_resources(manager_limits.uid, options.jail_mounts)
+        .await
+    {
+        InitExeResourceResult::Ok(result) => *result,
+        result => return Err(RunAndManageResult::from(result).to_manager()),
+    };
+    manager_exe_resources.cpu_affinity = cpus[0];
+    let mut instances = vec![];
+    for cpu in &cpus[1..] {
+        let instance_cgroup =
+            match Cgroup::new_tmp(tested_limits.memory_limit, tested_limits.process_limit) {
+                Ok(result) => result,
+                Err(result) => return Err(RunAndManageResult::InternalError(result)),
             };
-        let mut interactor_code_exe_resources = match exe_interactor_code
-            .initial_exe_resources(interactor_code_uid)
+        let mut instance_exe_resources = match exe_tested_code
+            .initial_exe_resources(tested_limits.uid, options.jail_mounts)
             .await
         {
-            InitExeResourceResult::Ok(result) => result,
-            result => {
-                return vec![
-                    RunAndInteractResult::from(result).to_interactor();
-                    interactor_code_inputs.len()
-                ]
+            InitExeResourceResult::Ok(result) => *result,
+            result => return Err(result.into()),
+        };
+        instance_exe_resources.cpu_affinity = *cpu;
+        instances.push((
+            instance_exe_resources,
+            instance_cgroup,
+            tested_limits.run_limits(options),
+        ));
+    }
+    debug_assert_eq!(instances.len(), instance_count);
+    Ok((manager_cgroup, manager_exe_resources, instances))
+}
+
+// One run of the tested program in RunInStages, under limits of its own. The
+// output of a stage, after its transformer if it has one, is the input of the
+// next stage.
+#[derive(Debug, Clone)]
+pub struct Stage<'a> {
+    pub time_limit: TimeSpan,
+    pub cpu_time_limit: TimeSpan,
+    pub memory_limit: MemorySize,
+    pub process_limit: usize,
+    pub transformer: Option<Transformer<'a>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Transformer<'a> {
+    pub code: &'a RawCode,
+    pub time_limit: TimeSpan,
+    pub cpu_time_limit: TimeSpan,
+    pub memory_limit: MemorySize,
+    pub process_limit: usize,
+}
+
+// Every stage gets exe resources of its own, so that a run cannot read what
+// an earlier run of the same test left in its directory.
+struct StageRunner {
+    cgroup: Cgroup,
+    exe_resources: ExeResources,
+    transformer: Option<(Cgroup, ExeResources)>,
+}
+
+async fn compile_transformers(
+    stages: &[Stage<'_>],
+    transformer_uid: u32,
+    cache: Option<&CompileCache>,
+) -> Result<Vec<Option<ExeCode>>, RunInStagesResult> {
+    let mut exe_transformer_codes = vec![];
+    for stage in stages {
+        match &stage.transformer {
+            None => exe_transformer_codes.push(None),
+            Some(transformer) => match compile(transformer.code, transformer_uid, cache).await {
+                CompileResult::Ok(result) => exe_transformer_codes.push(Some(*result)),
+                result => return Err(RunInStagesResult::from(result).to_transformer()),
+            },
+        }
+    }
+    Ok(exe_transformer_codes)
+}
+
+async fn stage_runners(
+    exe_tested_code: &ExeCode,
+    tested_code_uid: u32,
+    exe_transformer_codes: &[Option<ExeCode>],
+    transformer_uid: u32,
+    stages: &[Stage<'_>],
+    jail_mounts: Option<&Vec<String>>,
+    cpu: Option<usize>,
+) -> Result<Vec<StageRunner>, RunInStagesResult> {
+    let mut runners = vec![];
+    for (stage, exe_transformer_code) in stages.iter().zip(exe_transformer_codes.iter()) {
+        let cgroup = match Cgroup::new_tmp(stage.memory_limit, stage.process_limit) {
+            Ok(result) => result,
+            Err(result) => return Err(RunInStagesResult::InternalError(result)),
+        };
+        let mut exe_resources = match exe_tested_code
+            .initial_exe_resources(tested_code_uid, jail_mounts)
+            .await
+        {
+            InitExeResourceResult::Ok(result) => *result,
+            result => return Err(result.into()),
+        };
+        exe_resources.cpu_affinity = cpu;
+        let transformer = match (&stage.transformer, exe_transformer_code) {
+            (Some(transformer), Some(exe_transformer_code)) => {
+                let cgroup =
+                    match Cgroup::new_tmp(transformer.memory_limit, transformer.process_limit) {
+                        Ok(result) => result,
+                        Err(result) => return Err(RunInStagesResult::InternalError(result)),
+                    };
+                let mut exe_resources = match exe_transformer_code
+                    .initial_exe_resources(transformer_uid, jail_mounts)
+                    .await
+                {
+                    InitExeResourceResult::Ok(result) => *result,
+                    result => return Err(RunInStagesResult::from(result).to_transformer()),
+                };
+                exe_resources.cpu_affinity = cpu;
+                Some((cgroup, exe_resources))
             }
+            _ => None,
         };
-        let mut all_results = vec![];
-        for interactor_code_input in interactor_code_inputs {
-            let result = tested_code_exe_resources
-                .run_with_interactor(
-                    &mut tested_cgroup,
-                    tested_code_time_limit,
-                    &mut interactor_code_exe_resources,
-                    &mut interactor_cgroup,
-                    interactor_code_extra_time_limit,
-                    interactor_code_input,
-                    output_limit,
+        runners.push(StageRunner {
+            cgroup,
+            exe_resources,
+            transformer,
+        });
+    }
+    Ok(runners)
+}
+
+async fn run_stages(
+    runners: &mut [StageRunner],
+    stages: &[Stage<'_>],
+    input: &Vec<u8>,
+    options: &RunOptions<'_>,
+) -> RunInStagesResult {
+    let mut stage_resources = vec![];
+    let mut next_input = None;
+    for (runner, stage) in runners.iter_mut().zip(stages.iter()) {
+        let result = runner
+            .exe_resources
+            .run_to_end(
+                next_input.as_ref().unwrap_or(input),
+                &mut runner.cgroup,
+                RunLimits {
+                    time_limit: stage.time_limit,
+                    cpu_time_limit: stage.cpu_time_limit,
+                    output_limit: options.output_limit,
+                    network_isolation: options.network_isolation,
+                },
+            )
+            .await;
+        if let Err(result) = RunInStagesResult::push_stage(&mut stage_resources, result, false) {
+            return result;
+        }
+        let stage_resource = stage_resources.last().unwrap();
+        let mut output = stage_resource.tested.stdout.clone();
+        if let (Some((cgroup, exe_resources)), Some(transformer)) =
+            (&mut runner.transformer, &stage.transformer)
+        {
+            let result = exe_resources
+                .run_to_end(
+                    &output,
+                    cgroup,
+                    RunLimits {
+                        time_limit: transformer.time_limit,
+                        cpu_time_limit: transformer.cpu_time_limit,
+                        output_limit: options.output_limit,
+                        network_isolation: options.network_isolation,
+                    },
                 )
                 .await;
-            all_results.push(result.into());
+            if let Err(result) = RunInStagesResult::push_stage(&mut stage_resources, result, true) {
+                return result;
+            }
+            output = match &stage_resources.last().unwrap().transformer {
+                None => vec![],
+                Some(result) => result.stdout.clone(),
+            };
+        }
+        next_input = Some(output);
+    }
+    RunInStagesResult::Ok(stage_resources)
+}
+
+// Runs the tested program once per stage, as in problems where a first run
+// encodes something that a second run has to decode.
+pub struct RunInStages;
+
+impl RunInStages {
+    pub async fn single(
+        tested_code: &RawCode,
+        tested_code_uid: u32,
+        stages: &[Stage<'_>],
+        transformer_uid: u32,
+        input: &Vec<u8>,
+        options: &RunOptions<'_>,
+    ) -> RunInStagesResult {
+        let exe_tested_code = match compile(tested_code, tested_code_uid, options.cache).await {
+            CompileResult::Ok(result) => result,
+            result => return result.into(),
+        };
+        let exe_transformer_codes =
+            match compile_transformers(stages, transformer_uid, options.cache).await {
+                Ok(result) => result,
+                Err(result) => return result,
+            };
+        let mut runners = match stage_runners(
+            &exe_tested_code,
+            tested_code_uid,
+            &exe_transformer_codes,
+            transformer_uid,
+            stages,
+            options.jail_mounts,
+            None,
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(result) => return result,
+        };
+        run_stages(&mut runners, stages, input, options).await
+    }
+
+    pub async fn multiple(
+        tested_code: &RawCode,
+        tested_code_uid: u32,
+        stages: &[Stage<'_>],
+        transformer_uid: u32,
+        inputs: &[Vec<u8>],
+        options: &RunOptions<'_>,
+    ) -> Vec<RunInStagesResult> {
+        let exe_tested_code = match compile(tested_code, tested_code_uid, options.cache).await {
+            CompileResult::Ok(result) => result,
+            result => return vec![result.into(); inputs.len()],
+        };
+        let exe_transformer_codes =
+            match compile_transformers(stages, transformer_uid, options.cache).await {
+                Ok(result) => result,
+                Err(result) => return vec![result; inputs.len()],
+            };
+        let mut workers = vec![];
+        // The stages take turns, so they share a core.
+        for cpus in worker_cpus(worker_count(options.concurrency, inputs.len()), 1) {
+            match stage_runners(
+                &exe_tested_code,
+                tested_code_uid,
+                &exe_transformer_codes,
+                transformer_uid,
+                stages,
+                options.jail_mounts,
+                cpus[0],
+            )
+            .await
+            {
+                Ok(result) => workers.push(result),
+                Err(result) => return vec![result; inputs.len()],
+            }
+        }
+        let test_queue = TestQueue::new(inputs.len(), &options.stop_policy);
+        let workers = workers
+            .into_iter()
+            .map(|mut runners| {
+                let test_queue = &test_queue;
+                async move {
+                    let mut indexes = vec![];
+                    let mut all_results = vec![];
+                    while let Some(index) = test_queue.take() {
+                        indexes.push(index);
+                        if test_queue.is_skipped(index) {
+                            all_results.push(RunInStagesResult::Skipped);
+                            continue;
+                        }
+                        let result =
+                            run_stages(&mut runners, stages, &inputs[index], options).await;
+                        test_queue.report(index, result.is_accepted());
+                        all_results.push(result);
+                    }
+                    (indexes, all_results)
+                }
+            })
+            .collect();
+        in_order(join_all(workers).await, inputs.len())
+    }
+}
+
+async fn stress_program(
+    code: &RawCode,
+    limits: &ProgramLimits,
+    options: &RunOptions<'_>,
+    program: StressProgram,
+) -> Result<(Cgroup, ExeResources), StressResult> {
+    let cgroup = match Cgroup::new_tmp(limits.memory_limit, limits.process_limit) {
+        Ok(result) => result,
+        Err(result) => {
+            return Err(StressResult::ProgramError(
+                program,
+                None,
+                OnlyRunResult::InternalError(result),
+            ))
+        }
+    };
+    let exe_code = match compile(code, limits.uid, options.cache).await {
+        CompileResult::Ok(result) => result,
+        result => return Err(StressResult::ProgramError(program, None, result.into())),
+    };
+    match exe_code
+        .initial_exe_resources(limits.uid, options.jail_mounts)
+        .await
+    {
+        InitExeResourceResult::Ok(result) => Ok((cgroup, *result)),
+        result => Err(StressResult::ProgramError(program, None, result.into())),
+    }
+}
+
+// Looks for an input a candidate gets wrong. The generator reads a seed on
+// stdin and prints an input, which the reference and the candidate both run
+// on, and the checker compares their outputs as AnsAndEval does. Seeds go up
+// from the first one until the iterations or the time budget run out, or a
+// failure is found. After that up to shrink_iterations more seeds are tried,
+// keeping the failure with the shortest input. The generator, the reference
+// and the checker are trusted and share a set of limits, so one Stress can be
+// run against every candidate of a problem.
+pub struct Stress<'a> {
+    pub generator_code: &'a RawCode,
+    pub reference_code: &'a RawCode,
+    pub eval_code: &'a RawCode,
+    pub trusted_limits: ProgramLimits,
+    pub first_seed: u64,
+    pub iterations: usize,
+    pub time_budget: TimeSpan,
+    pub shrink_iterations: usize,
+}
+
+impl Stress<'_> {
+    pub async fn run(
+        &self,
+        candidate_code: &RawCode,
+        candidate_limits: &ProgramLimits,
+        options: &RunOptions<'_>,
+    ) -> StressResult {
+        let mut programs = vec![];
+        for (code, limits, program) in [
+            (
+                self.generator_code,
+                &self.trusted_limits,
+                StressProgram::Generator,
+            ),
+            (
+                self.reference_code,
+                &self.trusted_limits,
+                StressProgram::Reference,
+            ),
+            (candidate_code, candidate_limits, StressProgram::Candidate),
+            (self.eval_code, &self.trusted_limits, StressProgram::Checker),
+        ] {
+            match stress_program(code, limits, options, program).await {
+                Ok(result) => programs.push(result),
+                Err(result) => return result,
+            }
+        }
+        let (mut eval_cgroup, mut eval_exe_resources) = programs.pop().unwrap();
+        let (mut candidate_cgroup, mut candidate_exe_resources) = programs.pop().unwrap();
+        let (mut reference_cgroup, mut reference_exe_resources) = programs.pop().unwrap();
+        let (mut generator_cgroup, mut generator_exe_resources) = programs.pop().unwrap();
+        let trusted_limits = self.trusted_limits.run_limits(options);
+        let candidate_limits = candidate_limits.run_limits(options);
+        // The budget only counts the seeds, compiling is left out.
+        let start_time = std::time::Instant::now();
+        let mut tried = 0;
+        let mut failure: Option<StressFailure> = None;
+        let mut shrink_iterations_left = self.shrink_iterations;
+        for seed in self.first_seed.. {
+            if tried >= self.iterations || TimeSpan::from(start_time.elapsed()) >= self.time_budget
+            {
+                break;
+            }
+            if failure.is_some() {
+                if shrink_iterations_left == 0 {
+                    break;
+                }
+                shrink_iterations_left -= 1;
+            }
+            tried += 1;
+            let input = match generator_exe_resources
+                .run_to_end(
+                    &format!("{}\n", seed).into_bytes(),
+                    &mut generator_cgroup,
+                    trusted_limits,
+                )
+                .await
+            {
+                RunToEndResult::Ok(result) => result.stdout,
+                result => {
+                    return StressResult::ProgramError(
+                        StressProgram::Generator,
+                        Some(seed),
+                        result.into(),
+                    )
+                }
+            };
+            if failure
+                .as_ref()
+                .is_some_and(|failure| failure.input.len() <= input.len())
+            {
+                continue;
+            }
+            let reference_output = match reference_exe_resources
+                .run_to_end(&input, &mut reference_cgroup, trusted_limits)
+                .await
+            {
+                RunToEndResult::Ok(result) => result.stdout,
+                result => {
+                    return StressResult::ProgramError(
+                        StressProgram::Reference,
+                        Some(seed),
+                        result.into(),
+                    )
+                }
+            };
+            let candidate = OnlyRunResult::from(
+                candidate_exe_resources
+                    .run_to_end(&input, &mut candidate_cgroup, candidate_limits)
+                    .await,
+            );
+            let eval_verdict = match &candidate {
+                OnlyRunResult::Ok(candidate_resource) => {
+                    let eval_resource = match eval_exe_resources
+                        .run_eval(
+                            Some(&input),
+                            &candidate_resource.stdout,
+                            &reference_output,
+                            options.testlib,
+                            &mut eval_cgroup,
+                            trusted_limits,
+                        )
+                        .await
+                    {
+                        RunToEndResult::Ok(result) => result,
+                        result => {
+                            return StressResult::ProgramError(
+                                StressProgram::Checker,
+                                Some(seed),
+                                result.into(),
+                            )
+                        }
+                    };
+                    match eval_exe_resources
+                        .eval_verdict(&eval_resource, options.testlib, options.output_limit)
+                        .await
+                    {
+                        Ok(result) if result.is_accepted() => continue,
+                        Ok(result) => Some(result),
+                        Err(result) => {
+                            return StressResult::ProgramError(
+                                StressProgram::Checker,
+                                Some(seed),
+                                OnlyRunResult::InternalError(result),
+                            )
+                        }
+                    }
+                }
+                OnlyRunResult::InternalError(_) => {
+                    return StressResult::ProgramError(
+                        StressProgram::Candidate,
+                        Some(seed),
+                        candidate,
+                    )
+                }
+                _ => None,
+            };
+            failure = Some(StressFailure {
+                seed,
+                input,
+                reference_output,
+                candidate,
+                eval_verdict,
+            });
+        }
+        match failure {
+            None => StressResult::Passed(tried),
+            Some(failure) => StressResult::Failed(tried, failure),
         }
-        all_results
     }
 }
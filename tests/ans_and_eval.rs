@@ -3,7 +3,7 @@ use emjudge_judgecore::{
     quantity::{MemorySize, TimeSpan},
     result::AnsAndEvalResult,
     settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
-    test::AnsAndEval,
+    test::{AnsAndEval, ProgramLimits, RunOptions},
 };
 use tokio::io::AsyncReadExt;
 
@@ -33,19 +33,22 @@ async fn onepluston() {
             &eval_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_milliseconds(1000),
-        MemorySize::from_megabytes(256),
-        eval_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(256),
+            256,
+            eval_uid,
+        ),
         &outputs,
         &ans,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     assert_eq!(result.len(), 10000);
     for i in result {
         match i {
-            AnsAndEvalResult::Ok(result) => {
-                assert_eq!(result.stdout, "AC".as_bytes().to_vec());
+            AnsAndEvalResult::Ok(_, verdict) => {
+                assert!(verdict.is_accepted());
             }
             i => {
                 panic!("Unexpected result: {}", i);
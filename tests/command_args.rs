@@ -0,0 +1,31 @@
+use emjudge_judgecore::settings::turn_command_into_command_and_args;
+use std::collections::HashMap;
+
+#[test]
+fn placeholders() {
+    let placeholders = HashMap::from([
+        ("exe".to_string(), "main".to_string()),
+        ("memory_limit_mb".to_string(), "256".to_string()),
+    ]);
+    let (command, args) = turn_command_into_command_and_args(
+        "./{exe} \"${memory_limit_mb}MB\" '{exe}'",
+        &placeholders,
+    )
+    .unwrap();
+    assert_eq!(command, "./main");
+    assert_eq!(args, vec!["256MB", "{exe}"]);
+}
+
+#[test]
+fn shell_variables() {
+    let (command, args) =
+        turn_command_into_command_and_args("sh -c \"echo ${HOME}\"", &HashMap::new()).unwrap();
+    assert_eq!(command, "sh");
+    assert_eq!(args, vec!["-c", "echo ${HOME}"]);
+    let (_, args) = turn_command_into_command_and_args(
+        "sh -c 'for f in *; do echo ${f}; done' {}",
+        &HashMap::new(),
+    )
+    .unwrap();
+    assert_eq!(args, vec!["-c", "for f in *; do echo ${f}; done", "{}"]);
+}
@@ -0,0 +1,59 @@
+use emjudge_judgecore::{compare::Comparator, result::EvalStatus};
+
+#[test]
+fn exact_and_tokens() {
+    let verdict = Comparator::Exact.compare(b"1 2\n3\n", b"1 2\n3\n");
+    assert!(verdict.is_accepted());
+    assert_eq!(verdict.score, 1.0);
+    let verdict = Comparator::Exact.compare(b"1 2\n3 \n", b"1 2\n3\n");
+    assert_eq!(verdict.status, EvalStatus::WrongAnswer);
+    assert_eq!(
+        verdict.message,
+        "first difference at line 2, column 2: expected `\n`, found ` `"
+    );
+    let verdict = Comparator::Tokens.compare(b"1   2\n\n3 \n", b"1 2\n3");
+    assert!(verdict.is_accepted());
+    let verdict = Comparator::Tokens.compare(b"1 2\n3 5\n", b"1 2\n3 4\n");
+    assert_eq!(
+        verdict.message,
+        "first difference at line 2, token 2: expected `4`, found `5`"
+    );
+    let verdict = Comparator::Tokens.compare(b"1 2", b"1 2 3");
+    assert_eq!(
+        verdict.message,
+        "output ended after 2 tokens, expected `3` at line 1, token 3 of the answer"
+    );
+    let verdict = Comparator::Tokens.compare(b"1 2\n3", b"1 2");
+    assert_eq!(verdict.message, "extra token `3` at line 2, token 1");
+    let verdict = Comparator::CaseInsensitive.compare(b"Yes\nNO", b"YES\nno");
+    assert!(verdict.is_accepted());
+}
+
+#[test]
+fn lines_and_floats() {
+    let verdict = Comparator::Lines.compare(b"hello world  \nbye\n\n\n", b"hello world\nbye");
+    assert!(verdict.is_accepted());
+    let verdict = Comparator::Lines.compare(b"hello  world\nbye", b"hello world\nbye");
+    assert_eq!(
+        verdict.message,
+        "first difference at line 1: expected `hello world`, found `hello  world`"
+    );
+    let comparator = Comparator::Float {
+        absolute: 1e-6,
+        relative: 1e-6,
+    };
+    let verdict = comparator.compare(b"0.3333333 1000000.5 nan", b"0.333333333 1000000 nan");
+    assert!(verdict.is_accepted());
+    let verdict = comparator.compare(b"0.3334", b"0.3333");
+    assert_eq!(
+        verdict.message,
+        "first difference at line 1, token 1: expected `0.3333`, found `0.3334`"
+    );
+    let verdict = Comparator::UnorderedLines.compare(b"b\na\nc\n", b"a\nb\nc");
+    assert!(verdict.is_accepted());
+    let verdict = Comparator::UnorderedLines.compare(b"b\na\nd\n", b"a\nb\nc");
+    assert_eq!(verdict.message, "missing line `c`");
+    let verdict = Comparator::UnorderedLines.compare(b"b\na\nc\nd", b"a\nb\nc");
+    assert_eq!(verdict.message, "unexpected line `d` at line 4");
+    assert!(Comparator::Tokens.ans_and_eval(b"42", b"42").is_ok());
+}
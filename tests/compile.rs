@@ -0,0 +1,288 @@
+use emjudge_judgecore::{
+    cache::CompileCache,
+    program::RawCode,
+    quantity::{MemorySize, TimeSpan},
+    result::CompileResult,
+    settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
+};
+use std::collections::HashMap;
+use std::io::Write;
+use tokio::io::AsyncReadExt;
+
+#[tokio::test(flavor = "current_thread")]
+async fn compile_tle() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut code = vec![];
+    tokio::fs::File::open("examples/programs/helloworld.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut code)
+        .await
+        .unwrap();
+    let compile_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let result = RawCode::new(&code, compile_and_exe_settings.get_language("C++").unwrap())
+        .compile(
+            compile_uid,
+            TimeSpan::from_milliseconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            MemorySize::from_megabytes(1),
+        )
+        .await;
+    match result {
+        CompileResult::CompileTimeLimitExceeded(_) => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn compile_error() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut code = vec![];
+    tokio::fs::File::open("examples/programs/compile_error.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut code)
+        .await
+        .unwrap();
+    let compile_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let result = RawCode::new(&code, compile_and_exe_settings.get_language("C++").unwrap())
+        .compile(
+            compile_uid,
+            TimeSpan::from_seconds(10),
+            MemorySize::from_gigabytes(1),
+            256,
+            MemorySize::from_bytes(16),
+        )
+        .await;
+    match result {
+        CompileResult::CompileError(result) => {
+            assert!(result.len() <= 16);
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn compile_setting_error() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut compile_and_exe_setting = compile_and_exe_settings
+        .get_language("C++")
+        .unwrap()
+        .clone();
+    compile_and_exe_setting.compile_command = "g++ {source} -o {exe} -D 'NAME=a b".to_string();
+    let compile_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let result = RawCode::new(&vec![], &compile_and_exe_setting)
+        .compile(
+            compile_uid,
+            TimeSpan::from_seconds(10),
+            MemorySize::from_gigabytes(1),
+            256,
+            MemorySize::from_megabytes(1),
+        )
+        .await;
+    match result {
+        CompileResult::SettingError => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn compile_cache() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut code = vec![];
+    tokio::fs::File::open("examples/programs/helloworld.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut code)
+        .await
+        .unwrap();
+    let compile_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let code = RawCode::new(&code, compile_and_exe_settings.get_language("C++").unwrap());
+    let cache_dir = tempfile::tempdir().unwrap();
+    let cache = CompileCache::new_with_disk(
+        MemorySize::from_megabytes(64),
+        cache_dir.path().to_str().unwrap(),
+        MemorySize::from_megabytes(64),
+    )
+    .unwrap();
+    let mut exe_codes = vec![];
+    for _ in 0..2 {
+        match cache
+            .compile(
+                &code,
+                compile_uid,
+                TimeSpan::from_seconds(10),
+                MemorySize::from_gigabytes(1),
+                256,
+                MemorySize::from_megabytes(1),
+            )
+            .await
+        {
+            CompileResult::Ok(result) => exe_codes.push(result),
+            i => {
+                panic!("Unexpected result: {}", i);
+            }
+        }
+    }
+    assert_eq!(cache.miss_count(), 1);
+    assert_eq!(cache.hit_count(), 1);
+    assert_eq!(exe_codes[0].exe_files, exe_codes[1].exe_files);
+
+    // A fresh cache over the same directory picks the entry up from disk.
+    let cache = CompileCache::new_with_disk(
+        MemorySize::from_megabytes(64),
+        cache_dir.path().to_str().unwrap(),
+        MemorySize::from_megabytes(64),
+    )
+    .unwrap();
+    match cache
+        .compile(
+            &code,
+            compile_uid,
+            TimeSpan::from_seconds(10),
+            MemorySize::from_gigabytes(1),
+            256,
+            MemorySize::from_megabytes(1),
+        )
+        .await
+    {
+        CompileResult::Ok(result) => assert_eq!(result.exe_files, exe_codes[0].exe_files),
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+    assert_eq!(cache.miss_count(), 0);
+    assert_eq!(cache.hit_count(), 1);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn compile_multiple_files() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let code = tokio::fs::read("examples/programs/multiple_files/main.cpp")
+        .await
+        .unwrap();
+    let mut files = HashMap::new();
+    files.insert(
+        "lib/add.h".to_string(),
+        tokio::fs::read("examples/programs/multiple_files/lib/add.h")
+            .await
+            .unwrap(),
+    );
+    let mut compile_and_exe_setting = compile_and_exe_settings
+        .get_language("C++")
+        .unwrap()
+        .clone();
+    compile_and_exe_setting.problem_files.insert(
+        "grader.h".to_string(),
+        "examples/programs/multiple_files/grader.h".to_string(),
+    );
+    let compile_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let result = RawCode::new_with_files(&code, &files, &compile_and_exe_setting)
+        .compile(
+            compile_uid,
+            TimeSpan::from_seconds(10),
+            MemorySize::from_gigabytes(1),
+            256,
+            MemorySize::from_megabytes(16),
+        )
+        .await;
+    match result {
+        CompileResult::Ok(_) => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn compile_path_traversal() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let code = tokio::fs::read("examples/programs/helloworld.cpp")
+        .await
+        .unwrap();
+    let compile_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    for path in ["../escape.h", "/tmp/escape.h", "./main.cpp/../../escape.h"] {
+        let mut files = HashMap::new();
+        files.insert(path.to_string(), b"int x;".to_vec());
+        let result = RawCode::new_with_files(
+            &code,
+            &files,
+            compile_and_exe_settings.get_language("C++").unwrap(),
+        )
+        .compile(
+            compile_uid,
+            TimeSpan::from_seconds(10),
+            MemorySize::from_gigabytes(1),
+            256,
+            MemorySize::from_megabytes(1),
+        )
+        .await;
+        match result {
+            CompileResult::CompileError(_) => {}
+            i => {
+                panic!("Unexpected result for {}: {}", path, i);
+            }
+        }
+    }
+
+    let mut archive = vec![];
+    {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(&mut archive));
+        zip.start_file("main.cpp", zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(&code).unwrap();
+        zip.start_file("../escape.h", zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(b"int x;").unwrap();
+        zip.finish().unwrap();
+    }
+    let result = RawCode::new_with_archive(
+        &archive,
+        compile_and_exe_settings.get_language("C++").unwrap(),
+    )
+    .compile(
+        compile_uid,
+        TimeSpan::from_seconds(10),
+        MemorySize::from_gigabytes(1),
+        256,
+        MemorySize::from_megabytes(1),
+    )
+    .await;
+    match result {
+        CompileResult::CompileError(_) => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use emjudge_judgecore::{
+    quantity::ProcessResource,
+    result::{AnsAndEvalResult, EvalStatus, EvalVerdict, RunToEndResult},
+};
+
+fn eval(stdout: &str, exit_code: i32) -> ProcessResource {
+    let mut eval = ProcessResource::default();
+    eval.stdout = stdout.as_bytes().to_vec();
+    eval.exit_code = Some(exit_code);
+    eval
+}
+
+#[test]
+fn keywords() {
+    let verdict = EvalVerdict::from_process_resource(&eval("AC", 0));
+    assert_eq!(verdict.status, EvalStatus::Accepted);
+    assert_eq!(verdict.score, 1.0);
+    let verdict = EvalVerdict::from_process_resource(&eval("WA\nexpected 3, found 4\n", 0));
+    assert_eq!(verdict.status, EvalStatus::WrongAnswer);
+    assert_eq!(verdict.score, 0.0);
+    assert_eq!(verdict.message, "expected 3, found 4");
+    let verdict = EvalVerdict::from_process_resource(&eval("PARTIAL 0.5 half of the pairs\n", 0));
+    assert_eq!(verdict.status, EvalStatus::Partial);
+    assert_eq!(verdict.score, 0.5);
+    assert_eq!(verdict.message, "half of the pairs");
+    let verdict = EvalVerdict::from_process_resource(&eval("PARTIAL 2", 0));
+    assert_eq!(verdict.status, EvalStatus::Fail);
+    let verdict = EvalVerdict::from_process_resource(&eval("FAIL answer file is broken", 0));
+    assert_eq!(verdict.status, EvalStatus::Fail);
+    assert_eq!(verdict.message, "answer file is broken");
+}
+
+#[test]
+fn exit_codes() {
+    let verdict = EvalVerdict::from_process_resource(&eval("", 0));
+    assert_eq!(verdict.status, EvalStatus::Accepted);
+    let verdict = EvalVerdict::from_process_resource(&eval("WA too short", 1));
+    assert_eq!(verdict.status, EvalStatus::WrongAnswer);
+    assert_eq!(verdict.message, "too short");
+    let verdict = EvalVerdict::from_process_resource(&eval("extra blank line", 2));
+    assert_eq!(verdict.status, EvalStatus::Fail);
+    let verdict = EvalVerdict::from_process_resource(&eval("", 5));
+    assert_eq!(verdict.status, EvalStatus::Fail);
+
+    match RunToEndResult::RuntimeError(eval("", 1)).into_eval(true) {
+        RunToEndResult::Ok(_) => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+    match RunToEndResult::RuntimeError(eval("WA", 1)).into_eval(false) {
+        RunToEndResult::Ok(_) => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+    let mut killed = eval("", 1);
+    killed.signal = Some(9);
+    match RunToEndResult::RuntimeError(killed).into_eval(true) {
+        RunToEndResult::RuntimeError(_) => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[test]
+fn crashed_checker() {
+    // A script that dies of an exception exits with 1 and prints nothing,
+    // which is the checker's own error rather than a wrong answer.
+    let result = RunToEndResult::RuntimeError(eval("", 1)).into_eval(false);
+    match &result {
+        RunToEndResult::RuntimeError(_) => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+    match AnsAndEvalResult::from(result) {
+        AnsAndEvalResult::EvalRuntimeError(_) => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[test]
+fn verdict_round_trip() {
+    let result =
+        AnsAndEvalResult::from(RunToEndResult::RuntimeError(eval("WA", 1)).into_eval(false));
+    assert!(result.is_ok());
+    assert!(!result.is_accepted());
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["verdict"], "Ok");
+    assert_eq!(value["eval_verdict"]["status"], "WrongAnswer");
+    match serde_json::from_value::<AnsAndEvalResult>(value).unwrap() {
+        AnsAndEvalResult::Ok(_, verdict) => {
+            assert_eq!(verdict.status, EvalStatus::WrongAnswer);
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[test]
+fn testlib_report() {
+    let report = "<?xml version=\"1.0\" encoding=\"windows-1251\"?><result outcome = \"points\" points = \"0.25\">1 of 4 &lt;queries&gt; &amp; more</result>";
+    let verdict = EvalVerdict::from_testlib(&eval("", 7), report);
+    assert_eq!(verdict.status, EvalStatus::Partial);
+    assert_eq!(verdict.score, 0.25);
+    assert_eq!(verdict.message, "1 of 4 <queries> & more");
+    let report = "<result outcome = \"wrong-answer\">1st numbers differ - expected: '3', found: '4'</result>";
+    let verdict = EvalVerdict::from_testlib(&eval("", 1), report);
+    assert_eq!(verdict.status, EvalStatus::WrongAnswer);
+    assert_eq!(
+        verdict.message,
+        "1st numbers differ - expected: '3', found: '4'"
+    );
+    let verdict = EvalVerdict::from_testlib(&eval("", 0), "<result outcome=\"accepted\"/>");
+    assert_eq!(verdict.status, EvalStatus::Accepted);
+    assert_eq!(verdict.score, 1.0);
+    let verdict = EvalVerdict::from_testlib(
+        &eval("", 7),
+        "<result outcome = \"points\" points = \"3\"/>",
+    );
+    assert_eq!(verdict.status, EvalStatus::Fail);
+    let verdict = EvalVerdict::from_testlib(&eval("", 0), "<result outcome = \"dirt\"/>");
+    assert_eq!(verdict.status, EvalStatus::Fail);
+}
+
+#[test]
+fn testlib_exit_codes() {
+    let mut process = eval("", 7);
+    process.stderr = b"points 0.5 half of the queries\n".to_vec();
+    let verdict = EvalVerdict::from_testlib(&process, "");
+    assert_eq!(verdict.status, EvalStatus::Partial);
+    assert_eq!(verdict.score, 0.5);
+    assert_eq!(verdict.message, "half of the queries");
+    let mut process = eval("", 8);
+    process.stderr = b"wrong answer Unexpected EOF in the participants output".to_vec();
+    let verdict = EvalVerdict::from_testlib(&process, "");
+    assert_eq!(verdict.status, EvalStatus::WrongAnswer);
+    assert_eq!(
+        verdict.message,
+        "wrong answer Unexpected EOF in the participants output"
+    );
+    let verdict = EvalVerdict::from_testlib(&eval("", 3), "");
+    assert_eq!(verdict.status, EvalStatus::Fail);
+}
@@ -10,3 +10,29 @@ fn language_info() {
         println!("{}:\n{}", language, setting);
     }
 }
+
+#[test]
+fn invalid_settings() {
+    let result = emjudge_judgecore::settings::CompileAndExeSettings::load_from_string(
+        r#"
+[languages.Broken]
+exe_files = ["main"]
+exe_command = "./main 'unterminated"
+language_info_command = "echo broken"
+"#
+        .to_string(),
+        config::FileFormat::Toml,
+    );
+    match result {
+        Err(result) => {
+            assert_eq!(
+                result.kind(),
+                emjudge_judgecore::error::JudgeErrorKind::Setting
+            );
+            assert!(!result.is_retryable());
+        }
+        Ok(_) => {
+            panic!("Unexpected result: Ok");
+        }
+    }
+}
@@ -1,9 +1,9 @@
 use emjudge_judgecore::{
     program::RawCode,
     quantity::{MemorySize, TimeSpan},
-    result::OnlyRunResult,
+    result::{OnlyRunResult, TimeLimitKind},
     settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
-    test::OnlyRun,
+    test::{OnlyRun, ProgramLimits, RunOptions, StopPolicy},
 };
 use tokio::io::AsyncReadExt;
 
@@ -29,11 +29,14 @@ async fn mle() {
             &tested_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_milliseconds(1000),
-        MemorySize::from_megabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(1),
+            256,
+            code_uid,
+        ),
         &inputs,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     assert_eq!(result.len(), 100);
@@ -71,17 +74,68 @@ async fn just_return() {
             &tested_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_milliseconds(100),
-        MemorySize::from_megabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(100),
+            MemorySize::from_megabytes(1),
+            256,
+            code_uid,
+        ),
         &inputs,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     assert_eq!(result.len(), 1000);
     for i in result {
         match i {
-            OnlyRunResult::Ok(_) => {}
+            OnlyRunResult::Ok(result) => {
+                assert_eq!(result.exit_code, Some(0));
+            }
+            i => {
+                panic!("Unexpected result: {}", i);
+            }
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn segfault() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    let inputs = vec![vec![]; 100];
+    tokio::fs::File::open("examples/programs/segfault.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+
+    let result = OnlyRun::multiple(
+        &RawCode::new(
+            &tested_script,
+            compile_and_exe_settings.get_language("C++").unwrap(),
+        ),
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(256),
+            256,
+            code_uid,
+        ),
+        &inputs,
+        &RunOptions::new(MemorySize::from_megabytes(10)),
+    )
+    .await;
+    assert_eq!(result.len(), 100);
+    for i in result {
+        match i {
+            OnlyRunResult::RuntimeError(result) => {
+                assert_eq!(result.exit_code, None);
+                assert_eq!(result.signal_name(), Some("SIGSEGV".to_string()));
+            }
             i => {
                 panic!("Unexpected result: {}", i);
             }
@@ -89,6 +143,274 @@ async fn just_return() {
     }
 }
 
+#[tokio::test(flavor = "current_thread")]
+async fn process_limit() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/fork.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+
+    let result = OnlyRun::single(
+        &RawCode::new(
+            &tested_script,
+            compile_and_exe_settings.get_language("C++").unwrap(),
+        ),
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(5000),
+            MemorySize::from_megabytes(256),
+            4,
+            code_uid,
+        ),
+        &vec![],
+        &RunOptions::new(MemorySize::from_megabytes(10)),
+    )
+    .await;
+    match result {
+        OnlyRunResult::Ok(result) => {
+            assert!(result.process_limit_exceeded);
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn network_isolation() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/network.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+
+    let result = OnlyRun::single(
+        &RawCode::new(
+            &tested_script,
+            compile_and_exe_settings.get_language("C++").unwrap(),
+        ),
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(256),
+            256,
+            code_uid,
+        ),
+        &vec![],
+        &RunOptions {
+            network_isolation: true,
+            ..RunOptions::new(MemorySize::from_megabytes(10))
+        },
+    )
+    .await;
+    match result {
+        OnlyRunResult::RuntimeError(result) => {
+            assert_eq!(result.exit_code, Some(1));
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn jail() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/read_passwd.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let jail_mounts = ["/usr", "/lib", "/lib64", "/bin"]
+        .iter()
+        .filter(|x| std::path::Path::new(x).exists())
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>();
+
+    let result = OnlyRun::single(
+        &RawCode::new(
+            &tested_script,
+            compile_and_exe_settings.get_language("C++").unwrap(),
+        ),
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(256),
+            256,
+            code_uid,
+        ),
+        &vec![],
+        &RunOptions {
+            jail_mounts: Some(&jail_mounts),
+            ..RunOptions::new(MemorySize::from_megabytes(10))
+        },
+    )
+    .await;
+    match result {
+        OnlyRunResult::RuntimeError(result) => {
+            assert_eq!(result.exit_code, Some(1));
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn restricted_function() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut compile_and_exe_setting = compile_and_exe_settings
+        .get_language("C++")
+        .unwrap()
+        .clone();
+    compile_and_exe_setting.seccomp_profile = "default".to_string();
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/ptrace.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+
+    let result = OnlyRun::single(
+        &RawCode::new(&tested_script, &compile_and_exe_setting),
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(256),
+            256,
+            code_uid,
+        ),
+        &vec![],
+        &RunOptions::new(MemorySize::from_megabytes(10)),
+    )
+    .await;
+    match result {
+        OnlyRunResult::RestrictedFunction(syscall, _) => {
+            assert_eq!(syscall, libc::SYS_ptrace);
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn quoted_args() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut compile_and_exe_setting = compile_and_exe_settings
+        .get_language("C++")
+        .unwrap()
+        .clone();
+    compile_and_exe_setting.compile_command =
+        "g++ {source} -o {exe} -D 'GREETING=\"hello world\"'".to_string();
+    compile_and_exe_setting.exe_command = "./{exe} \"memory ${memory_limit_mb}MB\"".to_string();
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/quoted_args.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+
+    let result = OnlyRun::single(
+        &RawCode::new(&tested_script, &compile_and_exe_setting),
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(256),
+            256,
+            code_uid,
+        ),
+        &vec![],
+        &RunOptions::new(MemorySize::from_megabytes(10)),
+    )
+    .await;
+    match result {
+        OnlyRunResult::Ok(result) => {
+            assert_eq!(result.stdout, b"hello world memory 256MB\n".to_vec());
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn controlled_env() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut compile_and_exe_setting = compile_and_exe_settings
+        .get_language("C++")
+        .unwrap()
+        .clone();
+    compile_and_exe_setting
+        .env
+        .insert("EMJUDGE_VALUE".to_string(), "42".to_string());
+    std::env::set_var("EMJUDGE_SECRET", "1");
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/print_env.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+
+    let result = OnlyRun::single(
+        &RawCode::new(&tested_script, &compile_and_exe_setting),
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(256),
+            256,
+            code_uid,
+        ),
+        &vec![],
+        &RunOptions::new(MemorySize::from_megabytes(10)),
+    )
+    .await;
+    match result {
+        OnlyRunResult::Ok(result) => {
+            assert_eq!(result.stdout, b"42 clean\n".to_vec());
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
 #[tokio::test(flavor = "current_thread")]
 async fn tle() {
     let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
@@ -110,17 +432,20 @@ async fn tle() {
             &tested_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_milliseconds(1),
-        MemorySize::from_megabytes(1),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1),
+            MemorySize::from_megabytes(1),
+            256,
+            code_uid,
+        ),
         &inputs,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     assert_eq!(result.len(), 1000);
     for i in result {
         match i {
-            OnlyRunResult::TimeLimitExceeded(result) => {
+            OnlyRunResult::TimeLimitExceeded(_, result) => {
                 assert!(result.runtime >= TimeSpan::from_milliseconds(1))
             }
             i => {
@@ -130,6 +455,51 @@ async fn tle() {
     }
 }
 
+#[tokio::test(flavor = "current_thread")]
+async fn cpu_tle() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    let inputs = vec![vec![]; 10];
+    tokio::fs::File::open("examples/programs/loop.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let result = OnlyRun::multiple(
+        &RawCode::new(
+            &tested_script,
+            compile_and_exe_settings.get_language("C++").unwrap(),
+        ),
+        &ProgramLimits {
+            time_limit: TimeSpan::from_seconds(5),
+            cpu_time_limit: TimeSpan::from_milliseconds(100),
+            memory_limit: MemorySize::from_megabytes(1),
+            process_limit: 256,
+            uid: code_uid,
+        },
+        &inputs,
+        &RunOptions::new(MemorySize::from_megabytes(10)),
+    )
+    .await;
+    assert_eq!(result.len(), 10);
+    for i in result {
+        match i {
+            OnlyRunResult::TimeLimitExceeded(TimeLimitKind::CpuTime, result) => {
+                assert!(result.cputime >= TimeSpan::from_milliseconds(100))
+            }
+            i => {
+                panic!("Unexpected result: {}", i);
+            }
+        }
+    }
+}
+
 #[tokio::test(flavor = "current_thread")]
 async fn large_printf() {
     let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
@@ -151,11 +521,14 @@ async fn large_printf() {
             &tested_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_milliseconds(1000),
-        MemorySize::from_megabytes(256),
-        code_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(256),
+            256,
+            code_uid,
+        ),
         &inputs,
-        MemorySize::from_megabytes(1),
+        &RunOptions::new(MemorySize::from_megabytes(1)),
     )
     .await;
     assert_eq!(result.len(), 1000);
@@ -168,3 +541,150 @@ async fn large_printf() {
         }
     }
 }
+
+#[tokio::test(flavor = "current_thread")]
+async fn endless_output() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/endless_output.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let result = OnlyRun::single(
+        &RawCode::new(
+            &tested_script,
+            compile_and_exe_settings.get_language("C++").unwrap(),
+        ),
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(60),
+            MemorySize::from_megabytes(256),
+            256,
+            code_uid,
+        ),
+        &vec![],
+        &RunOptions::new(MemorySize::from_kilobytes(64)),
+    )
+    .await;
+    match result {
+        OnlyRunResult::OutputLimitExceeded(result) => {
+            assert!(result.runtime < TimeSpan::from_seconds(60));
+            assert_eq!(result.stdout, vec![b'a'; 64 * 1024]);
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn parallel_in_order() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    let inputs: Vec<Vec<u8>> = (0..100)
+        .map(|i| format!("{} {}\n", i, i).into_bytes())
+        .collect();
+    tokio::fs::File::open("examples/programs/aplusb/tested.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+
+    let result = OnlyRun::multiple(
+        &RawCode::new(
+            &tested_script,
+            compile_and_exe_settings.get_language("C++").unwrap(),
+        ),
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(64),
+            256,
+            code_uid,
+        ),
+        &inputs,
+        &RunOptions {
+            concurrency: 8,
+            ..RunOptions::new(MemorySize::from_megabytes(10))
+        },
+    )
+    .await;
+    assert_eq!(result.len(), 100);
+    for (i, result) in result.into_iter().enumerate() {
+        match result {
+            OnlyRunResult::Ok(result) => {
+                assert_eq!(result.stdout, format!("{}\n", i * 2).into_bytes());
+            }
+            result => {
+                panic!("Unexpected result: {}", result);
+            }
+        }
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn stop_policy() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    let inputs = vec![vec![]; 4];
+    tokio::fs::File::open("examples/programs/mle.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let code_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let code = RawCode::new(
+        &tested_script,
+        compile_and_exe_settings.get_language("C++").unwrap(),
+    );
+
+    for (stop_policy, is_skipped) in [
+        (StopPolicy::StopOnFirstFailure, [false, true, true, true]),
+        (
+            StopPolicy::PerGroup(vec![0, 0, 1, 1]),
+            [false, true, false, true],
+        ),
+    ] {
+        let result = OnlyRun::multiple(
+            &code,
+            &ProgramLimits::new(
+                TimeSpan::from_milliseconds(1000),
+                MemorySize::from_megabytes(1),
+                256,
+                code_uid,
+            ),
+            &inputs,
+            &RunOptions {
+                stop_policy,
+                ..RunOptions::new(MemorySize::from_megabytes(10))
+            },
+        )
+        .await;
+        assert_eq!(result.len(), 4);
+        for (result, is_skipped) in result.into_iter().zip(is_skipped) {
+            match (result, is_skipped) {
+                (OnlyRunResult::Skipped, true) => {}
+                (OnlyRunResult::MemoryLimitExceeded(_), false) => {}
+                (i, _) => {
+                    panic!("Unexpected result: {}", i);
+                }
+            }
+        }
+    }
+}
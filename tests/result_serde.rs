@@ -0,0 +1,229 @@
+use emjudge_judgecore::{
+    error::{JudgeError, JudgeErrorKind},
+    quantity::{InstancesResource, ProcessResource, TimeSpan},
+    result::{
+        EvalStatus, EvalVerdict, OnlyRunResult, RunAndEvalResult, RunAndInteractResult,
+        RunInStagesResult, RunToEndResult, RunWithManagerResult, StressFailure, StressProgram,
+        StressResult, TimeLimitKind,
+    },
+};
+
+#[test]
+fn verdict_round_trip() {
+    let mut tested = ProcessResource::default();
+    tested.runtime = TimeSpan::from_milliseconds(1500);
+    tested.stdout = b"partial".to_vec();
+    let result = OnlyRunResult::TimeLimitExceeded(TimeLimitKind::CpuTime, tested);
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["verdict"], "TimeLimitExceeded");
+    assert_eq!(value["time_limit_kind"], "CpuTime");
+    assert_eq!(value["tested"]["runtime"], "1500ms");
+    match serde_json::from_value::<OnlyRunResult>(value).unwrap() {
+        OnlyRunResult::TimeLimitExceeded(TimeLimitKind::CpuTime, result) => {
+            assert_eq!(result.stdout, b"partial".to_vec());
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[test]
+fn eval_verdict_round_trip() {
+    let result =
+        RunAndEvalResult::EvalRuntimeError(ProcessResource::default(), ProcessResource::default());
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["verdict"], "EvalRuntimeError");
+    assert!(value.get("tested").is_some());
+    assert!(value.get("eval").is_some());
+    match serde_json::from_value::<RunAndEvalResult>(value).unwrap() {
+        RunAndEvalResult::EvalRuntimeError(_, _) => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[test]
+fn internal_error_round_trip() {
+    let result = RunAndEvalResult::InternalError(JudgeError::Cgroup(
+        "cgroup_attach_task_pid() failed".to_string(),
+    ));
+    let json = serde_json::to_string(&result).unwrap();
+    match serde_json::from_str::<RunAndEvalResult>(json.as_str()).unwrap() {
+        RunAndEvalResult::InternalError(result) => {
+            assert_eq!(result.kind(), JudgeErrorKind::Cgroup);
+            assert!(result.is_retryable());
+            assert_eq!(result.to_string(), "cgroup_attach_task_pid() failed");
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+    assert!(serde_json::from_str::<RunAndEvalResult>(r#"{"verdict": "Ok"}"#).is_err());
+}
+
+#[test]
+fn skipped_round_trip() {
+    let json = serde_json::to_string(&OnlyRunResult::Skipped).unwrap();
+    assert_eq!(json, r#"{"verdict":"Skipped"}"#);
+    match serde_json::from_str::<OnlyRunResult>(json.as_str()).unwrap() {
+        OnlyRunResult::Skipped => {}
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[test]
+fn interactor_verdict_round_trip() {
+    let mut interactor = ProcessResource::default();
+    interactor.stdout = b"WA asked 21 questions".to_vec();
+    interactor.exit_code = Some(1);
+    let verdict = EvalVerdict::from_process_resource(&interactor);
+    let result = RunAndInteractResult::Ok(ProcessResource::default(), interactor, verdict);
+    assert!(result.is_ok());
+    assert!(!result.is_accepted());
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["eval_verdict"]["status"], "WrongAnswer");
+    match serde_json::from_value::<RunAndInteractResult>(value).unwrap() {
+        RunAndInteractResult::Ok(_, _, verdict) => {
+            assert_eq!(verdict.status, EvalStatus::WrongAnswer);
+            assert_eq!(verdict.message, "asked 21 questions");
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[test]
+fn manager_verdict_round_trip() {
+    let mut instance = ProcessResource::default();
+    instance.signal = Some(13);
+    let result = RunWithManagerResult::RuntimeError(
+        1,
+        InstancesResource::new(vec![ProcessResource::default(), instance]),
+        ProcessResource::default(),
+    );
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["verdict"], "RuntimeError");
+    assert_eq!(value["instance"], 1);
+    assert!(value["instances"]["total"].is_object());
+    match serde_json::from_value::<RunWithManagerResult>(value).unwrap() {
+        RunWithManagerResult::RuntimeError(i, instances, _) => {
+            assert_eq!(i, 1);
+            assert_eq!(instances.each[1].signal, Some(13));
+        }
+        i => {
+            panic!("Unexpected result: {:?}", i);
+        }
+    }
+}
+
+#[test]
+fn stages_round_trip() {
+    let mut stages = vec![];
+    let mut tested = ProcessResource::default();
+    tested.stdout = b"101010\n".to_vec();
+    assert!(RunInStagesResult::push_stage(&mut stages, RunToEndResult::Ok(tested), false).is_ok());
+    let mut transformer = ProcessResource::default();
+    transformer.exit_code = Some(1);
+    let result = match RunInStagesResult::push_stage(
+        &mut stages,
+        RunToEndResult::RuntimeError(transformer),
+        true,
+    ) {
+        Err(result) => result,
+        Ok(_) => panic!("A transformer runtime error must end the stages"),
+    };
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["verdict"], "TransformerRuntimeError");
+    match serde_json::from_value::<RunInStagesResult>(value).unwrap() {
+        RunInStagesResult::TransformerRuntimeError(stages) => {
+            assert_eq!(stages.len(), 1);
+            assert_eq!(stages[0].tested.stdout, b"101010\n".to_vec());
+            assert_eq!(stages[0].transformer.as_ref().unwrap().exit_code, Some(1));
+        }
+        i => {
+            panic!("Unexpected result: {}", i);
+        }
+    }
+}
+
+#[test]
+fn stress_round_trip() {
+    let mut candidate = ProcessResource::default();
+    candidate.stdout = b"-1948578118\n".to_vec();
+    let result = StressResult::Failed(
+        7,
+        StressFailure {
+            seed: 7,
+            input: b"976413892 1369975286\n".to_vec(),
+            reference_output: b"2346389178\n".to_vec(),
+            candidate: OnlyRunResult::Ok(candidate),
+            eval_verdict: Some(EvalVerdict::from_process_resource(&ProcessResource {
+                stdout: b"WA overflow".to_vec(),
+                ..ProcessResource::default()
+            })),
+        },
+    );
+    assert!(!result.is_ok());
+    let value = serde_json::to_string(&result).unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&value).unwrap()["verdict"],
+        "Failed"
+    );
+    let failure = serde_json::from_str::<StressResult>(&value)
+        .unwrap()
+        .unwrap();
+    assert_eq!(failure.candidate_output(), b"-1948578118\n");
+    assert_eq!(failure.message(), "overflow");
+    let failure = StressFailure {
+        candidate: OnlyRunResult::RuntimeError(ProcessResource::default()),
+        eval_verdict: None,
+        ..failure
+    };
+    assert!(failure.message().starts_with("RuntimeError("));
+    let result = StressResult::ProgramError(
+        StressProgram::Generator,
+        None,
+        OnlyRunResult::CompileError("error".to_string()),
+    );
+    let value = serde_json::to_value(&result).unwrap();
+    assert_eq!(value["verdict"], "ProgramError");
+    match serde_json::from_value::<StressResult>(value).unwrap() {
+        StressResult::ProgramError(
+            StressProgram::Generator,
+            None,
+            OnlyRunResult::CompileError(_),
+        ) => {}
+        result => panic!("Unexpected result: {}", result),
+    }
+}
+
+#[test]
+fn process_resource_old_format() {
+    let mut resource = ProcessResource::default();
+    resource.runtime = TimeSpan::from_milliseconds(500);
+    resource.stdout = b"3".to_vec();
+    let mut value = serde_json::to_value(&resource).unwrap();
+    let object = value.as_object_mut().unwrap();
+    for field in [
+        "cputime",
+        "exit_code",
+        "signal",
+        "core_dumped",
+        "process_limit_exceeded",
+        "transcript",
+    ] {
+        object.remove(field);
+    }
+    let resource = serde_json::from_value::<ProcessResource>(value).unwrap();
+    assert_eq!(resource.runtime, TimeSpan::from_milliseconds(500));
+    assert_eq!(resource.stdout, b"3");
+    assert_eq!(resource.cputime, TimeSpan::default());
+    assert_eq!(resource.exit_code, None);
+    assert!(!resource.core_dumped);
+    assert!(!resource.process_limit_exceeded);
+}
@@ -3,7 +3,7 @@ use emjudge_judgecore::{
     quantity::{MemorySize, TimeSpan},
     result::RunAndEvalResult,
     settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
-    test::RunAndEval,
+    test::{ProgramLimits, RunAndEval, RunOptions},
 };
 use tokio::io::AsyncReadExt;
 
@@ -41,26 +41,32 @@ async fn aplusb() {
             &tested_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_milliseconds(1000),
-        MemorySize::from_megabytes(256),
-        tested_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(256),
+            256,
+            tested_uid,
+        ),
         &RawCode::new(
             &eval_script,
             compile_and_exe_settings.get_language("C++").unwrap(),
         ),
-        TimeSpan::from_milliseconds(1000),
-        MemorySize::from_megabytes(256),
-        eval_uid,
+        &ProgramLimits::new(
+            TimeSpan::from_milliseconds(1000),
+            MemorySize::from_megabytes(256),
+            256,
+            eval_uid,
+        ),
         &inputs,
         &ans,
-        MemorySize::from_megabytes(10),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
     )
     .await;
     assert_eq!(result.len(), 10000);
     for i in result {
         match i {
-            RunAndEvalResult::Ok(_, eval) => {
-                assert_eq!(eval.stdout, "AC".as_bytes().to_vec());
+            RunAndEvalResult::Ok(_, _, verdict) => {
+                assert!(verdict.is_accepted());
             }
             i => {
                 panic!("Unexpected result: {}", i);
@@ -0,0 +1,249 @@
+use emjudge_judgecore::{
+    program::RawCode,
+    quantity::{MemorySize, TimeSpan, Transcript, TranscriptDirection},
+    result::{EvalStatus, RunAndInteractResult},
+    settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
+    test::{ProgramLimits, RunAndInteract, RunOptions},
+};
+use tokio::io::AsyncReadExt;
+
+fn sent(transcript: &Transcript, direction: TranscriptDirection) -> String {
+    let mut result = vec![];
+    for chunk in &transcript.chunks {
+        if chunk.direction == direction {
+            assert_eq!(chunk.offset, result.len());
+            result.extend_from_slice(&chunk.data);
+        }
+    }
+    String::from_utf8(result).unwrap()
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn transcript() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut interactor_script = vec![];
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/guessnumber/interactor.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut interactor_script)
+        .await
+        .unwrap();
+    tokio::fs::File::open("examples/programs/guessnumber/tested.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let tested_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let interactor_uid = create_a_tmp_user_return_uid("emjudge-judgecore-eval").unwrap();
+    for (transcript_limit, truncated) in [
+        (MemorySize::from_megabytes(1), false),
+        (MemorySize::from_bytes(8), true),
+    ] {
+        let result = RunAndInteract::single(
+            &RawCode::new(
+                &tested_script,
+                compile_and_exe_settings.get_language("C++").unwrap(),
+            ),
+            &ProgramLimits::new(
+                TimeSpan::from_seconds(1),
+                MemorySize::from_gigabytes(1),
+                256,
+                tested_uid,
+            ),
+            &RawCode::new(
+                &interactor_script,
+                compile_and_exe_settings.get_language("C++").unwrap(),
+            ),
+            &ProgramLimits::new(
+                TimeSpan::from_seconds(1),
+                MemorySize::from_gigabytes(1),
+                256,
+                interactor_uid,
+            ),
+            &b"1 10 3".to_vec(),
+            Some(transcript_limit),
+            &RunOptions::new(MemorySize::from_megabytes(10)),
+        )
+        .await;
+        let (tested, interactor, verdict) = match result {
+            RunAndInteractResult::Ok(tested, interactor, verdict) => (tested, interactor, verdict),
+            result => panic!("Unexpected result: {}", result),
+        };
+        assert!(verdict.is_accepted());
+        assert_eq!(verdict.message, "with 4 steps");
+        assert!(String::from_utf8_lossy(&interactor.stdout).starts_with("AC with 4 steps"));
+        let transcript = tested.transcript.unwrap();
+        assert_eq!(transcript.truncated, truncated);
+        assert!(transcript.size <= transcript_limit);
+        if !truncated {
+            assert_eq!(
+                sent(&transcript, TranscriptDirection::FromInteractor),
+                "1 10\nL\nL\nE\n"
+            );
+            assert_eq!(
+                sent(&transcript, TranscriptDirection::ToInteractor),
+                "? 1\n? 2\n? 3\n! 3\n"
+            );
+        }
+    }
+}
+
+// The tested program dies of a broken pipe once the interactor is gone, which
+// must not hide what the interactor decided.
+#[tokio::test(flavor = "current_thread")]
+async fn interactor_exits_first() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/guessnumber/tested.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let tested_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let interactor_uid = create_a_tmp_user_return_uid("emjudge-judgecore-eval").unwrap();
+    for interactor in ["wa_interactor.cpp", "crash_interactor.cpp"] {
+        let mut interactor_script = vec![];
+        tokio::fs::File::open(format!("examples/programs/guessnumber/{}", interactor))
+            .await
+            .unwrap()
+            .read_to_end(&mut interactor_script)
+            .await
+            .unwrap();
+        let result = RunAndInteract::single(
+            &RawCode::new(
+                &tested_script,
+                compile_and_exe_settings.get_language("C++").unwrap(),
+            ),
+            &ProgramLimits::new(
+                TimeSpan::from_seconds(1),
+                MemorySize::from_gigabytes(1),
+                256,
+                tested_uid,
+            ),
+            &RawCode::new(
+                &interactor_script,
+                compile_and_exe_settings.get_language("C++").unwrap(),
+            ),
+            &ProgramLimits::new(
+                TimeSpan::from_seconds(1),
+                MemorySize::from_gigabytes(1),
+                256,
+                interactor_uid,
+            ),
+            &b"1 10 3".to_vec(),
+            None,
+            &RunOptions::new(MemorySize::from_megabytes(10)),
+        )
+        .await;
+        match (interactor, result) {
+            ("wa_interactor.cpp", RunAndInteractResult::Ok(_, _, verdict)) => {
+                assert_eq!(verdict.status, EvalStatus::WrongAnswer);
+                assert_eq!(verdict.message, "no guess may be at the left end");
+            }
+            ("crash_interactor.cpp", RunAndInteractResult::InteractorRuntimeError(_, result)) => {
+                assert!(result.signal.is_some());
+            }
+            (_, result) => panic!("Unexpected result: {}", result),
+        }
+    }
+}
+
+#[tokio::test]
+async fn tested_killed_by_sigpipe() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/guessnumber/pipe_tested.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let mut interactor_script = vec![];
+    tokio::fs::File::open("examples/programs/guessnumber/wa_interactor.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut interactor_script)
+        .await
+        .unwrap();
+    let tested_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let interactor_uid = create_a_tmp_user_return_uid("emjudge-judgecore-eval").unwrap();
+    // The tested program dies of SIGPIPE right after the interactor gave its
+    // verdict, so both exits are usually seen at once.
+    for _ in 0..10 {
+        let result = RunAndInteract::single(
+            &RawCode::new(
+                &tested_script,
+                compile_and_exe_settings.get_language("C++").unwrap(),
+            ),
+            &ProgramLimits::new(
+                TimeSpan::from_seconds(1),
+                MemorySize::from_gigabytes(1),
+                256,
+                tested_uid,
+            ),
+            &RawCode::new(
+                &interactor_script,
+                compile_and_exe_settings.get_language("C++").unwrap(),
+            ),
+            &ProgramLimits::new(
+                TimeSpan::from_seconds(1),
+                MemorySize::from_gigabytes(1),
+                256,
+                interactor_uid,
+            ),
+            &b"1 10 3".to_vec(),
+            None,
+            &RunOptions::new(MemorySize::from_megabytes(10)),
+        )
+        .await;
+        match result {
+            RunAndInteractResult::Ok(tested_resource, _, verdict) => {
+                assert_eq!(tested_resource.signal, Some(libc::SIGPIPE));
+                assert_eq!(verdict.status, EvalStatus::WrongAnswer);
+            }
+            result => panic!("Unexpected result: {}", result),
+        }
+    }
+}
+
+#[test]
+fn transcript_limit() {
+    let mut transcript = Transcript::new(MemorySize::from_bytes(6));
+    transcript.record(
+        TranscriptDirection::FromInteractor,
+        TimeSpan::from_milliseconds(1),
+        0,
+        b"1 10\n",
+    );
+    transcript.record(
+        TranscriptDirection::ToInteractor,
+        TimeSpan::from_milliseconds(2),
+        0,
+        b"? 1\n",
+    );
+    transcript.record(
+        TranscriptDirection::FromInteractor,
+        TimeSpan::from_milliseconds(3),
+        5,
+        b"L\n",
+    );
+    assert!(transcript.truncated);
+    assert_eq!(transcript.size, MemorySize::from_bytes(6));
+    assert_eq!(transcript.chunks.len(), 2);
+    assert_eq!(transcript.chunks[1].data, b"?".to_vec());
+}
@@ -0,0 +1,102 @@
+use emjudge_judgecore::{
+    program::RawCode,
+    quantity::{MemorySize, TimeSpan},
+    settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
+    test::{RunInStages, RunOptions, Stage, StopPolicy, Transformer},
+};
+use tokio::io::AsyncReadExt;
+
+#[tokio::test(flavor = "current_thread")]
+async fn encode_and_decode() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut tested_script = vec![];
+    let mut transformer_script = vec![];
+    tokio::fs::File::open("examples/programs/run_twice/tested.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    tokio::fs::File::open("examples/programs/run_twice/transformer.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut transformer_script)
+        .await
+        .unwrap();
+    let tested_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let transformer_uid = create_a_tmp_user_return_uid("emjudge-judgecore-eval").unwrap();
+    let transformer_code = RawCode::new(
+        &transformer_script,
+        compile_and_exe_settings.get_language("C++").unwrap(),
+    );
+    let stages = vec![
+        Stage {
+            time_limit: TimeSpan::from_seconds(1),
+            cpu_time_limit: TimeSpan::from_seconds(1),
+            memory_limit: MemorySize::from_megabytes(256),
+            process_limit: 256,
+            transformer: Some(Transformer {
+                code: &transformer_code,
+                time_limit: TimeSpan::from_seconds(1),
+                cpu_time_limit: TimeSpan::from_seconds(1),
+                memory_limit: MemorySize::from_megabytes(256),
+                process_limit: 256,
+            }),
+        },
+        Stage {
+            time_limit: TimeSpan::from_seconds(1),
+            cpu_time_limit: TimeSpan::from_seconds(1),
+            memory_limit: MemorySize::from_megabytes(256),
+            process_limit: 256,
+            transformer: None,
+        },
+    ];
+    let tested_code = RawCode::new(
+        &tested_script,
+        compile_and_exe_settings.get_language("C++").unwrap(),
+    );
+    let result = RunInStages::single(
+        &tested_code,
+        tested_uid,
+        &stages,
+        transformer_uid,
+        &b"encode 42".to_vec(),
+        &RunOptions::new(MemorySize::from_megabytes(10)),
+    )
+    .await;
+    assert_eq!(result.output(), Some(&b"42\n".to_vec()));
+    let stage_resources = result.unwrap();
+    assert_eq!(stage_resources.len(), 2);
+    assert_eq!(stage_resources[0].tested.stdout, b"101010\n".to_vec());
+    assert_eq!(
+        stage_resources[0].transformer.as_ref().unwrap().stdout,
+        b"decode 101010\n".to_vec()
+    );
+    assert!(stage_resources[1].transformer.is_none());
+    let inputs: Vec<Vec<u8>> = (0..5)
+        .map(|i| format!("encode {}", i * 7).as_bytes().to_vec())
+        .collect();
+    let results = RunInStages::multiple(
+        &tested_code,
+        tested_uid,
+        &stages,
+        transformer_uid,
+        &inputs,
+        &RunOptions {
+            concurrency: 2,
+            stop_policy: StopPolicy::StopOnFirstFailure,
+            ..RunOptions::new(MemorySize::from_megabytes(10))
+        },
+    )
+    .await;
+    for (i, result) in results.iter().enumerate() {
+        match result.output() {
+            Some(output) => assert_eq!(output, &format!("{}\n", i * 7).as_bytes().to_vec()),
+            None => panic!("Unexpected result: {}", result),
+        }
+    }
+}
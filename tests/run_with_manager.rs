@@ -0,0 +1,180 @@
+use emjudge_judgecore::{
+    cgroup::Cgroup,
+    program::{ExeResources, RawCode, RunLimits},
+    quantity::{MemorySize, ProcessResource, TimeSpan},
+    result::{EvalStatus, RunAndManageResult, RunWithManagerResult},
+    settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
+    test::{ProgramLimits, RunOptions, RunWithManager},
+};
+use tokio::io::AsyncReadExt;
+
+async fn exe_resources(path: &str, uid: u32) -> ExeResources {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut script = vec![];
+    tokio::fs::File::open(path)
+        .await
+        .unwrap()
+        .read_to_end(&mut script)
+        .await
+        .unwrap();
+    RawCode::new(
+        &script,
+        compile_and_exe_settings.get_language("C++").unwrap(),
+    )
+    .compile(
+        uid,
+        TimeSpan::from_seconds(10),
+        MemorySize::from_gigabytes(1),
+        256,
+        MemorySize::from_megabytes(10),
+    )
+    .await
+    .unwrap()
+    .initial_exe_resources(uid, None)
+    .await
+    .unwrap()
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn communication() {
+    let tested_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let manager_uid = create_a_tmp_user_return_uid("emjudge-judgecore-eval").unwrap();
+    let mut manager =
+        exe_resources("examples/programs/communication/manager.cpp", manager_uid).await;
+    let mut manager_cgroup = Cgroup::new_tmp(MemorySize::from_gigabytes(1), 256).unwrap();
+    for (tested, status) in [
+        (
+            "examples/programs/communication/double.cpp",
+            EvalStatus::Accepted,
+        ),
+        ("examples/programs/just_return.cpp", EvalStatus::WrongAnswer),
+    ] {
+        let mut instances = vec![];
+        for _ in 0..3 {
+            instances.push((
+                exe_resources(tested, tested_uid).await,
+                Cgroup::new_tmp(MemorySize::from_gigabytes(1), 256).unwrap(),
+                RunLimits {
+                    time_limit: TimeSpan::from_seconds(1),
+                    cpu_time_limit: TimeSpan::from_seconds(1),
+                    output_limit: MemorySize::from_megabytes(10),
+                    network_isolation: false,
+                },
+            ));
+        }
+        let result = manager
+            .run_with_manager(
+                &mut manager_cgroup,
+                TimeSpan::from_seconds(1),
+                b"20",
+                &mut instances,
+                RunLimits {
+                    time_limit: TimeSpan::from_seconds(1),
+                    cpu_time_limit: TimeSpan::from_seconds(1),
+                    output_limit: MemorySize::from_megabytes(10),
+                    network_isolation: false,
+                },
+            )
+            .await;
+        let (instances, manager, verdict) = match result {
+            RunWithManagerResult::Ok(instances, manager, verdict) => (instances, manager, verdict),
+            result => panic!("Unexpected result: {:?}", result),
+        };
+        assert_eq!(instances.each.len(), 3);
+        assert_eq!(verdict.status, status);
+        match status {
+            EvalStatus::Accepted => {
+                assert_eq!(verdict.message, "with 3 instances");
+            }
+            _ => {
+                assert_eq!(verdict.message, "instance 0 did not double 20");
+            }
+        }
+        assert!(manager.exit_code.is_some());
+        assert!(instances.total.memory >= instances.each[0].memory);
+        assert!(instances.total.runtime >= instances.each[2].runtime);
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn runner() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let mut manager_script = vec![];
+    let mut tested_script = vec![];
+    tokio::fs::File::open("examples/programs/communication/manager.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut manager_script)
+        .await
+        .unwrap();
+    tokio::fs::File::open("examples/programs/communication/double.cpp")
+        .await
+        .unwrap()
+        .read_to_end(&mut tested_script)
+        .await
+        .unwrap();
+    let tested_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let manager_uid = create_a_tmp_user_return_uid("emjudge-judgecore-eval").unwrap();
+    let results = RunWithManager::multiple(
+        &RawCode::new(
+            &tested_script,
+            compile_and_exe_settings.get_language("C++").unwrap(),
+        ),
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            tested_uid,
+        ),
+        2,
+        &RawCode::new(
+            &manager_script,
+            compile_and_exe_settings.get_language("C++").unwrap(),
+        ),
+        &ProgramLimits::new(
+            TimeSpan::from_seconds(1),
+            MemorySize::from_gigabytes(1),
+            256,
+            manager_uid,
+        ),
+        &[b"20".to_vec(), b"7".to_vec()],
+        &RunOptions::new(MemorySize::from_megabytes(10)),
+    )
+    .await;
+    assert_eq!(results.len(), 2);
+    for result in results {
+        match result {
+            RunAndManageResult::Ok(instances, _, verdict) => {
+                assert_eq!(instances.each.len(), 2);
+                assert_eq!(verdict.message, "with 2 instances");
+            }
+            result => panic!("Unexpected result: {}", result),
+        }
+    }
+}
+
+#[test]
+fn total() {
+    let mut first = ProcessResource::default();
+    first.runtime = TimeSpan::from_milliseconds(300);
+    first.cputime = TimeSpan::from_milliseconds(200);
+    first.memory = MemorySize::from_megabytes(3);
+    let mut second = ProcessResource::default();
+    second.runtime = TimeSpan::from_milliseconds(500);
+    second.cputime = TimeSpan::from_milliseconds(100);
+    second.memory = MemorySize::from_megabytes(4);
+    second.process_limit_exceeded = true;
+    let total = ProcessResource::total(&[first, second]);
+    assert_eq!(total.runtime, TimeSpan::from_milliseconds(500));
+    assert_eq!(total.cputime, TimeSpan::from_milliseconds(300));
+    assert_eq!(total.memory, MemorySize::from_megabytes(7));
+    assert!(total.process_limit_exceeded);
+}
@@ -0,0 +1,69 @@
+use emjudge_judgecore::{
+    quantity::ProcessResource,
+    result::{EvalVerdict, RunAndEvalResult},
+    score::{Aggregation, ProblemScoring, Subtask},
+};
+
+fn checked(output: &str) -> RunAndEvalResult {
+    let mut eval = ProcessResource::default();
+    eval.stdout = output.as_bytes().to_vec();
+    let verdict = EvalVerdict::from_process_resource(&eval);
+    RunAndEvalResult::Ok(ProcessResource::default(), eval, verdict)
+}
+
+fn subtask(name: &str, points: f64, tests: Vec<usize>, dependencies: Vec<&str>) -> Subtask {
+    Subtask {
+        name: name.to_string(),
+        points,
+        tests,
+        test_weights: vec![],
+        aggregation: Aggregation::Min,
+        dependencies: dependencies.into_iter().map(|i| i.to_string()).collect(),
+    }
+}
+
+#[test]
+fn subtasks_with_dependencies() {
+    let results = vec![
+        checked("AC"),
+        checked("AC"),
+        checked("PARTIAL 0.5"),
+        checked("WA"),
+        RunAndEvalResult::Skipped,
+        checked("PARTIAL 0.25"),
+    ];
+    let mut partial = subtask("partial", 40.0, vec![2, 5], vec![]);
+    partial.aggregation = Aggregation::Sum;
+    let scoring = ProblemScoring {
+        subtasks: vec![
+            subtask("samples", 10.0, vec![0, 1], vec![]),
+            subtask("small", 20.0, vec![0, 2], vec!["samples"]),
+            subtask("large", 30.0, vec![3, 4], vec!["small"]),
+            partial,
+            subtask("all", 5.0, vec![0], vec!["large", "samples"]),
+        ],
+    };
+    let report = scoring.score(&results).unwrap();
+    let scores: Vec<f64> = report.subtasks.iter().map(|i| i.score).collect();
+    assert_eq!(scores, vec![10.0, 10.0, 0.0, 15.0, 0.0]);
+    assert_eq!(report.score, 35.0);
+    assert_eq!(report.points, 105.0);
+    assert_eq!(report.subtasks[2].blocked_by, vec!["small"]);
+    assert_eq!(report.subtasks[4].blocked_by, vec!["large", "small"]);
+}
+
+#[test]
+fn invalid_scoring() {
+    let results = vec![checked("AC")];
+    let scoring = ProblemScoring {
+        subtasks: vec![subtask("a", 1.0, vec![1], vec![])],
+    };
+    assert!(scoring.score(&results).is_err());
+    let scoring = ProblemScoring {
+        subtasks: vec![
+            subtask("a", 1.0, vec![0], vec!["b"]),
+            subtask("b", 1.0, vec![0], vec!["a"]),
+        ],
+    };
+    assert!(scoring.score(&results).is_err());
+}
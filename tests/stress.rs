@@ -0,0 +1,86 @@
+use emjudge_judgecore::{
+    program::RawCode,
+    quantity::{MemorySize, TimeSpan},
+    result::{EvalStatus, StressResult},
+    settings::{create_a_tmp_user_return_uid, CompileAndExeSettings},
+    test::{ProgramLimits, RunOptions, Stress},
+};
+use tokio::io::AsyncReadExt;
+
+async fn read(path: &str) -> Vec<u8> {
+    let mut script = vec![];
+    tokio::fs::File::open(path)
+        .await
+        .unwrap()
+        .read_to_end(&mut script)
+        .await
+        .unwrap();
+    script
+}
+
+// The candidate adds in 32 bits, so it overflows on some of the inputs.
+#[tokio::test(flavor = "current_thread")]
+async fn overflow() {
+    let compile_and_exe_settings = CompileAndExeSettings::load_from_file(
+        "examples/compile_and_exe_settings.toml",
+        config::FileFormat::Toml,
+    )
+    .unwrap();
+    let cpp = compile_and_exe_settings.get_language("C++").unwrap();
+    let generator_script = read("examples/programs/stress/gen.cpp").await;
+    let reference_script = read("examples/programs/stress/brute.cpp").await;
+    let candidate_script = read("examples/programs/aplusb/tested.cpp").await;
+    let eval_script = read("examples/programs/aplusb/eval.cpp").await;
+    let candidate_uid = create_a_tmp_user_return_uid("emjudge-judgecore-code").unwrap();
+    let trusted_uid = create_a_tmp_user_return_uid("emjudge-judgecore-eval").unwrap();
+    for (candidate_script, iterations, expected) in [
+        (&reference_script, 5, None),
+        (&candidate_script, 30, Some(7)),
+    ] {
+        let generator_code = RawCode::new(&generator_script, cpp);
+        let reference_code = RawCode::new(&reference_script, cpp);
+        let eval_code = RawCode::new(&eval_script, cpp);
+        let stress = Stress {
+            generator_code: &generator_code,
+            reference_code: &reference_code,
+            eval_code: &eval_code,
+            trusted_limits: ProgramLimits::new(
+                TimeSpan::from_seconds(1),
+                MemorySize::from_megabytes(256),
+                256,
+                trusted_uid,
+            ),
+            first_seed: 1,
+            iterations,
+            time_budget: TimeSpan::from_minutes(1),
+            shrink_iterations: 0,
+        };
+        let result = stress
+            .run(
+                &RawCode::new(candidate_script, cpp),
+                &ProgramLimits::new(
+                    TimeSpan::from_seconds(1),
+                    MemorySize::from_megabytes(256),
+                    256,
+                    candidate_uid,
+                ),
+                &RunOptions::new(MemorySize::from_megabytes(10)),
+            )
+            .await;
+        match (expected, result) {
+            (None, StressResult::Passed(tried)) => assert_eq!(tried, iterations),
+            (Some(seed), StressResult::Failed(tried, failure)) => {
+                assert_eq!(tried, 7);
+                assert_eq!(failure.seed, seed);
+                assert_eq!(failure.input, b"976413892 1369975286\n".to_vec());
+                assert_eq!(failure.reference_output, b"2346389178\n".to_vec());
+                assert_ne!(failure.candidate_output(), b"2346389178\n");
+                assert_eq!(
+                    failure.eval_verdict.unwrap().status,
+                    EvalStatus::WrongAnswer
+                );
+            }
+            (_, result) => panic!("Unexpected result: {}", result),
+        }
+    }
+}